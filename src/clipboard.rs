@@ -0,0 +1,122 @@
+use std::io;
+
+/* The system clipboard, for "+y and "+p. Built with the clipboard
+   feature it hands text to whichever of the platform's clipboard
+   tools is installed; without it, every call fails the same way. */
+pub struct Clipboard;
+
+#[cfg(not(feature = "clipboard"))]
+impl Clipboard {
+    pub fn set(_text: &str) -> io::Result<()> {
+        Err(Self::not_built_in())
+    }
+
+    pub fn get() -> io::Result<String> {
+        Err(Self::not_built_in())
+    }
+
+    fn not_built_in() -> io::Error {
+        io::Error::new(io::ErrorKind::Unsupported, "clipboard support not built in")
+    }
+}
+
+#[cfg(feature = "clipboard")]
+impl Clipboard {
+    pub fn set(text: &str) -> io::Result<()> {
+        tools::run(tools::COPY, Some(text)).map(|_| ())
+    }
+
+    pub fn get() -> io::Result<String> {
+        tools::run(tools::PASTE, None)
+    }
+}
+
+#[cfg(feature = "clipboard")]
+mod tools {
+    use std::{io::{self, Read, Write}, process};
+
+    #[cfg(target_os = "macos")]
+    pub const COPY: &[&[&str]] = &[&["pbcopy"]];
+    #[cfg(target_os = "macos")]
+    pub const PASTE: &[&[&str]] = &[&["pbpaste"]];
+
+    #[cfg(windows)]
+    pub const COPY: &[&[&str]] = &[&["clip"]];
+    #[cfg(windows)]
+    pub const PASTE: &[&[&str]] = &[&["powershell", "-NoProfile", "-Command", "Get-Clipboard"]];
+
+    /* Wayland first, since X tools can run there too but miss what
+       Wayland programs copied. */
+    #[cfg(not(any(target_os = "macos", windows)))]
+    pub const COPY: &[&[&str]] = &[
+        &["wl-copy"],
+        &["xclip", "-selection", "clipboard"],
+        &["xsel", "--clipboard", "--input"],
+    ];
+    #[cfg(not(any(target_os = "macos", windows)))]
+    pub const PASTE: &[&[&str]] = &[
+        &["wl-paste", "--no-newline"],
+        &["xclip", "-selection", "clipboard", "-o"],
+        &["xsel", "--clipboard", "--output"],
+    ];
+
+    /* The first of commands that is installed gets input, if any, and
+       answers with what it printed. */
+    pub fn run(commands: &[&[&str]], input: Option<&str>) -> io::Result<String> {
+        for command in commands {
+            /* Copying tools can linger to serve the clipboard, so
+               nothing of theirs is kept open that would wait on them. */
+            let piped = |wanted: bool| if wanted { process::Stdio::piped() } else { process::Stdio::null() };
+            let spawned = process::Command::new(command[0])
+                .args(&command[1..])
+                .stdin(piped(input.is_some()))
+                .stdout(piped(input.is_none()))
+                .stderr(process::Stdio::null())
+                .spawn();
+            let mut child = match spawned {
+                Ok(child)                                     => child,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e)                                        => return Err(e),
+            };
+
+            if let (Some(text), Some(mut stdin)) = (input, child.stdin.take()) {
+                stdin.write_all(text.as_bytes())?;
+            }
+
+            let mut output = String::new();
+            if let Some(mut stdout) = child.stdout.take() {
+                stdout.read_to_string(&mut output)?;
+            }
+
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(io::Error::other(format!("{} failed: {}", command[0], status)));
+            }
+            return Ok(output);
+        }
+
+        Err(io::Error::new(io::ErrorKind::NotFound, "no clipboard tool found"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(feature = "clipboard"))]
+    #[test]
+    fn without_the_feature_every_call_says_so() {
+        for error in [Clipboard::set("text").unwrap_err(), Clipboard::get().unwrap_err()] {
+            assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+            assert_eq!(error.to_string(), "clipboard support not built in");
+        }
+    }
+
+    #[cfg(feature = "clipboard")]
+    #[test]
+    fn with_the_feature_a_missing_tool_is_skipped_over() {
+        let missing: &[&[&str]] = &[&["rusty-spoon-no-such-tool"], &["rusty-spoon-nor-this-one"]];
+
+        assert_eq!(tools::run(missing, None).unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,415 @@
+use std::{env, fs, io, path, time};
+
+use crate::keymap::{self, Action, Keymap};
+use crate::theme::Theme;
+use crate::tui;
+
+/* Settings read from config.toml at startup. Anything the file leaves
+   out keeps its default, and so does the file as a whole when there
+   isn't one.
+
+       tab_width = 8
+       scroll_step = 5
+       scrolloff = 3
+       show_line_numbers = false
+       relative_numbers = true
+       wrap = true
+       highlight_cursor_line = false
+       list = true
+       strip_on_save = true
+       final_newline = true
+       theme = "light"
+       poll_timeout_ms = 250
+       autosave_s = 30
+       alternate_screen = false
+       key_history = 5
+       show_key_history = false
+       show_key_intervals = true
+       bell = "visual"
+       modal = true
+
+       [keys]
+       "ctrl+c" = "quit"
+
+       [normal]
+       "q" = "quit"
+       "g e" = "buffer-end"
+
+       [selection]
+       "c" = "delete-selection"
+*/
+pub struct Config {
+    pub tab_width:             usize,
+    pub poll_timeout:          time::Duration,
+    pub scroll_step:           usize,
+    /* Lines kept in view above and below the cursor. */
+    pub scrolloff:             usize,
+    pub show_line_numbers:     bool,
+    /* Together with show_line_numbers, the cursor's line keeps its
+       number and the rest count from it. */
+    pub relative_numbers:      bool,
+    pub wrap:                  bool,
+    pub highlight_cursor_line: bool,
+    /* Tabs and trailing spaces drawn as → and ·. */
+    pub list:                  bool,
+    /* Trailing whitespace goes from every line on saving. */
+    pub strip_on_save:         bool,
+    /* Files are written ending in exactly one line break. */
+    pub final_newline:         bool,
+    pub theme:                 Theme,
+    /* None, or 0 in the file, for no autosave at all. */
+    pub autosave:              Option<time::Duration>,
+    /* Off to draw inline, over whatever the shell left on screen. */
+    pub alternate_screen:      bool,
+    /* How many of the last keys are kept, and whether the status bar
+       shows them. */
+    pub key_history:           usize,
+    pub show_key_history:      bool,
+    /* Milliseconds between keys, for chasing input lag. */
+    pub show_key_intervals:    bool,
+    /* What a key that does nothing, or a move that goes nowhere,
+       sets off. */
+    pub bell:                  Bell,
+    /* Starts in Normal mode, with [normal] keys as commands. */
+    pub modal:                 bool,
+    pub keymap:                Keymap,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tab_width:             4,
+            poll_timeout:          tui::DEFAULT_POLL_TIMEOUT,
+            scroll_step:           3,
+            scrolloff:             0,
+            show_line_numbers:     true,
+            relative_numbers:      false,
+            wrap:                  false,
+            highlight_cursor_line: true,
+            list:                  false,
+            strip_on_save:         false,
+            final_newline:         false,
+            theme:                 Theme::default(),
+            autosave:              None,
+            alternate_screen:      true,
+            key_history:           3,
+            show_key_history:      true,
+            show_key_intervals:    false,
+            bell:                  Bell::Off,
+            modal:                 false,
+            keymap:                Keymap::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> io::Result<Self> {
+        match config_path() {
+            Some(file_path) => Self::load_from(&file_path),
+            None            => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(file_path: &path::Path) -> io::Result<Self> {
+        match fs::read_to_string(file_path) {
+            Ok(text)                                      => Self::parse(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e)                                        => Err(e),
+        }
+    }
+
+    /* Understands just enough TOML for the settings above: tables,
+       key = value pairs, integers, booleans, strings and comments. */
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut config = Self::default();
+        let mut table = String::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let invalid = |what: &str| io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("config.toml, line {}: {}", index + 1, what),
+            );
+
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                table = name.trim().to_owned();
+                continue;
+            }
+
+            let (key, value) = line.split_once('=')
+                .ok_or_else(|| invalid("expected key = value"))?;
+            let key = unquote(key.trim()).ok_or_else(|| invalid("unterminated string"))?;
+            let value = Value::parse(value.trim()).ok_or_else(|| invalid("unrecognized value"))?;
+
+            match (table.as_str(), key) {
+                ("", "tab_width") =>
+                    config.tab_width = value.as_count().ok_or_else(|| invalid("expected a number"))?,
+                ("", "scroll_step") =>
+                    config.scroll_step = value.as_count().ok_or_else(|| invalid("expected a number"))?,
+                ("", "scrolloff") =>
+                    config.scrolloff = value.as_count().ok_or_else(|| invalid("expected a number"))?,
+                ("", "poll_timeout_ms") =>
+                    config.poll_timeout = value.as_count()
+                        .map(|millis| time::Duration::from_millis(millis as u64))
+                        .ok_or_else(|| invalid("expected a number"))?,
+                ("", "autosave_s") =>
+                    config.autosave = value.as_count()
+                        .map(|seconds| Some(time::Duration::from_secs(seconds as u64)).filter(|every| !every.is_zero()))
+                        .ok_or_else(|| invalid("expected a number"))?,
+                ("", "alternate_screen") =>
+                    config.alternate_screen = value.as_boolean().ok_or_else(|| invalid("expected true or false"))?,
+                ("", "key_history") =>
+                    config.key_history = value.as_count().ok_or_else(|| invalid("expected a number"))?,
+                ("", "show_key_history") =>
+                    config.show_key_history = value.as_boolean().ok_or_else(|| invalid("expected true or false"))?,
+                ("", "show_key_intervals") =>
+                    config.show_key_intervals = value.as_boolean().ok_or_else(|| invalid("expected true or false"))?,
+                ("", "bell") =>
+                    config.bell = value.as_str()
+                        .and_then(Bell::from_name)
+                        .ok_or_else(|| invalid("expected \"audible\", \"visual\" or \"none\""))?,
+                ("", "modal") =>
+                    config.modal = value.as_boolean().ok_or_else(|| invalid("expected true or false"))?,
+                ("", "show_line_numbers") =>
+                    config.show_line_numbers = value.as_boolean().ok_or_else(|| invalid("expected true or false"))?,
+                ("", "relative_numbers") =>
+                    config.relative_numbers = value.as_boolean().ok_or_else(|| invalid("expected true or false"))?,
+                ("", "wrap") =>
+                    config.wrap = value.as_boolean().ok_or_else(|| invalid("expected true or false"))?,
+                ("", "highlight_cursor_line") =>
+                    config.highlight_cursor_line = value.as_boolean().ok_or_else(|| invalid("expected true or false"))?,
+                ("", "list") =>
+                    config.list = value.as_boolean().ok_or_else(|| invalid("expected true or false"))?,
+                ("", "strip_on_save") =>
+                    config.strip_on_save = value.as_boolean().ok_or_else(|| invalid("expected true or false"))?,
+                ("", "final_newline") =>
+                    config.final_newline = value.as_boolean().ok_or_else(|| invalid("expected true or false"))?,
+                ("", "theme") =>
+                    config.theme = value.as_str()
+                        .and_then(Theme::from_name)
+                        .ok_or_else(|| invalid("expected \"dark\" or \"light\""))?,
+                ("keys", key) => {
+                    let (code, modifiers) = keymap::parse_key(key)
+                        .ok_or_else(|| invalid("unrecognized key"))?;
+                    let action = value.as_str()
+                        .and_then(Action::from_name)
+                        .ok_or_else(|| invalid("unrecognized action"))?;
+                    config.keymap = config.keymap.bind(code, modifiers, action);
+                }
+                ("selection", key) => {
+                    let (code, modifiers) = keymap::parse_key(key)
+                        .ok_or_else(|| invalid("unrecognized key"))?;
+                    let action = value.as_str()
+                        .and_then(Action::from_name)
+                        .ok_or_else(|| invalid("unrecognized action"))?;
+                    config.keymap = config.keymap.bind_selection(code, modifiers, action);
+                }
+                ("normal", keys) => {
+                    let keys = keymap::parse_keys(keys)
+                        .ok_or_else(|| invalid("unrecognized key"))?;
+                    let action = value.as_str()
+                        .and_then(Action::from_name)
+                        .ok_or_else(|| invalid("unrecognized action"))?;
+                    config.keymap = match keys.as_slice() {
+                        &[key]         => config.keymap.bind_normal(key.code, key.modifiers, action),
+                        &[first, then] => config.keymap.bind_sequence(first, then, action),
+                        _otherwise     => return Err(invalid("expected one key or two")),
+                    };
+                }
+                _otherwise =>
+                    return Err(invalid(&format!("unknown setting {}", key))),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/* The terminal's own bell, or the status bar flashing for a moment
+   instead, for those who would rather not hear it. */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bell {
+    Audible,
+    Visual,
+    Off,
+}
+
+impl Bell {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "audible"  => Some(Bell::Audible),
+            "visual"   => Some(Bell::Visual),
+            "none"     => Some(Bell::Off),
+            _otherwise => None,
+        }
+    }
+}
+
+/* $XDG_CONFIG_HOME/rusty_spoon/config.toml, or under ~/.config when
+   that isn't set. */
+fn config_path() -> Option<path::PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|home| !home.is_empty())
+        .map(path::PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| path::PathBuf::from(home).join(".config")))?;
+
+    Some(config_home.join("rusty_spoon").join("config.toml"))
+}
+
+enum Value {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+impl Value {
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "true"  => Some(Value::Boolean(true)),
+            "false" => Some(Value::Boolean(false)),
+            quoted if quoted.starts_with('"') =>
+                unquote(quoted).map(|text| Value::String(text.to_owned())),
+            number =>
+                number.replace('_', "").parse().ok().map(Value::Integer),
+        }
+    }
+
+    fn as_count(&self) -> Option<usize> {
+        match self {
+            Value::Integer(n) => usize::try_from(*n).ok(),
+            _otherwise        => None,
+        }
+    }
+
+    fn as_boolean(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(b) => Some(*b),
+            _otherwise        => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _otherwise       => None,
+        }
+    }
+}
+
+/* Bare keys come back as they are; no escapes inside quotes. */
+fn unquote(text: &str) -> Option<&str> {
+    match text.strip_prefix('"') {
+        Some(rest) => rest.strip_suffix('"'),
+        None       => Some(text),
+    }
+}
+
+/* The line up to a # that is not inside a string. */
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"'            => quoted = !quoted,
+            '#' if !quoted => return &line[..index],
+            _otherwise     => (),
+        }
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    #[test]
+    fn settings_left_out_keep_their_defaults() {
+        let config = Config::parse("tab_width = 8\nwrap = true\ntheme = \"light\"\n").unwrap();
+        let default = Config::default();
+
+        assert_eq!(config.tab_width, 8);
+        assert!(config.wrap);
+        assert_eq!(config.theme, Theme::default_light());
+
+        assert_eq!(config.scroll_step, default.scroll_step);
+        assert_eq!(config.scrolloff, default.scrolloff);
+        assert_eq!(config.poll_timeout, default.poll_timeout);
+        assert_eq!(config.show_line_numbers, default.show_line_numbers);
+        assert_eq!(config.highlight_cursor_line, default.highlight_cursor_line);
+        assert_eq!(config.autosave, default.autosave);
+        assert_eq!(config.bell, default.bell);
+        assert_eq!(config.modal, default.modal);
+    }
+
+    #[test]
+    fn an_empty_file_is_all_defaults() {
+        let config = Config::parse("# nothing to see here\n\n").unwrap();
+        let default = Config::default();
+
+        assert_eq!(config.tab_width, default.tab_width);
+        assert_eq!(config.theme, default.theme);
+        assert_eq!(config.autosave, default.autosave);
+    }
+
+    #[test]
+    fn a_missing_file_is_all_defaults() {
+        let config = Config::load_from(path::Path::new("/no/such/rusty_spoon/config.toml")).unwrap();
+
+        assert_eq!(config.tab_width, Config::default().tab_width);
+    }
+
+    #[test]
+    fn values_come_as_toml_spells_them() {
+        let text = "
+            poll_timeout_ms = 1_000  # a second
+            autosave_s = 0
+            bell = \"visual\"
+            \"scroll_step\" = 5
+        ";
+        let config = Config::parse(text).unwrap();
+
+        assert_eq!(config.poll_timeout, time::Duration::from_secs(1));
+        assert_eq!(config.autosave, None);
+        assert_eq!(config.bell, Bell::Visual);
+        assert_eq!(config.scroll_step, 5);
+    }
+
+    #[test]
+    fn tables_bind_keys() {
+        let text = "[keys]\n\"ctrl+c\" = \"quit\"\n[normal]\n\"g e\" = \"buffer-end\"\n";
+        let keymap = Config::parse(text).unwrap().keymap;
+        let g = KeyEvent::new(KeyCode::Char('g'), KeyModifiers::NONE).into();
+        let e = KeyEvent::new(KeyCode::Char('e'), KeyModifiers::NONE).into();
+
+        assert_eq!(keymap.lookup(&KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL).into()), Some(Action::Quit));
+        assert_eq!(keymap.lookup_sequence(&g, &e), Some(Action::BufferEnd));
+    }
+
+    #[test]
+    fn mistakes_are_reported_with_their_line() {
+        for (text, expected) in [
+            ("tab_width = 4\ntab_width = \"wide\"", "line 2: expected a number"),
+            ("colour = 3", "line 1: unknown setting colour"),
+            ("wrap", "line 1: expected key = value"),
+            ("bell = \"loud\"", "line 1: expected \"audible\", \"visual\" or \"none\""),
+            ("[keys]\n\"hyper+x\" = \"quit\"", "line 2: unrecognized key"),
+        ] {
+            let error = Config::parse(text).err().unwrap();
+
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert!(error.to_string().ends_with(expected), "{:?} gave {}", text, error);
+        }
+    }
+
+    #[test]
+    fn a_zero_poll_timeout_is_allowed() {
+        let config = Config::parse("poll_timeout_ms = 0").unwrap();
+
+        assert_eq!(config.poll_timeout, time::Duration::ZERO);
+    }
+}
@@ -1,13 +1,13 @@
-use std::io;
+use std::{cmp, collections::{HashMap, VecDeque}, io, mem, panic, thread, time::{Duration, Instant}};
+use std::sync::{mpsc, Arc, Mutex};
 
-use crossterm::terminal;
-
-/* I want to be able to subscribe. */
 pub enum Cmd<Msg: Clone> {
     None,
-    Suspend(Box<dyn FnOnce() -> io::Result<Msg>>),
+    Suspend(Box<dyn FnOnce() -> io::Result<Msg> + Send>),
     Dispatch(Msg),
+    Delay(Duration, Msg),
     AndThen(Box<Cmd<Msg>>, Box<Cmd<Msg>>),
+    Batch(Vec<Cmd<Msg>>),
     Gtfo,
 }
 
@@ -16,33 +16,223 @@ impl <Msg: Clone> Cmd<Msg> {
 
     pub fn suspend<F>(effect: F) -> Cmd<Msg> 
     where
-        F: FnOnce() -> io::Result<Msg> + Sized + 'static,
+        F: FnOnce() -> io::Result<Msg> + Send + 'static,
     {
         Cmd::Suspend(Box::new(effect))
     }
 
+    /* For an effect that cannot fail, or that makes a message of its
+       own failures, as Resource::fetch does. */
+    pub fn perform<F>(effect: F) -> Cmd<Msg>
+    where
+        F: FnOnce() -> Msg + Send + 'static,
+    {
+        Cmd::suspend(move || Ok(effect()))
+    }
+
     pub fn dispatch(message: Msg) -> Cmd<Msg> { Cmd::Dispatch(message) }
 
+    /* Dispatches message once after has passed. Nothing waits for it;
+       the loop keeps taking input meanwhile. */
+    pub fn delay(after: Duration, message: Msg) -> Cmd<Msg> { Cmd::Delay(after, message) }
+
     pub fn and_then(self, then: Cmd<Msg>) -> Cmd<Msg> {
         Cmd::AndThen(Box::new(then), Box::new(self))
     }
 
+    /* Starts them left to right, none of them waiting on the others;
+       effects among them may well finish in some other order. Whatever
+       the batch is followed by waits for all of them. Without workers
+       that comes to each one running to completion before the next. */
+    pub fn batch(cmds: Vec<Cmd<Msg>>) -> Cmd<Msg> {
+        Cmd::Batch(cmds)
+    }
+
     pub fn gtfo() -> Self { Cmd::Gtfo }
+
+    /* The same command, with whatever message it comes up with passed
+       through f first; for a part of the application to hand its
+       commands up to the whole, in the whole's own messages. */
+    pub fn map<N, F>(self, f: F) -> Cmd<N>
+    where
+        N: Clone + 'static,
+        F: Fn(Msg) -> N + Send + Sync + 'static,
+        Msg: 'static,
+    {
+        self.map_with(Arc::new(f))
+    }
+
+    fn map_with<N>(self, f: Arc<dyn Fn(Msg) -> N + Send + Sync>) -> Cmd<N>
+    where
+        N: Clone + 'static,
+        Msg: 'static,
+    {
+        match self {
+            Cmd::None                  => Cmd::None,
+            Cmd::Suspend(effect)       => Cmd::suspend(move || effect().map(|message| f(message))),
+            Cmd::Dispatch(message)     => Cmd::Dispatch(f(message)),
+            Cmd::Delay(after, message) => Cmd::Delay(after, f(message)),
+            Cmd::AndThen(this, that)   =>
+                Cmd::AndThen(Box::new(this.map_with(f.clone())), Box::new(that.map_with(f))),
+            Cmd::Batch(cmds)           =>
+                Cmd::Batch(cmds.into_iter().map(|cmd| cmd.map_with(f.clone())).collect()),
+            Cmd::Gtfo                  => Cmd::Gtfo,
+        }
+    }
+}
+
+/* Recurring inputs, as opposed to the one-shot effects of Cmd. These
+   are asked for anew after every update, so an application can stop
+   a subscription simply by no longer returning it. */
+pub enum Sub<Msg> {
+    None,
+    Interval(Duration, Box<dyn Fn(Instant) -> Msg>),
+    AnimationFrame(Box<dyn Fn(usize) -> Msg>),
+    Batch(Vec<Sub<Msg>>),
+}
+
+/* Often enough for a spinner to look like it spins. */
+const ANIMATION_FRAME: Duration = Duration::from_millis(100);
+
+impl <Msg> Sub<Msg> {
+    pub fn none() -> Self { Sub::None }
+
+    pub fn interval<F>(every: Duration, to_msg: F) -> Self
+    where
+        F: Fn(Instant) -> Msg + 'static,
+    {
+        Sub::Interval(every, Box::new(to_msg))
+    }
+
+    /* Counts frames from 0, the first right away, and starts over
+       whenever the subscription is taken up again. */
+    pub fn animation_frame<F>(to_msg: F) -> Self
+    where
+        F: Fn(usize) -> Msg + 'static,
+    {
+        Sub::AnimationFrame(Box::new(to_msg))
+    }
+
+    pub fn batch(subscriptions: Vec<Sub<Msg>>) -> Self { Sub::Batch(subscriptions) }
+
+    fn intervals(&self) -> Vec<(Duration, &dyn Fn(Instant) -> Msg)> {
+        match self {
+            Sub::Interval(every, to_msg) => vec![(*every, to_msg.as_ref())],
+            Sub::Batch(subscriptions)    =>
+                subscriptions.iter().flat_map(|sub| sub.intervals()).collect(),
+            _otherwise                   => vec![],
+        }
+    }
+
+    fn animations(&self) -> Vec<&dyn Fn(usize) -> Msg> {
+        match self {
+            Sub::AnimationFrame(to_msg) => vec![to_msg.as_ref()],
+            Sub::Batch(subscriptions)   =>
+                subscriptions.iter().flat_map(|sub| sub.animations()).collect(),
+            _otherwise                  => vec![],
+        }
+    }
+}
+
+/* When each subscribed interval is next due; intervals are told
+   apart by their period, and those that share one come due together.
+   Delayed messages wait here too, in the order they were scheduled,
+   and so does the next animation frame, with its number. */
+struct Timers<Msg> {
+    deadlines: HashMap<Duration, Instant>,
+    delayed:   Vec<(Instant, Msg)>,
+    animation: Option<(Instant, usize)>,
+}
+
+impl <Msg> Timers<Msg> {
+    fn new() -> Self {
+        Self { deadlines: HashMap::new(), delayed: vec![], animation: None }
+    }
+
+    fn schedule(&mut self, due: Instant, message: Msg) {
+        self.delayed.push((due, message));
+    }
+
+    /* The message of a delay or an interval that has come due, if
+       any, and otherwise how long until the next one does. */
+    fn poll(&mut self, subscriptions: &Sub<Msg>, now: Instant) -> (Option<Msg>, Option<Duration>) {
+        let intervals = subscriptions.intervals();
+
+        self.deadlines.retain(|every, _| intervals.iter().any(|(other, _)| every == other));
+        for (every, _) in &intervals {
+            self.deadlines.entry(*every).or_insert(now + *every);
+        }
+
+        /* Intervals that are due queue up behind the delays, so that
+           each gets its turn however many come due at once. */
+        for (every, deadline) in self.deadlines.iter_mut() {
+            if *deadline <= now {
+                *deadline = now + *every;
+                for (_, to_msg) in intervals.iter().filter(|(other, _)| other == every) {
+                    self.delayed.push((now, to_msg(now)));
+                }
+            }
+        }
+
+        /* Nobody watching means no frames, so the loop gets to sleep. */
+        let animations = subscriptions.animations();
+        if animations.is_empty() {
+            self.animation = None;
+        } else {
+            let (due, frame) = self.animation.get_or_insert((now, 0));
+            if *due <= now {
+                for to_msg in &animations {
+                    self.delayed.push((now, to_msg(*frame)));
+                }
+                *due = now + ANIMATION_FRAME;
+                *frame += 1;
+            }
+        }
+
+        if let Some(index) = self.delayed.iter().position(|(due, _)| *due <= now) {
+            return (Some(self.delayed.remove(index).1), None);
+        }
+
+        let wait = self.deadlines.values()
+            .chain(self.delayed.iter().map(|(due, _)| due))
+            .chain(self.animation.iter().map(|(due, _)| due))
+            .map(|deadline| deadline.saturating_duration_since(now))
+            .min();
+
+        (None, wait)
+    }
 }
 
 pub trait Application: Sized {
     type Msg: Clone;
     type View;
+    type Flags;
 
-    fn init() -> (Self, Cmd<Self::Msg>);
+    fn init(flags: Self::Flags) -> (Self, Cmd<Self::Msg>);
 
     fn update(&mut self, msg: &Self::Msg) -> Cmd<Self::Msg>;
 
-    fn view(&self, out: &Self::View) -> io::Result<()>;
+    fn view(&self, out: &mut Self::View) -> io::Result<()>;
+
+    fn subscriptions(&self) -> Sub<Self::Msg> { Sub::none() }
+
+    /* Waiting for input failed. Whatever this returns carries on as
+       if it came from update; an Err ends the loop. Out of the box,
+       timeouts and interruptions are shrugged off, the rest is
+       fatal. */
+    fn on_poll_error(&mut self, error: io::Error) -> io::Result<Cmd<Self::Msg>> {
+        match error.kind() {
+            io::ErrorKind::TimedOut    |
+            io::ErrorKind::Interrupted |
+            io::ErrorKind::WouldBlock  => Ok(Cmd::none()),
+            _otherwise                 => Err(error),
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub enum Resource<A> {
+    #[default]
     Unknown,
     Present(A),
     Failed(String),
@@ -51,75 +241,1012 @@ pub enum Resource<A> {
 impl <A> Resource<A> {
     pub fn fetch<F, G, Msg>(effect: F, as_msg: G) -> Cmd<Msg> 
     where 
-        F: FnOnce() -> io::Result<A> + Sized + 'static,
-        G: FnOnce(Self) -> Msg + 'static,
+        F: FnOnce() -> io::Result<A> + Send + 'static,
+        G: FnOnce(Self) -> Msg + Send + 'static,
         Msg: Clone,
     {
-        Cmd::suspend(||
+        Cmd::perform(||
             match effect() {
-                Ok(a)  => Ok(as_msg(Resource::Present(a))),
-                Err(e) => Ok(as_msg(Resource::Failed(e.to_string()))),
+                Ok(a)  => as_msg(Resource::Present(a)),
+                Err(e) => as_msg(Resource::Failed(e.to_string())),
             }
         )
     }
 
-    fn present(&self) -> Option<&A> {
+    /* Not yet in, which for a fetch means still under way. */
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, Self::Unknown)
+    }
+
+    pub fn is_present(&self) -> bool {
+        matches!(self, Self::Present(_))
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(self, Self::Failed(_))
+    }
+
+    pub fn as_present(&self) -> Option<&A> {
         match self {
             Self::Present(x) => Some(x),
             _otherwise       => None,
         }
     }
+
+    pub fn error(&self) -> Option<&str> {
+        match self {
+            Self::Failed(error) => Some(error),
+            _otherwise          => None,
+        }
+    }
+
+    /* Unknown and Failed stay as they are; only what is present gets
+       passed to f. */
+    pub fn map<B, F>(self, f: F) -> Resource<B>
+    where
+        F: FnOnce(A) -> B,
+    {
+        match self {
+            Resource::Unknown       => Resource::Unknown,
+            Resource::Present(a)    => Resource::Present(f(a)),
+            Resource::Failed(error) => Resource::Failed(error),
+        }
+    }
+
+    /* The same, for an f that can itself come up empty or fail. */
+    pub fn and_then<B, F>(self, f: F) -> Resource<B>
+    where
+        F: FnOnce(A) -> Resource<B>,
+    {
+        match self {
+            Resource::Unknown       => Resource::Unknown,
+            Resource::Present(a)    => f(a),
+            Resource::Failed(error) => Resource::Failed(error),
+        }
+    }
+}
+
+/* Work for a worker thread, and what comes of it: the message, as
+   the effect put it, and which effect it was. */
+type Job = Box<dyn FnOnce() + Send>;
+type Done<Msg> = (usize, io::Result<Msg>);
+
+/* What is left to do after the command at hand, last first: more
+   commands, or word to a batch that one of its own is through. */
+enum Step<Msg: Clone> {
+    Run(Box<Cmd<Msg>>),
+    Join(usize),
+}
+
+type Rest<Msg> = Vec<Step<Msg>>;
+
+/* Batches still running: how many of each are not through yet, and
+   what comes after once they all are. */
+struct Joins<Msg: Clone> {
+    pending: HashMap<usize, (usize, Rest<Msg>)>,
+    next:    usize,
+}
+
+impl <Msg: Clone> Joins<Msg> {
+    fn new() -> Self {
+        Self { pending: HashMap::new(), next: 0 }
+    }
+
+    fn open(&mut self, members: usize, rest: Rest<Msg>) -> usize {
+        let id = self.next;
+        self.next += 1;
+        self.pending.insert(id, (members, rest));
+        id
+    }
+
+    /* What comes after the batch, once the last of it is through. */
+    fn close(&mut self, id: usize) -> Option<Rest<Msg>> {
+        let (members, _) = self.pending.get_mut(&id)?;
+        *members -= 1;
+        if *members > 0 {
+            return None;
+        }
+        self.pending.remove(&id).map(|(_, rest)| rest)
+    }
 }
 
-impl <A> Default for Resource<A> {
-    fn default() -> Self { Self::Unknown }
+const WORKER_THREADS: usize = 4;
+
+/* How long the loop waits on input or on the workers before it looks
+   at the other. */
+const WORKER_SLICE: Duration = Duration::from_millis(10);
+
+/* How long effects still running when the loop ends are given to
+   finish; long enough for a save, not for a read that hangs. */
+const WORKER_GRACE: Duration = Duration::from_secs(1);
+
+/* Threads that suspended effects run on. The commands an effect was
+   followed by wait here until it is done, so that and_then still
+   means after, even though the loop goes on to other things in
+   between. Whatever is still running when the loop ends gets a
+   moment to finish, an effect being as likely as not a save, and is
+   then left behind rather than waited on for good. */
+struct Workers<Msg: Clone> {
+    jobs:    Option<mpsc::Sender<Job>>,
+    done:    mpsc::Sender<Done<Msg>>,
+    results: mpsc::Receiver<Done<Msg>>,
+    arrived: Option<Done<Msg>>,
+    waiting: HashMap<usize, Rest<Msg>>,
+    next:    usize,
+}
+
+impl <Msg: Clone + Send + 'static> Workers<Msg> {
+    fn new() -> Self {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        for _ in 0..WORKER_THREADS {
+            let queue = queue.clone();
+            thread::spawn(move || loop {
+                /* The queue is let go of before the job runs, for
+                   the next job to go to another thread. */
+                let job = match queue.lock() {
+                    Ok(queue) => queue.recv(),
+                    Err(_)    => return,
+                };
+                match job {
+                    Ok(job) => job(),
+                    Err(_)  => return,
+                }
+            });
+        }
+        let (done, results) = mpsc::channel();
+
+        Self { jobs: Some(jobs), done, results, arrived: None, waiting: HashMap::new(), next: 0 }
+    }
+
+    /* An effect that panics fails the loop, as it would have done
+       had it run there. */
+    fn start(&mut self, effect: Box<dyn FnOnce() -> io::Result<Msg> + Send>, rest: Rest<Msg>) {
+        let id = self.next;
+        self.next += 1;
+        self.waiting.insert(id, rest);
+
+        let done = self.done.clone();
+        let job = Box::new(move || {
+            let result = panic::catch_unwind(panic::AssertUnwindSafe(effect))
+                .unwrap_or_else(|_| Err(io::Error::other("effect panicked")));
+            let _ = done.send((id, result));
+        });
+        if let Some(Err(mpsc::SendError(job))) = self.jobs.as_ref().map(|jobs| jobs.send(job)) {
+            job();
+        }
+    }
+
+    fn busy(&self) -> bool {
+        !self.waiting.is_empty()
+    }
+
+    /* An effect that is done, with the commands that were waiting on
+       it. */
+    fn finished(&mut self) -> Option<(Rest<Msg>, io::Result<Msg>)> {
+        let (id, result) = self.arrived.take().or_else(|| self.results.try_recv().ok())?;
+        Some((self.waiting.remove(&id).unwrap_or_default(), result))
+    }
+
+    /* Until one is done, or timeout is up; finished has it then. */
+    fn wait(&mut self, timeout: Duration) {
+        if self.arrived.is_none() {
+            self.arrived = self.results.recv_timeout(timeout).ok();
+        }
+    }
+}
+
+/* Threads that are idle see the queue close and end; those still
+   busy are waited on until WORKER_GRACE is up and not after. */
+impl <Msg: Clone> Drop for Workers<Msg> {
+    fn drop(&mut self) {
+        self.jobs = None;
+        let deadline = Instant::now() + WORKER_GRACE;
+        let mut running = self.waiting.len() - usize::from(self.arrived.is_some());
+        while running > 0 {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.results.recv_timeout(left) {
+                Ok(_)  => running -= 1,
+                Err(_) => break,
+            }
+        }
+    }
 }
 
 pub trait Host {
     type Event;
-    type Display;
 
-    fn poll_events(&self) -> io::Result<Self::Event>;
+    /* What the application views into, a frame at a time. */
+    type Frame: Default;
+
+    /* None when nothing happened for as long as the host cares to
+       wait; that is not an error, the loop just goes round again. */
+    fn poll_events(&self) -> io::Result<Option<Self::Event>>;
 
-    fn flush(&self, buffer: &Self::Display) -> io::Result<()>;
+    /* Whether an event arrived within timeout. */
+    fn wait_for_event(&self, timeout: Duration) -> io::Result<bool>;
 
-    fn get_display(&self) -> &Self::Display;
+    fn now(&self) -> Instant { Instant::now() }
 
-    fn run_automat<App>(&self) -> io::Result<()>
+    /* The longest the loop waits for input in one go, however far off
+       the next timer is; None to wait as long as the timers allow. */
+    fn poll_timeout(&self) -> Option<Duration> { None }
+
+    /* Whether suspended effects go off to worker threads, leaving the
+       loop to take input meanwhile, rather than holding it up until
+       they are done. */
+    fn uses_workers(&self) -> bool { true }
+
+    /* Pushes a frame out to the world, leaving it empty for the
+       next view. */
+    fn flush(&self, frame: &mut Self::Frame) -> io::Result<()>;
+
+    /* None when no event came and the model was left alone. */
+    fn next_event<App>(&self, model: &mut App) -> io::Result<Option<Cmd<App::Msg>>>
     where 
-        App: Application<View = Self::Display>,
+        App: Application<View = Self::Frame>,
         App::Msg: From<Self::Event>
     {
-        let (mut model, mut cmd) = App::init();
-        let mut cmd_stack = vec![];
-
-        /* The trio of .get_display, .view, and .commit_xxx
-           could probably be summed up with CommandBuffer to make 
-           it more principled. */
-        let screen = self.get_display();
-
-        loop {
-            model.view(&screen)?;
-            self.flush(&screen)?;
-
-            cmd = match cmd {
-                Cmd::Suspend(effect)     => model.update(&effect()?),
-                Cmd::Dispatch(msg)       => model.update(&msg),
-                Cmd::Gtfo                => break Ok(()),
+        match self.poll_events() {
+            Ok(Some(event)) => Ok(Some(model.update(&App::Msg::from(event)))),
+            Ok(None)        => Ok(None),
+            Err(error)      => model.on_poll_error(error).map(Some),
+        }
+    }
+
+    fn run_automat<App>(&self, flags: App::Flags) -> io::Result<()>
+    where 
+        App: Application<View = Self::Frame>,
+        App::Msg: From<Self::Event> + Send + 'static
+    {
+        self.run_for::<App>(flags, usize::MAX).map(|_| ())
+    }
+
+    /* Like run_automat, but gives up after steps turns of the loop
+       and hands back the model, so that someone can look at it. */
+    fn run_for<App>(&self, flags: App::Flags, steps: usize) -> io::Result<App>
+    where 
+        App: Application<View = Self::Frame>,
+        App::Msg: From<Self::Event> + Send + 'static
+    {
+        let (mut model, mut cmd) = App::init(flags);
+        let mut cmd_stack: Rest<App::Msg> = vec![];
+        let mut strands = VecDeque::new();
+        let mut joins = Joins::new();
+        let mut timers = Timers::new();
+        let mut workers = self.uses_workers().then(Workers::new);
+
+        let mut frame = Self::Frame::default();
+
+        /* Only a model that has been touched since the last view gets
+           viewed again; turns that merely unpack an AndThen, or wait
+           in vain for input, leave the screen as it is. */
+        let mut dirty = true;
+
+        for _ in 0..steps {
+            if dirty {
+                model.view(&mut frame)?;
+                self.flush(&mut frame)?;
+                dirty = false;
+            }
+
+            let touched = match cmd {
+                Cmd::Suspend(effect)     => match &mut workers {
+                    Some(workers) => {
+                        workers.start(effect, mem::take(&mut cmd_stack));
+                        cmd = Cmd::none();
+                        continue;
+                    }
+                    None =>
+                        Some(model.update(&effect()?)),
+                },
+                Cmd::Dispatch(msg)       => Some(model.update(&msg)),
+                Cmd::Gtfo                => break,
+                Cmd::Delay(after, msg)   => {
+                    timers.schedule(self.now() + after, msg);
+                    cmd = Cmd::none();
+                    continue;
+                }
                 Cmd::AndThen(this, that) => {
-                    cmd_stack.push(this);
-                    *that
+                    cmd_stack.push(Step::Run(this));
+                    cmd = *that;
+                    continue;
+                }
+                /* Each of a batch goes its own way, with only word
+                   back to the batch to come after it; what came
+                   after the batch waits there for all of them. */
+                Cmd::Batch(cmds)         => {
+                    if !cmds.is_empty() {
+                        let id = joins.open(cmds.len(), mem::take(&mut cmd_stack));
+                        strands.extend(cmds.into_iter().map(|cmd| (cmd, vec![Step::Join(id)])));
+                    }
+                    cmd = Cmd::none();
+                    continue;
                 }
                 Cmd::None => {
-                    if let Some(cmd) = cmd_stack.pop() { *cmd } else {
-                        /* Some of these events are interesting on this level; resize,
-                           for instance, must update Screen.dimensions.
+                    match cmd_stack.pop() {
+                        Some(Step::Run(next)) => {
+                            cmd = *next;
+                            continue;
+                        }
+                        Some(Step::Join(id)) => {
+                            if let Some(rest) = joins.close(id) {
+                                cmd_stack = rest;
+                            }
+                            continue;
+                        }
+                        None => (),
+                    }
 
-                           Focus gained and lost are probably also interesting. */
-                        model.update(&self.poll_events().map(&App::Msg::from)?)
+                    /* A batch's next one starts only once the last
+                       is through or off on a worker. */
+                    if let Some((next, rest)) = strands.pop_front() {
+                        cmd = next;
+                        cmd_stack = rest;
+                        continue;
+                    }
+
+                    /* Whatever came after an effect done on a worker
+                       picks up once the effect is through. */
+                    if let Some((rest, msg)) = workers.as_mut().and_then(Workers::finished) {
+                        cmd_stack = rest;
+                        cmd = model.update(&msg?);
+                        dirty = true;
+                        continue;
+                    }
+
+                    /* Some of these events are interesting on this level; resize,
+                       for instance, must update Screen.dimensions. */
+                    let (due, wait) = timers.poll(&model.subscriptions(), self.now());
+                    let wait = match (wait, self.poll_timeout()) {
+                        (Some(wait), Some(limit)) => Some(cmp::min(wait, limit)),
+                        (wait, limit)             => wait.or(limit),
+                    };
+                    match (due, wait) {
+                        (Some(msg), _) =>
+                            Some(model.update(&msg)),
+                        /* Input cannot be waited on together with the
+                           workers, so the two take turns, a slice at a
+                           time. */
+                        (None, wait) if workers.as_ref().is_some_and(Workers::busy) => {
+                            let slice = wait.map_or(WORKER_SLICE, |wait| cmp::min(wait, WORKER_SLICE));
+                            match self.wait_for_event(slice) {
+                                Ok(true)   => self.next_event(&mut model)?,
+                                Ok(false)  => {
+                                    if let Some(workers) = &mut workers {
+                                        workers.wait(slice);
+                                    }
+                                    None
+                                }
+                                Err(error) => Some(model.on_poll_error(error)?),
+                            }
+                        }
+                        (None, Some(wait)) =>
+                            match self.wait_for_event(wait) {
+                                Ok(true)   => self.next_event(&mut model)?,
+                                Ok(false)  => None,
+                                Err(error) => Some(model.on_poll_error(error)?),
+                            },
+                        (None, None) =>
+                            self.next_event(&mut model)?,
                     }
                 }
             };
+
+            cmd = match touched {
+                Some(next) => {
+                    dirty = true;
+                    next
+                }
+                None => Cmd::none(),
+            };
+        }
+
+        Ok(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::{Cell, RefCell}, collections::VecDeque, sync::Condvar};
+
+    /* Long enough that nothing in these tests waits on it unless
+       something is wrong. */
+    const PATIENCE: Duration = Duration::from_secs(5);
+
+    /* Hands out its events, then hangs about until the loop ends.
+       Counts the frames it is asked to flush. Any errors it was given
+       come first, one a poll. */
+    struct Queue {
+        events:  RefCell<VecDeque<Msg>>,
+        errors:  RefCell<VecDeque<io::ErrorKind>>,
+        workers: bool,
+        flushed: Cell<usize>,
+        limit:   Option<Duration>,
+        waits:   RefCell<Vec<Duration>>,
+    }
+
+    impl Queue {
+        fn new(events: Vec<Msg>, workers: bool) -> Self {
+            Self {
+                events:  RefCell::new(events.into()),
+                errors:  RefCell::new(VecDeque::new()),
+                workers,
+                flushed: Cell::new(0),
+                limit:   None,
+                waits:   RefCell::new(vec![]),
+            }
+        }
+
+        fn polling_every(self, limit: Duration) -> Self {
+            Self { limit: Some(limit), ..self }
+        }
+
+        fn failing(self, errors: Vec<io::ErrorKind>) -> Self {
+            Self { errors: RefCell::new(errors.into()), ..self }
         }
     }
-}
\ No newline at end of file
+
+    impl Host for Queue {
+        type Event = Msg;
+        type Frame = ();
+
+        fn poll_events(&self) -> io::Result<Option<Msg>> {
+            match self.errors.borrow_mut().pop_front() {
+                Some(kind) => Err(io::Error::from(kind)),
+                None       => Ok(self.events.borrow_mut().pop_front()),
+            }
+        }
+
+        fn wait_for_event(&self, timeout: Duration) -> io::Result<bool> {
+            self.waits.borrow_mut().push(timeout);
+            if self.events.borrow().is_empty() {
+                thread::sleep(timeout);
+            }
+            Ok(!self.events.borrow().is_empty())
+        }
+
+        fn poll_timeout(&self) -> Option<Duration> { self.limit }
+
+        fn uses_workers(&self) -> bool { self.workers }
+
+        fn flush(&self, _frame: &mut ()) -> io::Result<()> {
+            self.flushed.set(self.flushed.get() + 1);
+            Ok(())
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Msg {
+        Key,
+        Quit,
+        Done(&'static str),
+    }
+
+    /* Writes down every message, and quits once it has seen them
+       all. */
+    struct Log {
+        seen:   Vec<Msg>,
+        expect: usize,
+        key:    Option<mpsc::Sender<()>>,
+    }
+
+    impl Application for Log {
+        type Msg = Msg;
+        type View = ();
+        type Flags = (Cmd<Msg>, usize, Option<mpsc::Sender<()>>);
+
+        fn init((cmd, expect, key): Self::Flags) -> (Self, Cmd<Msg>) {
+            (Self { seen: vec![], expect, key }, cmd)
+        }
+
+        fn update(&mut self, msg: &Msg) -> Cmd<Msg> {
+            self.seen.push(msg.clone());
+            if let (Msg::Key, Some(key)) = (msg, &self.key) {
+                let _ = key.send(());
+            }
+            match msg {
+                Msg::Quit                           => Cmd::gtfo(),
+                _ if self.seen.len() == self.expect => Cmd::gtfo(),
+                _otherwise                          => Cmd::none(),
+            }
+        }
+
+        fn view(&self, _out: &mut ()) -> io::Result<()> { Ok(()) }
+    }
+
+    fn run(cmd: Cmd<Msg>, events: Vec<Msg>, expect: usize, workers: bool) -> Vec<Msg> {
+        Queue::new(events, workers).run_for::<Log>((cmd, expect, None), 10_000).unwrap().seen
+    }
+
+    fn after(delay: Duration, name: &'static str) -> Cmd<Msg> {
+        Cmd::perform(move || {
+            thread::sleep(delay);
+            Msg::Done(name)
+        })
+    }
+
+    /* Comes back once count of its kind are all running at once, or
+       as "alone" when that is not to be. */
+    fn together(met: &Arc<(Mutex<usize>, Condvar)>, count: usize) -> Cmd<Msg> {
+        let met = met.clone();
+        Cmd::perform(move || {
+            let (arrived, all_there) = &*met;
+            let mut arrived = arrived.lock().unwrap();
+            *arrived += 1;
+            all_there.notify_all();
+            let (arrived, _) = all_there
+                .wait_timeout_while(arrived, PATIENCE, |arrived| *arrived < count)
+                .unwrap();
+            Msg::Done(if *arrived >= count { "together" } else { "alone" })
+        })
+    }
+
+    #[test]
+    fn a_slow_effect_does_not_hold_up_a_key() {
+        let (key, pressed) = mpsc::channel();
+        let slow = Cmd::perform(move || match pressed.recv_timeout(PATIENCE) {
+            Ok(())  => Msg::Done("after the key"),
+            Err(_)  => Msg::Done("gave up"),
+        });
+        let log = Queue::new(vec![Msg::Key], true)
+            .run_for::<Log>((slow, 2, Some(key)), 10_000)
+            .unwrap();
+
+        assert_eq!(log.seen, vec![Msg::Key, Msg::Done("after the key")]);
+    }
+
+    #[test]
+    fn batched_effects_run_at_the_same_time() {
+        let met = Arc::new((Mutex::new(0), Condvar::new()));
+        let cmd = Cmd::batch(vec![together(&met, 2), together(&met, 2)]);
+
+        assert_eq!(run(cmd, vec![], 2, true), vec![Msg::Done("together"); 2]);
+    }
+
+    #[test]
+    fn and_then_still_waits_for_the_effect_before_it() {
+        let cmd = after(Duration::from_millis(50), "first")
+            .and_then(after(Duration::ZERO, "second"));
+
+        assert_eq!(run(cmd, vec![], 2, true), vec![Msg::Done("first"), Msg::Done("second")]);
+    }
+
+    #[test]
+    fn what_follows_a_batch_waits_for_all_of_it() {
+        let cmd = Cmd::batch(vec![after(Duration::from_millis(50), "slow"), after(Duration::ZERO, "quick")])
+            .and_then(after(Duration::ZERO, "last"));
+
+        assert_eq!(
+            run(cmd, vec![], 3, true),
+            vec![Msg::Done("quick"), Msg::Done("slow"), Msg::Done("last")],
+        );
+    }
+
+    #[test]
+    fn without_workers_a_batch_runs_left_to_right() {
+        let cmd = Cmd::batch(vec![
+            after(Duration::from_millis(50), "slow"),
+            Cmd::dispatch(Msg::Done("dispatched")),
+            after(Duration::ZERO, "quick"),
+        ]);
+
+        assert_eq!(
+            run(cmd, vec![], 3, false),
+            vec![Msg::Done("slow"), Msg::Done("dispatched"), Msg::Done("quick")],
+        );
+    }
+
+    #[test]
+    fn quitting_does_not_wait_on_an_effect_that_hangs() {
+        let started = Instant::now();
+        let seen = run(after(PATIENCE * 2, "hung"), vec![Msg::Quit], usize::MAX, true);
+
+        assert_eq!(seen, vec![Msg::Quit]);
+        assert!(started.elapsed() < PATIENCE, "took {:?}", started.elapsed());
+    }
+
+    #[test]
+    fn quitting_gives_a_quick_effect_the_time_to_finish() {
+        let (done, finished) = mpsc::channel();
+        let save = Cmd::perform(move || {
+            thread::sleep(Duration::from_millis(100));
+            let _ = done.send(());
+            Msg::Done("saved")
+        });
+        run(save, vec![Msg::Quit], usize::MAX, true);
+
+        assert!(finished.try_recv().is_ok());
+    }
+
+    #[test]
+    fn maps_reach_into_batches() {
+        let cmd = Cmd::batch(vec![Cmd::dispatch(1), Cmd::dispatch(2)])
+            .map(|n| Msg::Done(if n == 1 { "one" } else { "two" }));
+
+        assert_eq!(run(cmd, vec![], 2, false), vec![Msg::Done("one"), Msg::Done("two")]);
+    }
+
+    #[test]
+    fn a_batch_of_three_dispatches_all_reach_update_in_order() {
+        let expected = vec![Msg::Done("one"), Msg::Done("two"), Msg::Done("three")];
+        let batch = || Cmd::batch(expected.iter().cloned().map(Cmd::dispatch).collect());
+
+        assert_eq!(run(batch(), vec![], 3, false), expected);
+        assert_eq!(run(batch(), vec![], 3, true), expected);
+    }
+
+    #[test]
+    fn an_empty_batch_does_nothing() {
+        assert_eq!(run(Cmd::batch(vec![]), vec![Msg::Quit], usize::MAX, true), vec![Msg::Quit]);
+
+        let cmd = Cmd::batch(vec![]).and_then(Cmd::dispatch(Msg::Done("after")));
+        assert_eq!(run(cmd, vec![], 1, true), vec![Msg::Done("after")]);
+    }
+
+    #[test]
+    fn nothing_is_viewed_until_the_model_is_updated() {
+        let host = Queue::new(vec![], false);
+        let nothing = Cmd::none().and_then(Cmd::none()).and_then(Cmd::batch(vec![Cmd::none()]));
+        host.run_for::<Log>((nothing, usize::MAX, None), 100).unwrap();
+
+        assert_eq!(host.flushed.get(), 1);
+    }
+
+    #[test]
+    fn every_update_is_viewed() {
+        let host = Queue::new(vec![Msg::Key, Msg::Key], false);
+        let log = host.run_for::<Log>((Cmd::dispatch(Msg::Done("init")), usize::MAX, None), 100).unwrap();
+
+        assert_eq!(log.seen.len(), 3);
+        assert_eq!(host.flushed.get(), 4);
+    }
+
+    fn named(n: i32) -> Msg {
+        Msg::Done(["zero", "one", "two", "three"][n as usize])
+    }
+
+    /* Every message cmd would come up with, in order, effects run
+       where they are; delays give theirs straight away. */
+    fn messages(cmd: Cmd<Msg>) -> Vec<Msg> {
+        match cmd {
+            Cmd::None | Cmd::Gtfo      => vec![],
+            Cmd::Suspend(effect)       => vec![effect().unwrap()],
+            Cmd::Dispatch(message)     => vec![message],
+            Cmd::Delay(_, message)     => vec![message],
+            Cmd::AndThen(then, first)  => [messages(*first), messages(*then)].concat(),
+            Cmd::Batch(cmds)           => cmds.into_iter().flat_map(messages).collect(),
+        }
+    }
+
+    #[test]
+    fn map_transforms_every_message_a_command_comes_up_with() {
+        assert_eq!(messages(Cmd::dispatch(1).map(named)), vec![Msg::Done("one")]);
+        assert_eq!(messages(Cmd::perform(|| 2).map(named)), vec![Msg::Done("two")]);
+        assert_eq!(messages(Cmd::suspend(|| Ok(3)).map(named)), vec![Msg::Done("three")]);
+
+        let delayed = Cmd::delay(Duration::from_secs(5), 1).map(named);
+        assert!(matches!(delayed, Cmd::Delay(after, Msg::Done("one")) if after == Duration::from_secs(5)));
+
+        let chained = Cmd::dispatch(1).and_then(Cmd::perform(|| 2)).and_then(Cmd::dispatch(3)).map(named);
+        assert_eq!(messages(chained), vec![Msg::Done("one"), Msg::Done("two"), Msg::Done("three")]);
+    }
+
+    #[test]
+    fn map_leaves_none_and_gtfo_alone() {
+        assert!(matches!(Cmd::<i32>::none().map(named), Cmd::None));
+        assert!(matches!(Cmd::<i32>::gtfo().map(named), Cmd::Gtfo));
+    }
+
+    #[test]
+    fn map_passes_failed_effects_through() {
+        let failed = Cmd::<i32>::suspend(|| Err(io::Error::other("no luck"))).map(named);
+        let Cmd::Suspend(effect) = failed else { panic!("not an effect any more") };
+
+        assert_eq!(effect().unwrap_err().to_string(), "no luck");
+    }
+
+    #[test]
+    fn mapped_commands_run_in_the_loop() {
+        let cmd = Cmd::batch(vec![Cmd::perform(|| 1), Cmd::dispatch(2)]).and_then(Cmd::perform(|| 3)).map(named);
+
+        assert_eq!(run(cmd, vec![], 3, false), vec![Msg::Done("one"), Msg::Done("two"), Msg::Done("three")]);
+    }
+
+    #[test]
+    fn what_perform_comes_up_with_reaches_update() {
+        let perform = || Cmd::perform(|| Msg::Done("performed"));
+
+        assert_eq!(run(perform(), vec![], 1, false), vec![Msg::Done("performed")]);
+        assert_eq!(run(perform(), vec![], 1, true), vec![Msg::Done("performed")]);
+    }
+
+    #[test]
+    fn resource_accessors_tell_the_three_states_apart() {
+        let unknown = Resource::<i32>::Unknown;
+        assert!(unknown.is_unknown() && !unknown.is_present() && !unknown.is_failed());
+        assert_eq!(unknown.as_present(), None);
+        assert_eq!(unknown.error(), None);
+
+        let present = Resource::Present(7);
+        assert!(!present.is_unknown() && present.is_present() && !present.is_failed());
+        assert_eq!(present.as_present(), Some(&7));
+        assert_eq!(present.error(), None);
+
+        let failed = Resource::<i32>::Failed("no luck".to_owned());
+        assert!(!failed.is_unknown() && !failed.is_present() && failed.is_failed());
+        assert_eq!(failed.as_present(), None);
+        assert_eq!(failed.error(), Some("no luck"));
+
+        assert!(Resource::<i32>::default().is_unknown());
+    }
+
+    #[test]
+    fn fetch_comes_back_present_or_failed() {
+        let as_msg = |resource: Resource<i32>| match (resource.as_present(), resource.error()) {
+            (Some(_), _) => Msg::Done("present"),
+            (_, Some(_)) => Msg::Done("failed"),
+            _otherwise   => Msg::Done("unknown"),
+        };
+
+        assert_eq!(messages(Resource::fetch(|| Ok(1), as_msg)), vec![Msg::Done("present")]);
+        assert_eq!(messages(Resource::fetch(|| Err(io::Error::other("no")), as_msg)), vec![Msg::Done("failed")]);
+    }
+
+    #[test]
+    fn map_applies_only_to_what_is_present() {
+        assert_eq!(Resource::Present(2).map(|n| n * 10).as_present(), Some(&20));
+        assert!(Resource::<i32>::Unknown.map(|n| n * 10).is_unknown());
+        assert_eq!(Resource::<i32>::Failed("gone".to_owned()).map(|n| n * 10).error(), Some("gone"));
+    }
+
+    #[test]
+    fn map_is_not_called_unless_something_is_present() {
+        let called = Cell::new(false);
+        let _ = Resource::<i32>::Unknown.map(|n| called.set(n > 0));
+        let _ = Resource::<i32>::Failed("gone".to_owned()).and_then(|n| {
+            called.set(n > 0);
+            Resource::Present(n)
+        });
+
+        assert!(!called.get());
+    }
+
+    #[test]
+    fn and_then_takes_whatever_f_comes_up_with() {
+        let half = |n: i32| if n % 2 == 0 {
+            Resource::Present(n / 2)
+        } else {
+            Resource::Failed(format!("{} is odd", n))
+        };
+
+        assert_eq!(Resource::Present(8).and_then(half).as_present(), Some(&4));
+        assert_eq!(Resource::Present(7).and_then(half).error(), Some("7 is odd"));
+        assert!(Resource::Present(8).and_then(|_| Resource::<i32>::Unknown).is_unknown());
+        assert!(Resource::<i32>::Unknown.and_then(half).is_unknown());
+        assert_eq!(Resource::<i32>::Failed("gone".to_owned()).and_then(half).error(), Some("gone"));
+    }
+
+    #[test]
+    fn map_and_and_then_chain() {
+        let length = Resource::Present("four".to_owned())
+            .map(|text| text.len())
+            .and_then(|n| Resource::Present(n * 2))
+            .map(|n| n + 1);
+
+        assert_eq!(length.as_present(), Some(&9));
+    }
+
+    fn frames() -> Sub<usize> {
+        Sub::animation_frame(|frame| frame)
+    }
+
+    #[test]
+    fn animation_frames_count_up_a_frame_apart() {
+        let mut timers = Timers::new();
+        let start = Instant::now();
+
+        assert_eq!(timers.poll(&frames(), start), (Some(0), None));
+        assert_eq!(timers.poll(&frames(), start), (None, Some(ANIMATION_FRAME)));
+        assert_eq!(timers.poll(&frames(), start + ANIMATION_FRAME / 2), (None, Some(ANIMATION_FRAME / 2)));
+        assert_eq!(timers.poll(&frames(), start + ANIMATION_FRAME).0, Some(1));
+        assert_eq!(timers.poll(&frames(), start + ANIMATION_FRAME * 2).0, Some(2));
+    }
+
+    #[test]
+    fn no_subscription_no_frames_and_no_waking_up() {
+        let mut timers = Timers::<usize>::new();
+        let start = Instant::now();
+
+        assert_eq!(timers.poll(&Sub::none(), start), (None, None));
+        assert_eq!(timers.poll(&Sub::none(), start + ANIMATION_FRAME * 10), (None, None));
+    }
+
+    #[test]
+    fn frames_start_over_once_taken_up_again() {
+        let mut timers = Timers::new();
+        let start = Instant::now();
+        timers.poll(&frames(), start);
+        timers.poll(&frames(), start + ANIMATION_FRAME);
+
+        assert_eq!(timers.poll(&Sub::none(), start + ANIMATION_FRAME * 2), (None, None));
+        assert_eq!(timers.poll(&frames(), start + ANIMATION_FRAME * 3).0, Some(0));
+    }
+
+    fn ticks() -> Sub<Instant> {
+        Sub::interval(Duration::from_secs(1), |now| now)
+    }
+
+    #[test]
+    fn an_interval_ticks_once_a_period_by_the_clock_it_is_given() {
+        let mut timers = Timers::new();
+        let start = Instant::now();
+        let second = Duration::from_secs(1);
+
+        assert_eq!(timers.poll(&ticks(), start), (None, Some(second)));
+        assert_eq!(timers.poll(&ticks(), start + second / 4), (None, Some(second * 3 / 4)));
+        assert_eq!(timers.poll(&ticks(), start + second), (Some(start + second), None));
+        assert_eq!(timers.poll(&ticks(), start + second), (None, Some(second)));
+        assert_eq!(timers.poll(&ticks(), start + second * 2).0, Some(start + second * 2));
+    }
+
+    #[test]
+    fn a_late_tick_comes_once_and_the_next_is_a_period_after_it() {
+        let mut timers = Timers::new();
+        let start = Instant::now();
+        let second = Duration::from_secs(1);
+        timers.poll(&ticks(), start);
+
+        let late = start + second * 5 / 2;
+        assert_eq!(timers.poll(&ticks(), late), (Some(late), None));
+        assert_eq!(timers.poll(&ticks(), late), (None, Some(second)));
+    }
+
+    #[test]
+    fn intervals_due_together_each_get_their_turn() {
+        let mut timers = Timers::new();
+        let start = Instant::now();
+        let both = || Sub::batch(vec![
+            Sub::interval(Duration::from_secs(1), |_| "second"),
+            Sub::interval(Duration::from_secs(2), |_| "other second"),
+        ]);
+        timers.poll(&both(), start);
+
+        let later = start + Duration::from_secs(2);
+        let mut due = vec![timers.poll(&both(), later).0, timers.poll(&both(), later).0];
+        due.sort();
+        assert_eq!(due, [Some("other second"), Some("second")]);
+        assert_eq!(timers.poll(&both(), later), (None, Some(Duration::from_secs(1))));
+    }
+
+    #[test]
+    fn an_interval_dropped_and_taken_up_again_starts_over() {
+        let mut timers = Timers::new();
+        let start = Instant::now();
+        let second = Duration::from_secs(1);
+        timers.poll(&ticks(), start);
+
+        assert_eq!(timers.poll(&Sub::none(), start + second * 3), (None, None));
+        assert_eq!(timers.poll(&ticks(), start + second * 3), (None, Some(second)));
+    }
+
+    #[test]
+    fn timeouts_and_interruptions_are_shrugged_off_but_not_the_rest() {
+        let mut log = Log { seen: vec![], expect: 0, key: None };
+
+        for kind in [io::ErrorKind::TimedOut, io::ErrorKind::Interrupted, io::ErrorKind::WouldBlock] {
+            assert!(matches!(log.on_poll_error(io::Error::from(kind)), Ok(Cmd::None)));
+        }
+        assert!(log.on_poll_error(io::Error::from(io::ErrorKind::BrokenPipe)).is_err());
+    }
+
+    #[test]
+    fn a_timed_out_poll_does_not_end_the_loop() {
+        let timeouts = vec![io::ErrorKind::TimedOut; 3];
+        let host = Queue::new(vec![Msg::Key, Msg::Quit], false).failing(timeouts);
+
+        assert!(host.run_automat::<Log>((Cmd::none(), 0, None)).is_ok());
+        assert!(host.events.borrow().is_empty());
+    }
+
+    #[test]
+    fn a_fatal_poll_error_ends_the_loop_with_it() {
+        let host = Queue::new(vec![Msg::Key, Msg::Quit], false).failing(vec![io::ErrorKind::BrokenPipe]);
+        let error = host.run_automat::<Log>((Cmd::none(), 0, None)).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(host.events.borrow().len(), 2);
+    }
+
+    /* Turns every poll error into a message of its own. */
+    struct Forgiving(Vec<Msg>);
+
+    impl Application for Forgiving {
+        type Msg = Msg;
+        type View = ();
+        type Flags = ();
+
+        fn init(_flags: ()) -> (Self, Cmd<Msg>) { (Self(vec![]), Cmd::none()) }
+
+        fn update(&mut self, msg: &Msg) -> Cmd<Msg> {
+            self.0.push(msg.clone());
+            match msg {
+                Msg::Quit  => Cmd::gtfo(),
+                _otherwise => Cmd::none(),
+            }
+        }
+
+        fn view(&self, _out: &mut ()) -> io::Result<()> { Ok(()) }
+
+        fn on_poll_error(&mut self, _error: io::Error) -> io::Result<Cmd<Msg>> {
+            Ok(Cmd::dispatch(Msg::Done("poll error")))
+        }
+    }
+
+    #[test]
+    fn poll_errors_can_become_messages() {
+        let host = Queue::new(vec![Msg::Quit], false).failing(vec![io::ErrorKind::BrokenPipe]);
+        let app = host.run_for::<Forgiving>((), 100).unwrap();
+
+        assert_eq!(app.0, [Msg::Done("poll error"), Msg::Quit]);
+    }
+
+    #[test]
+    fn a_delayed_message_comes_only_once_the_delay_is_up() {
+        let mut timers = Timers::new();
+        let start = Instant::now();
+        let second = Duration::from_secs(1);
+        timers.schedule(start + second * 2, "later");
+        timers.schedule(start + second, "sooner");
+
+        assert_eq!(timers.poll(&Sub::none(), start), (None, Some(second)));
+        assert_eq!(timers.poll(&Sub::none(), start + second / 2), (None, Some(second / 2)));
+        assert_eq!(timers.poll(&Sub::none(), start + second), (Some("sooner"), None));
+        assert_eq!(timers.poll(&Sub::none(), start + second), (None, Some(second)));
+        assert_eq!(timers.poll(&Sub::none(), start + second * 3), (Some("later"), None));
+        assert_eq!(timers.poll(&Sub::none(), start + second * 3), (None, None));
+    }
+
+    /* Forgiving, with a tick a second to wait for. */
+    struct Ticking(Forgiving);
+
+    impl Application for Ticking {
+        type Msg = Msg;
+        type View = ();
+        type Flags = ();
+
+        fn init(_flags: ()) -> (Self, Cmd<Msg>) { (Self(Forgiving(vec![])), Cmd::none()) }
+
+        fn update(&mut self, msg: &Msg) -> Cmd<Msg> { self.0.update(msg) }
+
+        fn view(&self, _out: &mut ()) -> io::Result<()> { Ok(()) }
+
+        fn subscriptions(&self) -> Sub<Msg> {
+            Sub::interval(Duration::from_secs(1), |_| Msg::Done("tick"))
+        }
+    }
+
+    #[test]
+    fn the_poll_timeout_cuts_the_wait_for_a_timer_short() {
+        let limit = Duration::from_millis(2);
+        let host = Queue::new(vec![], false).polling_every(limit);
+        host.run_for::<Ticking>((), 5).unwrap();
+
+        let waits = host.waits.borrow();
+        assert!(!waits.is_empty());
+        assert!(waits.iter().all(|wait| *wait == limit), "{:?}", waits);
+    }
+
+    #[test]
+    fn without_timers_the_poll_timeout_is_all_the_loop_waits() {
+        let host = Queue::new(vec![], false);
+        host.run_for::<Forgiving>((), 5).unwrap();
+        assert!(host.waits.borrow().is_empty());
+
+        let limit = Duration::from_millis(2);
+        let host = Queue::new(vec![], false).polling_every(limit);
+        host.run_for::<Forgiving>((), 5).unwrap();
+        assert_eq!(*host.waits.borrow(), [limit; 5]);
+    }
+}
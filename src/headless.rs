@@ -0,0 +1,200 @@
+use std::{cell::{Cell, RefCell}, collections::VecDeque, io, rc::Rc, time};
+
+use crossterm::event;
+
+use crate::{elm, tui};
+
+/* A Host without a terminal: events come off a script instead of the
+   keyboard, and everything the application renders piles up in
+   memory. Time only moves when the host would otherwise sit and wait,
+   so timers fire without anyone actually waiting for them.
+
+   Drive it with run_for and look at the model it hands back, or at
+   output.
+
+   Effects run where the loop is, unless asked otherwise, so that
+   whatever they bring in is there before the next scripted event. */
+pub struct ScriptedHost {
+    script:  RefCell<VecDeque<event::Event>>,
+    errors:  RefCell<VecDeque<io::ErrorKind>>,
+    clock:   Cell<time::Instant>,
+    screen:  tui::Screen,
+    output:  Rc<RefCell<Vec<u8>>>,
+    workers: bool,
+    stopped: bool,
+}
+
+impl ScriptedHost {
+    pub fn new<I: IntoIterator<Item = event::Event>>(script: I) -> io::Result<Self> {
+        let (screen, output) = tui::Screen::attach_string()?;
+        Ok(Self {
+            script:  RefCell::new(script.into_iter().collect()),
+            errors:  RefCell::new(VecDeque::new()),
+            clock:   Cell::new(time::Instant::now()),
+            screen,
+            output,
+            workers: false,
+            stopped: false,
+        })
+    }
+
+    /* Effects go off to worker threads, as on a terminal, and the
+       script carries on without waiting for them. */
+    pub fn with_workers(mut self) -> Self {
+        self.workers = true;
+        self
+    }
+
+    /* Waiting no longer moves time on, so nothing that was put off
+       comes due, and what the script left behind stays for a test to
+       look at. */
+    pub fn with_clock_stopped(mut self) -> Self {
+        self.stopped = true;
+        self
+    }
+
+    pub fn push(&self, event: event::Event) {
+        self.script.borrow_mut().push_back(event);
+    }
+
+    /* The next poll fails with kind, ahead of any scripted events. */
+    pub fn fail_with(&self, kind: io::ErrorKind) {
+        self.errors.borrow_mut().push_back(kind);
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.script.borrow().is_empty() && self.errors.borrow().is_empty()
+    }
+
+    /* Every byte rendered so far, escape sequences and all. */
+    pub fn output(&self) -> Vec<u8> {
+        self.output.borrow().clone()
+    }
+
+    /* The same, as plain text. */
+    pub fn text(&self) -> String {
+        tui::strip_ansi(&self.output.borrow())
+    }
+
+    /* Forgets what was rendered so far, so the next text() shows only
+       what comes after. */
+    pub fn clear_output(&self) {
+        self.output.borrow_mut().clear();
+    }
+}
+
+impl elm::Host for ScriptedHost {
+    type Event = event::Event;
+    type Frame = tui::CommandBuffer;
+
+    fn poll_events(&self) -> io::Result<Option<Self::Event>> {
+        match self.errors.borrow_mut().pop_front() {
+            Some(kind) => Err(io::Error::from(kind)),
+            None       => Ok(self.script.borrow_mut().pop_front()),
+        }
+    }
+
+    fn wait_for_event(&self, timeout: time::Duration) -> io::Result<bool> {
+        if self.is_exhausted() {
+            if !self.stopped {
+                self.clock.set(self.clock.get() + timeout);
+            }
+            Ok(false)
+        } else {
+            Ok(true)
+        }
+    }
+
+    fn now(&self) -> time::Instant { self.clock.get() }
+
+    fn uses_workers(&self) -> bool { self.workers }
+
+    fn flush(&self, frame: &mut Self::Frame) -> io::Result<()> {
+        self.screen.commit(frame)
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::elm::{Application, Cmd, Host};
+
+    /* Counts the events it is sent and shows the count. The flags
+       are what it starts out doing. */
+    #[derive(Default)]
+    struct Counter {
+        events: usize,
+    }
+
+    #[derive(Clone)]
+    struct Counted;
+
+    impl From<event::Event> for Counted {
+        fn from(_: event::Event) -> Self { Counted }
+    }
+
+    impl Application for Counter {
+        type Msg = Counted;
+        type View = tui::CommandBuffer;
+        type Flags = Cmd<Counted>;
+
+        fn init(flags: Self::Flags) -> (Self, Cmd<Counted>) {
+            (Self::default(), flags)
+        }
+
+        fn update(&mut self, _: &Counted) -> Cmd<Counted> {
+            self.events += 1;
+            Cmd::none()
+        }
+
+        fn view(&self, out: &mut tui::CommandBuffer) -> io::Result<()> {
+            out.resize(20, 1)
+               .move_to(0, 0)?
+               .print(&format!("{} events", self.events))?;
+            Ok(())
+        }
+    }
+
+    fn key() -> event::Event {
+        event::Event::FocusGained
+    }
+
+    #[test]
+    fn scripted_events_reach_the_application() {
+        let host = ScriptedHost::new([key(), key()]).unwrap();
+        host.push(key());
+        let counter = host.run_for::<Counter>(Cmd::none(), 10).unwrap();
+
+        assert_eq!(counter.events, 3);
+        assert!(host.is_exhausted());
+        /* Each frame after the first only redraws the digit. */
+        assert_eq!(host.text().replace(' ', ""), "0events123");
+    }
+
+    #[test]
+    fn cleared_output_shows_only_what_came_after() {
+        let host = ScriptedHost::new([key()]).unwrap();
+        host.run_for::<Counter>(Cmd::none(), 10).unwrap();
+        host.clear_output();
+
+        assert!(host.output().is_empty());
+        assert_eq!(host.text(), "");
+    }
+
+    #[test]
+    fn poll_errors_come_before_the_script() {
+        let host = ScriptedHost::new([key()]).unwrap();
+        host.fail_with(io::ErrorKind::TimedOut);
+        host.fail_with(io::ErrorKind::BrokenPipe);
+
+        assert!(host.run_for::<Counter>(Cmd::none(), 10).is_err());
+        assert!(!host.is_exhausted());
+    }
+
+    #[test]
+    fn effects_can_go_to_workers() {
+        let host = ScriptedHost::new([]).unwrap().with_workers();
+        let counter = host.run_for::<Counter>(Cmd::perform(|| Counted), 100).unwrap();
+
+        assert_eq!(counter.events, 1);
+    }
+}
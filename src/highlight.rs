@@ -0,0 +1,94 @@
+use std::{ops::Range, path};
+
+use crossterm::style::Color;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Style {
+    pub foreground: Color,
+    pub bold:       bool,
+}
+
+pub type Span = (Range<usize>, Style);
+
+/* Decides how a line looks, a line at a time. Spans are char ranges
+   into the line as given, which has its tabs expanded already, and
+   do not overlap. Whatever no span covers is drawn plain. */
+pub trait Highlighter {
+    fn spans(&self, line: &str) -> Vec<Span>;
+
+    /* Whether it understands what is in file_path at all. */
+    fn handles(&self, _file_path: &path::Path) -> bool { true }
+}
+
+/* Not much of a highlighter: keywords in one colour, the rest as it
+   is. Enough to show how one plugs in. */
+pub struct RustKeywordHighlighter;
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+    "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+    "trait", "true", "type", "unsafe", "use", "where", "while",
+];
+
+const KEYWORD: Style = Style { foreground: Color::Magenta, bold: true };
+
+impl Highlighter for RustKeywordHighlighter {
+    fn spans(&self, line: &str) -> Vec<Span> {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut spans = vec![];
+        let mut column = 0;
+
+        while column < chars.len() {
+            if !is_word(chars[column]) {
+                column += 1;
+                continue;
+            }
+
+            let start = column;
+            while column < chars.len() && is_word(chars[column]) {
+                column += 1;
+            }
+
+            let word = chars[start..column].iter().collect::<String>();
+            if RUST_KEYWORDS.contains(&word.as_str()) {
+                spans.push((start..column, KEYWORD));
+            }
+        }
+
+        spans
+    }
+
+    fn handles(&self, file_path: &path::Path) -> bool {
+        file_path.extension().is_some_and(|extension| extension == "rs")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keywords(line: &str) -> Vec<Range<usize>> {
+        RustKeywordHighlighter.spans(line).into_iter().map(|(columns, _)| columns).collect()
+    }
+
+    #[test]
+    fn keywords_at_either_end_of_the_line_are_found() {
+        assert_eq!(keywords("pub fn go() -> Self"), [0..3, 4..6, 15..19]);
+        assert_eq!(keywords("fn x fn"), [0..2, 5..7]);
+    }
+
+    #[test]
+    fn keywords_inside_longer_words_are_not() {
+        assert_eq!(keywords("fnord letter _use mut_"), []);
+        assert_eq!(keywords("(mut) &mut"), [1..4, 7..10]);
+    }
+
+    #[test]
+    fn only_rust_files_are_handled() {
+        assert!(RustKeywordHighlighter.handles(path::Path::new("src/main.rs")));
+        assert!(!RustKeywordHighlighter.handles(path::Path::new("notes.txt")));
+        assert!(!RustKeywordHighlighter.handles(path::Path::new("Makefile")));
+    }
+}
@@ -0,0 +1,136 @@
+use crossterm::event::{self, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEventKind};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    LineStart,
+    LineEnd,
+}
+
+/* What a terminal event means, as far as that can be told without
+   knowing what the editor is up to. Keys that mean the same thing
+   everywhere get a name; the rest stay keys, for the keymap to make
+   sense of. So do Save, Quit and the like, which are whatever the
+   keymap says they are. */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InputEvent {
+    Char(char),
+    Move(Direction),
+    Submit,
+    Cancel,
+    Erase,
+    Key(KeyEvent),
+    Paste(String),
+    Resize { columns: u16, rows: u16 },
+    Click { column: u16, row: u16 },
+    Scroll(Direction),
+    Focus(bool),
+    Ignored,
+}
+
+impl From<KeyEvent> for InputEvent {
+    fn from(key: KeyEvent) -> Self {
+        let move_to = |direction| match key.modifiers {
+            KeyModifiers::NONE => InputEvent::Move(direction),
+            _otherwise         => InputEvent::Key(KeyEvent::new(key.code, key.modifiers)),
+        };
+
+        match (key.code, key.modifiers) {
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => InputEvent::Char(c),
+            (KeyCode::Enter, KeyModifiers::NONE)                         => InputEvent::Submit,
+            (KeyCode::Esc, KeyModifiers::NONE)                           => InputEvent::Cancel,
+            (KeyCode::Backspace, KeyModifiers::NONE)                     => InputEvent::Erase,
+            (KeyCode::Up, _)                                             => move_to(Direction::Up),
+            (KeyCode::Down, _)                                           => move_to(Direction::Down),
+            (KeyCode::Left, _)                                           => move_to(Direction::Left),
+            (KeyCode::Right, _)                                          => move_to(Direction::Right),
+            (KeyCode::PageUp, _)                                         => move_to(Direction::PageUp),
+            (KeyCode::PageDown, _)                                       => move_to(Direction::PageDown),
+            (KeyCode::Home, _)                                           => move_to(Direction::LineStart),
+            (KeyCode::End, _)                                            => move_to(Direction::LineEnd),
+            /* Only what was pressed, not how it was reported, so that
+               it finds its binding. */
+            (code, modifiers) => InputEvent::Key(KeyEvent::new(code, modifiers)),
+        }
+    }
+}
+
+impl From<event::Event> for InputEvent {
+    fn from(event: event::Event) -> Self {
+        match event {
+            event::Event::Key(key)              => key.into(),
+            event::Event::Paste(text)           => InputEvent::Paste(text),
+            event::Event::Resize(columns, rows) => InputEvent::Resize { columns, rows },
+            event::Event::FocusGained           => InputEvent::Focus(true),
+            event::Event::FocusLost             => InputEvent::Focus(false),
+            event::Event::Mouse(mouse)          => match mouse.kind {
+                MouseEventKind::Down(MouseButton::Left) =>
+                    InputEvent::Click { column: mouse.column, row: mouse.row },
+                MouseEventKind::ScrollUp   => InputEvent::Scroll(Direction::Up),
+                MouseEventKind::ScrollDown => InputEvent::Scroll(Direction::Down),
+                _otherwise                 => InputEvent::Ignored,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyEventKind, KeyEventState, MouseEvent};
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> InputEvent {
+        event::Event::Key(KeyEvent::new(code, modifiers)).into()
+    }
+
+    fn mouse(kind: MouseEventKind) -> InputEvent {
+        event::Event::Mouse(MouseEvent { kind, column: 3, row: 4, modifiers: KeyModifiers::NONE }).into()
+    }
+
+    #[test]
+    fn plain_keys_get_a_name() {
+        assert_eq!(key(KeyCode::Char('a'), KeyModifiers::NONE), InputEvent::Char('a'));
+        assert_eq!(key(KeyCode::Char('A'), KeyModifiers::SHIFT), InputEvent::Char('A'));
+        assert_eq!(key(KeyCode::Enter, KeyModifiers::NONE), InputEvent::Submit);
+        assert_eq!(key(KeyCode::Esc, KeyModifiers::NONE), InputEvent::Cancel);
+        assert_eq!(key(KeyCode::Backspace, KeyModifiers::NONE), InputEvent::Erase);
+        assert_eq!(key(KeyCode::Home, KeyModifiers::NONE), InputEvent::Move(Direction::LineStart));
+        assert_eq!(key(KeyCode::PageDown, KeyModifiers::NONE), InputEvent::Move(Direction::PageDown));
+    }
+
+    #[test]
+    fn keys_with_modifiers_are_left_for_the_keymap() {
+        let control = KeyModifiers::CONTROL;
+
+        assert_eq!(key(KeyCode::Char('s'), control), InputEvent::Key(KeyEvent::new(KeyCode::Char('s'), control)));
+        assert_eq!(key(KeyCode::Left, control), InputEvent::Key(KeyEvent::new(KeyCode::Left, control)));
+        assert_eq!(key(KeyCode::Delete, KeyModifiers::NONE), InputEvent::Key(KeyEvent::new(KeyCode::Delete, KeyModifiers::NONE)));
+    }
+
+    #[test]
+    fn how_a_key_was_reported_does_not_matter() {
+        let repeated = KeyEvent {
+            code:      KeyCode::Char('q'),
+            modifiers: KeyModifiers::CONTROL,
+            kind:      KeyEventKind::Repeat,
+            state:     KeyEventState::CAPS_LOCK,
+        };
+
+        assert_eq!(InputEvent::from(repeated), InputEvent::Key(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL)));
+    }
+
+    #[test]
+    fn the_rest_of_the_terminal_events_map_across() {
+        assert_eq!(InputEvent::from(event::Event::Resize(80, 24)), InputEvent::Resize { columns: 80, rows: 24 });
+        assert_eq!(InputEvent::from(event::Event::Paste("hi".to_owned())), InputEvent::Paste("hi".to_owned()));
+        assert_eq!(InputEvent::from(event::Event::FocusLost), InputEvent::Focus(false));
+        assert_eq!(mouse(MouseEventKind::Down(MouseButton::Left)), InputEvent::Click { column: 3, row: 4 });
+        assert_eq!(mouse(MouseEventKind::ScrollDown), InputEvent::Scroll(Direction::Down));
+        assert_eq!(mouse(MouseEventKind::Moved), InputEvent::Ignored);
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::input::InputEvent;
+
+/* Everything a key can be bound to. */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Quit,
+    Save,
+    GotoLine,
+    ShowStats,
+    Search,
+    SearchNext,
+    ClearSearch,
+    Replace,
+    CommandLine,
+    Undo,
+    Redo,
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    PageUp,
+    PageDown,
+    LineStart,
+    LineEnd,
+    CenterOnCursor,
+    BufferStart,
+    BufferEnd,
+    WordLeft,
+    WordRight,
+    MatchBracket,
+    InsertChar(char),
+    DeleteCharBefore,
+    DeleteCharAt,
+    SplitLine,
+    JoinLines,
+    OpenLineBelow,
+    OpenLineAbove,
+    TruncateLine,
+    DeleteLine,
+    StripWhitespace,
+    NextBuffer,
+    PreviousBuffer,
+    FocusOtherPane,
+    Unsplit,
+    ToggleReadOnly,
+    ToggleWrap,
+    ToggleCursorLine,
+    ToggleList,
+    ToggleKeys,
+    InsertMode,
+    Append,
+    NormalMode,
+    YankLine,
+    PasteAfter,
+    PasteBefore,
+    ToggleSelection,
+    YankSelection,
+    DeleteSelection,
+    UseClipboard,
+}
+
+impl Action {
+    /* The name a config file binds a key to; everything but
+       InsertChar, which typing takes care of. */
+    pub fn from_name(name: &str) -> Option<Self> {
+        use Action::*;
+
+        let action = match name {
+            "quit"               => Quit,
+            "save"               => Save,
+            "goto-line"          => GotoLine,
+            "show-stats"         => ShowStats,
+            "search"             => Search,
+            "search-next"        => SearchNext,
+            "clear-search"       => ClearSearch,
+            "replace"            => Replace,
+            "command-line"       => CommandLine,
+            "undo"               => Undo,
+            "redo"               => Redo,
+            "move-up"            => MoveUp,
+            "move-down"          => MoveDown,
+            "move-left"          => MoveLeft,
+            "move-right"         => MoveRight,
+            "page-up"            => PageUp,
+            "page-down"          => PageDown,
+            "line-start"         => LineStart,
+            "line-end"           => LineEnd,
+            "center-on-cursor"   => CenterOnCursor,
+            "buffer-start"       => BufferStart,
+            "buffer-end"         => BufferEnd,
+            "word-left"          => WordLeft,
+            "word-right"         => WordRight,
+            "match-bracket"      => MatchBracket,
+            "delete-char-before" => DeleteCharBefore,
+            "delete-char-at"     => DeleteCharAt,
+            "split-line"         => SplitLine,
+            "join-lines"         => JoinLines,
+            "open-line-below"    => OpenLineBelow,
+            "open-line-above"    => OpenLineAbove,
+            "truncate-line"      => TruncateLine,
+            "delete-line"        => DeleteLine,
+            "strip-whitespace"   => StripWhitespace,
+            "next-buffer"        => NextBuffer,
+            "previous-buffer"    => PreviousBuffer,
+            "focus-other-pane"   => FocusOtherPane,
+            "unsplit"            => Unsplit,
+            "toggle-read-only"   => ToggleReadOnly,
+            "toggle-wrap"        => ToggleWrap,
+            "toggle-cursor-line" => ToggleCursorLine,
+            "toggle-list"        => ToggleList,
+            "toggle-key-history" => ToggleKeys,
+            "insert-mode"        => InsertMode,
+            "append"             => Append,
+            "normal-mode"        => NormalMode,
+            "yank-line"          => YankLine,
+            "paste-after"        => PasteAfter,
+            "paste-before"       => PasteBefore,
+            "toggle-selection"   => ToggleSelection,
+            "yank-selection"     => YankSelection,
+            "delete-selection"   => DeleteSelection,
+            "use-clipboard"      => UseClipboard,
+            _otherwise           => return None,
+        };
+
+        Some(action)
+    }
+
+    /* Whether it changes the text, and so has no business in a
+       read-only buffer. */
+    pub fn edits(&self) -> bool {
+        use Action::*;
+
+        matches!(
+            self,
+            Replace | Undo | Redo | InsertChar(_) | DeleteCharBefore | DeleteCharAt | SplitLine
+                | JoinLines | OpenLineBelow | OpenLineAbove | TruncateLine | DeleteLine
+                | StripWhitespace | PasteAfter | PasteBefore | DeleteSelection
+        )
+    }
+}
+
+/* Keys one after the other, spelled with spaces between them:
+   "g g". */
+pub fn parse_keys(spelled: &str) -> Option<Vec<KeyEvent>> {
+    spelled.split_whitespace()
+        .map(|key| parse_key(key).map(|(code, modifiers)| KeyEvent::new(code, modifiers)))
+        .collect()
+}
+
+/* Keys spelled the way people write them: "ctrl+q", "f3",
+   "shift+page-down", "esc". */
+pub fn parse_key(spelled: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut parts = spelled.split('+').collect::<Vec<_>>();
+    let key = parts.pop()?;
+
+    for modifier in parts {
+        modifiers |= match modifier.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" | "meta"     => KeyModifiers::ALT,
+            "shift"            => KeyModifiers::SHIFT,
+            _otherwise         => return None,
+        };
+    }
+
+    let mut chars = key.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _otherwise      => match key.to_ascii_lowercase().as_str() {
+            "esc" | "escape" => KeyCode::Esc,
+            "enter"          => KeyCode::Enter,
+            "tab"            => KeyCode::Tab,
+            "backspace"      => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert"         => KeyCode::Insert,
+            "up"             => KeyCode::Up,
+            "down"           => KeyCode::Down,
+            "left"           => KeyCode::Left,
+            "right"          => KeyCode::Right,
+            "home"           => KeyCode::Home,
+            "end"            => KeyCode::End,
+            "page-up"        => KeyCode::PageUp,
+            "page-down"      => KeyCode::PageDown,
+            "space"          => KeyCode::Char(' '),
+            function         => KeyCode::F(function.strip_prefix('f')?.parse().ok()?),
+        },
+    };
+
+    Some((code, modifiers))
+}
+
+/* Bound by what a key means rather than the key itself, so that a
+   binding and the keypress it is for end up the same however the
+   terminal reports it. Bindings hold in every mode, except where a
+   Normal mode one takes the key over. Sequences of two keys, like gg,
+   are Normal mode only. While there is a selection, its own bindings
+   come before all of those. */
+pub struct Keymap {
+    bindings:  HashMap<InputEvent, Action>,
+    normal:    HashMap<InputEvent, Action>,
+    sequences: HashMap<(InputEvent, InputEvent), Action>,
+    selection: HashMap<InputEvent, Action>,
+}
+
+impl Keymap {
+    /* No bindings at all; typed chars still insert. */
+    pub fn empty() -> Self {
+        Self {
+            bindings:  HashMap::new(),
+            normal:    HashMap::new(),
+            sequences: HashMap::new(),
+            selection: HashMap::new(),
+        }
+    }
+
+    pub fn bind(mut self, code: KeyCode, modifiers: KeyModifiers, action: Action) -> Self {
+        self.bindings.insert(KeyEvent::new(code, modifiers).into(), action);
+        self
+    }
+
+    pub fn bind_normal(mut self, code: KeyCode, modifiers: KeyModifiers, action: Action) -> Self {
+        self.normal.insert(KeyEvent::new(code, modifiers).into(), action);
+        self
+    }
+
+    pub fn bind_selection(mut self, code: KeyCode, modifiers: KeyModifiers, action: Action) -> Self {
+        self.selection.insert(KeyEvent::new(code, modifiers).into(), action);
+        self
+    }
+
+    pub fn bind_sequence(mut self, first: KeyEvent, second: KeyEvent, action: Action) -> Self {
+        self.sequences.insert((first.into(), second.into()), action);
+        self
+    }
+
+    /* Whether some sequence starts with input, so that it is worth
+       waiting for the next key. */
+    pub fn starts_sequence(&self, input: &InputEvent) -> bool {
+        self.sequences.keys().any(|(first, _)| first == input)
+    }
+
+    pub fn lookup_sequence(&self, first: &InputEvent, second: &InputEvent) -> Option<Action> {
+        self.sequences.get(&(first.clone(), second.clone())).copied()
+    }
+
+    /* None leaves the key to the mode the editor is in, so that moving
+       about still works. */
+    pub fn lookup_selection(&self, input: &InputEvent) -> Option<Action> {
+        self.selection.get(input).copied()
+    }
+
+    /* In Normal mode keys are commands, so nothing inserts itself. */
+    pub fn lookup_normal(&self, input: &InputEvent) -> Option<Action> {
+        self.normal.get(input)
+            .or_else(|| self.bindings.get(input))
+            .copied()
+    }
+
+    /* Unbound printable chars insert themselves, so that a keymap
+       only has to spell out the keys that do something else. */
+    pub fn lookup(&self, input: &InputEvent) -> Option<Action> {
+        self.bindings.get(input)
+            .copied()
+            .or(match input {
+                InputEvent::Char(c) => Some(Action::InsertChar(*c)),
+                _otherwise          => None,
+            })
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use Action::*;
+
+        let control = KeyModifiers::CONTROL;
+        let none = KeyModifiers::NONE;
+        let shift = KeyModifiers::SHIFT;
+
+        Self::empty()
+            .bind(KeyCode::Char('q'), control, Quit)
+            .bind(KeyCode::Char('s'), control, Save)
+            .bind(KeyCode::Char('g'), control, GotoLine)
+            .bind(KeyCode::Char('g'), KeyModifiers::ALT, ShowStats)
+            .bind(KeyCode::Char('f'), control, Search)
+            .bind(KeyCode::Char('e'), control, Replace)
+            .bind(KeyCode::Char('z'), control, Undo)
+            .bind(KeyCode::Char('y'), control, Redo)
+            .bind(KeyCode::Char('n'), control, NextBuffer)
+            .bind(KeyCode::Char('p'), control, PreviousBuffer)
+            .bind(KeyCode::Char('w'), control, FocusOtherPane)
+            .bind(KeyCode::Char('o'), control, Unsplit)
+            .bind(KeyCode::Char('r'), control, ToggleReadOnly)
+            .bind(KeyCode::Char('l'), control, CenterOnCursor)
+            .bind(KeyCode::Char('z'), KeyModifiers::ALT, ToggleWrap)
+            .bind(KeyCode::Char('l'), KeyModifiers::ALT, ToggleCursorLine)
+            .bind(KeyCode::Char('w'), KeyModifiers::ALT, ToggleList)
+            .bind(KeyCode::Char('j'), KeyModifiers::ALT, JoinLines)
+            .bind(KeyCode::Char('o'), KeyModifiers::ALT, OpenLineBelow)
+            .bind(KeyCode::Char('O'), KeyModifiers::ALT | KeyModifiers::SHIFT, OpenLineAbove)
+            .bind(KeyCode::Char('k'), control, TruncateLine)
+            .bind(KeyCode::Char('k'), KeyModifiers::ALT, DeleteLine)
+            .bind(KeyCode::F(3),      none,    SearchNext)
+            .bind(KeyCode::F(12),     none,    ToggleKeys)
+            .bind(KeyCode::Esc,       none,    ClearSearch)
+            .bind(KeyCode::Left,      control, WordLeft)
+            .bind(KeyCode::Right,     control, WordRight)
+            .bind(KeyCode::Up,        none,    MoveUp)
+            .bind(KeyCode::Down,      none,    MoveDown)
+            .bind(KeyCode::Left,      none,    MoveLeft)
+            .bind(KeyCode::Right,     none,    MoveRight)
+            .bind(KeyCode::PageUp,    none,    PageUp)
+            .bind(KeyCode::PageDown,  none,    PageDown)
+            .bind(KeyCode::Home,      none,    LineStart)
+            .bind(KeyCode::End,       none,    LineEnd)
+            .bind(KeyCode::Backspace, none,    DeleteCharBefore)
+            .bind(KeyCode::Delete,    none,    DeleteCharAt)
+            .bind(KeyCode::Enter,     none,    SplitLine)
+            .bind(KeyCode::Char('v'), KeyModifiers::ALT, ToggleSelection)
+            .bind_normal(KeyCode::Char('h'), none,  MoveLeft)
+            .bind_normal(KeyCode::Char('j'), none,  MoveDown)
+            .bind_normal(KeyCode::Char('k'), none,  MoveUp)
+            .bind_normal(KeyCode::Char('l'), none,  MoveRight)
+            .bind_normal(KeyCode::Char('w'), none,  WordRight)
+            .bind_normal(KeyCode::Char('b'), none,  WordLeft)
+            .bind_normal(KeyCode::Char('0'), none,  LineStart)
+            .bind_normal(KeyCode::Char('$'), none,  LineEnd)
+            .bind_normal(KeyCode::Char('%'), none,  MatchBracket)
+            .bind_normal(KeyCode::Char('i'), none,  InsertMode)
+            .bind_normal(KeyCode::Char('a'), none,  Append)
+            .bind_normal(KeyCode::Char('o'), none,  OpenLineBelow)
+            .bind_normal(KeyCode::Char('O'), shift, OpenLineAbove)
+            .bind_normal(KeyCode::Char('x'), none,  DeleteCharAt)
+            .bind_normal(KeyCode::Char('J'), shift, JoinLines)
+            .bind_normal(KeyCode::Char('u'), none,  Undo)
+            .bind_normal(KeyCode::Char('/'), none,  Search)
+            .bind_normal(KeyCode::Char('n'), none,  SearchNext)
+            .bind_normal(KeyCode::Char('G'), shift, BufferEnd)
+            .bind_normal(KeyCode::Char('p'), none,  PasteAfter)
+            .bind_normal(KeyCode::Char('P'), shift, PasteBefore)
+            .bind_normal(KeyCode::Char('v'), none,  ToggleSelection)
+            .bind_normal(KeyCode::Char(':'), none,  CommandLine)
+            .bind_sequence(KeyEvent::new(KeyCode::Char('g'), none), KeyEvent::new(KeyCode::Char('g'), none), BufferStart)
+            .bind_sequence(KeyEvent::new(KeyCode::Char('g'), none), KeyEvent::new(KeyCode::Char('g'), control), ShowStats)
+            .bind_sequence(KeyEvent::new(KeyCode::Char('d'), none), KeyEvent::new(KeyCode::Char('d'), none), DeleteLine)
+            .bind_sequence(KeyEvent::new(KeyCode::Char('y'), none), KeyEvent::new(KeyCode::Char('y'), none), YankLine)
+            .bind_sequence(KeyEvent::new(KeyCode::Char('"'), none), KeyEvent::new(KeyCode::Char('+'), none), UseClipboard)
+            .bind_selection(KeyCode::Char('v'), none, ToggleSelection)
+            .bind_selection(KeyCode::Esc,       none, ToggleSelection)
+            .bind_selection(KeyCode::Char('y'), none, YankSelection)
+            .bind_selection(KeyCode::Char('d'), none, DeleteSelection)
+            .bind_selection(KeyCode::Char('x'), none, DeleteSelection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> InputEvent {
+        KeyEvent::new(code, modifiers).into()
+    }
+
+    #[test]
+    fn rebinding_quit_moves_it_to_the_new_key() {
+        let control = KeyModifiers::CONTROL;
+        let keymap = Keymap::default().bind(KeyCode::Char('c'), control, Action::Quit);
+        assert_eq!(keymap.lookup(&key(KeyCode::Char('c'), control)), Some(Action::Quit));
+        assert_eq!(keymap.lookup(&key(KeyCode::Char('q'), control)), Some(Action::Quit));
+
+        let keymap = Keymap::empty().bind(KeyCode::Char('c'), control, Action::Quit);
+        assert_eq!(keymap.lookup(&key(KeyCode::Char('c'), control)), Some(Action::Quit));
+        assert_eq!(keymap.lookup(&key(KeyCode::Char('q'), control)), None);
+    }
+
+    #[test]
+    fn a_later_binding_of_the_same_key_wins() {
+        let keymap = Keymap::default().bind(KeyCode::Char('s'), KeyModifiers::CONTROL, Action::Undo);
+
+        assert_eq!(keymap.lookup(&key(KeyCode::Char('s'), KeyModifiers::CONTROL)), Some(Action::Undo));
+    }
+
+    #[test]
+    fn unbound_chars_insert_and_other_unbound_keys_mean_nothing() {
+        let keymap = Keymap::empty();
+
+        assert_eq!(keymap.lookup(&InputEvent::Char('x')), Some(Action::InsertChar('x')));
+        assert_eq!(keymap.lookup(&key(KeyCode::F(5), KeyModifiers::NONE)), None);
+        assert_eq!(keymap.lookup_normal(&InputEvent::Char('x')), None);
+    }
+
+    #[test]
+    fn normal_mode_bindings_take_over_only_in_normal_mode() {
+        let keymap = Keymap::default();
+        let j = InputEvent::Char('j');
+
+        assert_eq!(keymap.lookup_normal(&j), Some(Action::MoveDown));
+        assert_eq!(keymap.lookup(&j), Some(Action::InsertChar('j')));
+    }
+}
@@ -0,0 +1,4 @@
+/* The Elm-style loop the editor runs on. It knows nothing of the
+   editor, so it stands apart from it, and whatever it offers that the
+   editor has no use for yet is still there for the taking. */
+pub mod elm;
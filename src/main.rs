@@ -1,15 +1,38 @@
-use std::{cmp, fmt::{self, Display}, fs, io, path, ops::Range};
+use std::{borrow::Cow, cell::RefCell, cmp, collections::VecDeque, env, fmt::{self, Display}, fs, io, iter, mem, path, ops::Range, sync::{Arc, Mutex}, time};
 
-mod elm;
-use elm::Host;
+use rusty_spoon::elm::{self, Host};
 
 mod tui;
 
+#[cfg(test)]
+mod headless;
+
+mod rope;
+use rope::Rope;
+
+mod keymap;
+use keymap::{Action, Keymap};
+
+mod config;
+use config::{Bell, Config};
+
+mod input;
+use input::{Direction, InputEvent};
+
+mod highlight;
+use highlight::{Highlighter, RustKeywordHighlighter};
+
+mod theme;
+use theme::Theme;
+
+mod clipboard;
+use clipboard::Clipboard;
+
 /* Make a crossterm prelude for the elm module? */
-use crossterm::{cursor, event, event::{KeyCode, KeyModifiers}, style, QueueableCommand, terminal};
-use tui::RenderingBuffer;
+use crossterm::{event, style, style::Stylize};
+use tui::{CommandBuffer, CursorShape};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default, PartialEq)]
 struct ScreenSize {
     columns: usize,
     rows:    usize,
@@ -33,436 +56,7595 @@ impl fmt::Display for ScreenSize {
     }
 }
 
-impl Default for ScreenSize {
-    fn default() -> Self {
-        Self { columns: Default::default(), rows: Default::default() }
-    }
-}
-
 impl From<(u16, u16)> for ScreenSize {
     fn from(value: (u16, u16)) -> Self {
         ScreenSize::new(value.0 as usize, value.1 as usize)
     }
 }
 
+/* The byte range covering chars [skip, skip + take) of line, or None
+   when the line has no char at skip. */
+fn char_window(line: &str, skip: usize, take: usize) -> Option<Range<usize>> {
+    let mut boundaries = line.char_indices().map(|(index, _)| index);
+    let start = boundaries.nth(skip)?;
+    let end = if take == 0 { start } else {
+        boundaries.nth(take - 1).unwrap_or(line.len())
+    };
+
+    Some(start..end)
+}
+
+/* The byte offset of the char at column, or the end of line when
+   column is past it. */
+fn byte_offset(line: &str, column: usize) -> usize {
+    line.char_indices()
+        .nth(column)
+        .map_or(line.len(), |(index, _)| index)
+}
+
+/* Columns a char takes on screen when it starts in column; tabs
+   reach to the next tab stop. */
+fn char_width(c: char, column: usize, tab_width: usize) -> usize {
+    if c == '\t' {
+        let tab_width = cmp::max(tab_width, 1);
+        tab_width - column % tab_width
+    } else {
+        1
+    }
+}
+
+/* The screen column a char starts in. Past the end of the line
+   every column counts as one. */
+fn display_column(line: &str, char_column: usize, tab_width: usize) -> usize {
+    let mut column = 0;
+    let mut counted = 0;
+    for c in line.chars().take(char_column) {
+        column += char_width(c, column, tab_width);
+        counted += 1;
+    }
+
+    column + (char_column - counted)
+}
+
+/* The char that covers a screen column; the inverse of
+   display_column. */
+fn char_column(line: &str, display_column: usize, tab_width: usize) -> usize {
+    let mut column = 0;
+    for (index, c) in line.chars().enumerate() {
+        let width = char_width(c, column, tab_width);
+        if display_column < column + width {
+            return index;
+        }
+        column += width;
+    }
+
+    line.chars().count() + (display_column - column)
+}
+
+fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        let width = char_width(c, column, tab_width);
+        if c == '\t' {
+            expanded.extend(iter::repeat_n(' ', width));
+        } else {
+            expanded.push(c);
+        }
+        column += width;
+    }
+
+    expanded
+}
+
+/* Where a line, tabs expanded, breaks into rows of at most width
+   columns: after the last blank that fits, or wherever the row is
+   full if there is none. Always at least one row, if empty. */
+fn wrap_columns(line: &str, width: usize) -> Vec<Range<usize>> {
+    let width = cmp::max(width, 1);
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut rows = vec![];
+    let mut start = 0;
+
+    while chars.len() - start > width {
+        let end = (start + 1..=start + width).rev()
+            .find(|end| chars[end - 1].is_whitespace())
+            .unwrap_or(start + width);
+        rows.push(start..end);
+        start = end;
+    }
+    rows.push(start..chars.len());
+
+    rows
+}
+
+/* The row of wrap_columns that column is shown on; the end of the
+   line belongs to the last one. */
+fn wrapped_row(rows: &[Range<usize>], column: usize) -> usize {
+    rows.iter()
+        .position(|columns| column < columns.end)
+        .unwrap_or(rows.len().saturating_sub(1))
+}
+
+/* One row of the screen when lines wrap: which line it shows, and
+   which of its columns. */
+struct VisualRow {
+    row:     usize,
+    columns: Range<usize>,
+}
+
+/* The visible part of a line, and whether there is more of it off
+   either side of the screen. */
+struct ClippedLine<'a> {
+    text:      Cow<'a, str>,
+    cut_left:  bool,
+    cut_right: bool,
+}
+
+impl <'a> ClippedLine<'a> {
+    /* Puts « over the first column and » over the last where text
+       goes on beyond them. A line entirely off to the left is a
+       lone «. */
+    fn with_markers(self) -> Cow<'a, str> {
+        if !self.cut_left && !self.cut_right {
+            return self.text;
+        }
+
+        let mut chars = self.text.chars().collect::<Vec<_>>();
+        if self.cut_left {
+            match chars.first_mut() {
+                Some(first) => *first = '«',
+                None        => chars.push('«'),
+            }
+        }
+        if self.cut_right {
+            if let Some(last) = chars.last_mut() {
+                *last = '»';
+            }
+        }
+
+        Cow::Owned(chars.into_iter().collect())
+    }
+}
+
+/* A char in a line known to start at a certain screen column: in
+   row, the char at byte starts in column. */
+#[derive(Clone, Copy, Debug)]
+struct ColumnAnchor {
+    row:    usize,
+    column: usize,
+    byte:   usize,
+}
+
 #[derive(Debug)]
 struct EditingViewport {
-    row_offset:   usize,
+    row_offset:    usize,
     column_offset: usize,
+    tab_width:     usize,
+    wrap:          bool,
+    /* For each row of the screen, where its line was last cut on the
+       left, so that scrolling right along a very long line starts
+       counting columns from there rather than from the start of the
+       line. Forgotten whenever the text changes. */
+    anchors:       RefCell<Vec<Option<ColumnAnchor>>>,
 }
 
 impl EditingViewport {
+    /* Rows of the screen below the last line show no text at all,
+       not even an empty line. */
+    fn is_past_end(&self, line_index: usize, contents: &EditingModel) -> bool {
+        self.row_offset + line_index >= contents.line_count()
+    }
+
     fn select_and_clip<'a>(
         &self,
         line_index: usize, 
         width:      usize, 
-        lines:      &'a[String]
-    ) -> Option<&'a str> {
-        let effective_line_index = self.row_offset + line_index;
-        if effective_line_index < lines.len() {
-            let line = &lines[effective_line_index];
-
-            if self.column_offset < line.len() {
-                let len = cmp::min(width, line.len().saturating_sub(self.column_offset));
-                let end = self.column_offset + len;
-                let start = self.column_offset;
-                let slice = start..end;
-                Some(&line[slice])
-            } else if !line.is_empty() {
-                Some(&"«")
+        contents:   &'a EditingModel
+    ) -> ClippedLine<'a> {
+        let row = self.row_offset + line_index;
+        let line = contents.line(row);
+
+        /* Find the char that covers column_offset, starting from the
+           anchor when it is at or left of there. Offsets and widths
+           are in screen columns, never in bytes; slicing on anything
+           but a char boundary panics. */
+        let mut anchors = self.anchors.borrow_mut();
+        let anchor = anchors.get(line_index)
+            .copied()
+            .flatten()
+            .filter(|anchor| anchor.row == row && anchor.column <= self.column_offset)
+            .filter(|anchor| line.is_char_boundary(anchor.byte))
+            .unwrap_or(ColumnAnchor { row, column: 0, byte: 0 });
+
+        let mut column = anchor.column;
+        let mut start = anchor.byte;
+        for c in line[start..].chars() {
+            let width = char_width(c, column, self.tab_width);
+            if column + width > self.column_offset {
+                break;
+            }
+            column += width;
+            start += c.len_utf8();
+        }
+
+        if anchors.len() <= line_index {
+            anchors.resize(line_index + 1, None);
+        }
+        anchors[line_index] = Some(ColumnAnchor { row, column, byte: start });
+
+        /* From there on, only as far as the screen is wide. A tab cut
+           in two by either edge shows as the spaces that are left of
+           it. */
+        let cut_left = self.column_offset > 0 && !line.is_empty();
+        let mut text = String::new();
+        let mut end = start;
+        let mut shown = 0;
+        let mut tabs = false;
+        for c in line[start..].chars() {
+            if shown == width {
+                break;
+            }
+            let width_left = width - shown;
+            let char_width = char_width(c, column, self.tab_width);
+            let visible = cmp::min(column + char_width, self.column_offset + width)
+                - cmp::max(column, self.column_offset);
+
+            if c == '\t' || visible < char_width {
+                tabs = true;
+                text.extend(iter::repeat_n(' ', cmp::min(visible, width_left)));
             } else {
-                Some(&"")
+                text.push(c);
             }
-        } else {
-            None
+            shown += visible;
+            column += char_width;
+            end += c.len_utf8();
         }
-    }
+        let cut_right = column > self.column_offset + width
+            || line[end..].chars().next().is_some() && shown == width;
 
-    fn scroll_up(&mut self, by: usize) {
-        self.row_offset = self.row_offset.saturating_sub(by);
+        let text = if tabs { Cow::Owned(text) } else { Cow::Borrowed(&line[start..end]) };
+        ClippedLine { text, cut_left, cut_right }
     }
 
-    fn scroll_down(&mut self, by: usize) {
-        self.row_offset += by;
+    fn forget_anchors(&self) {
+        self.anchors.borrow_mut().clear();
     }
 
-    fn scroll_left(&mut self, by: usize) {
-        self.column_offset = self.column_offset.saturating_sub(by);
+    fn scroll_up(&mut self, by: usize) {
+        self.row_offset = self.row_offset.saturating_sub(by);
     }
 
-    fn scroll_right(&mut self, by: usize) {
-        self.column_offset += by;
+    /* Scrolling stops at limit, the furthest offset that still
+       shows something, rather than running off into empty space. */
+    fn scroll_down(&mut self, by: usize, limit: usize) {
+        self.row_offset = cmp::max(cmp::min(self.row_offset + by, limit), self.row_offset);
     }
 }
 
 impl Default for EditingViewport {
     fn default() -> Self {
-        Self { row_offset: Default::default(), column_offset: Default::default() }
+        Self { row_offset: 0, column_offset: 0, tab_width: 4, wrap: false, anchors: RefCell::new(vec![]) }
     }
 }
 
-struct EditingModel {
-    lines: Vec<String>,
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
 }
 
-impl EditingModel {
-    fn new() -> Self {
-        Self {
-            lines: vec![
-                "hi, mom".into(),
-                "Hello, world".into(),
-            ],
-        }
+impl LineEnding {
+    /* Whichever ending most lines use; ties go to LF. */
+    fn detect(text: &str) -> Self {
+        Self::from_counts(text.matches('\n').count(), text.matches("\r\n").count())
     }
 
-    fn with_lines(lines: &[String]) -> Self {
-        Self { lines: lines.to_vec(), }
+    /* Of all the line breaks, how many came with a \r before them. */
+    fn from_counts(line_feeds: usize, carriage_returns: usize) -> Self {
+        if carriage_returns * 2 > line_feeds {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        }
     }
 
-    fn from_file(file_path: &path::Path) -> io::Result<Self> {
-        let file_contents = fs::read_to_string(file_path)?;
-        let lines = file_contents.lines()
-            .map(|line| line.to_owned())
-            .collect::<Vec<_>>();
-        Ok(Self::with_lines(&lines))
+    fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf   => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
     }
+}
 
-    fn line_count(&self) -> usize { self.lines.len() }
-
-    fn line_slice(&self, line_index: usize, range: Range<usize>) -> Option<&str> {
-        self.lines.get(line_index).map(|line| &line[range])
-    }
+/* What the bytes of a file were taken for. Unknown is anything that
+   is not valid UTF-8, which opens with the bad bytes replaced. */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Encoding {
+    #[default]
+    Utf8,
+    Unknown,
 }
 
-impl Default for EditingModel {
-    fn default() -> Self {
-        Self::new()
+impl Encoding {
+    fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8    => "UTF-8",
+            Encoding::Unknown => "unknown",
+        }
     }
 }
 
-#[derive(Debug)]
-struct Position {
-    column:      usize,
-    row:         usize,
-}
+/* A byte order mark, which UTF-8 does not need but some editors put
+   at the start of a file anyway. */
+const BOM: &str = "\u{feff}";
 
-impl Position {
-    fn move_up(&mut self, by: usize)    { self.row = self.row.saturating_sub(by)      }
-    fn move_down(&mut self, by: usize)  { self.row += by  /* no! */                            }
-    fn move_left(&mut self)             { self.column = self.column.saturating_sub(1) }
-    fn move_right(&mut self)            { self.column += 1 /* No! */                           }
+/* From this size on, files open indexed. */
+const INDEXED_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+struct EditingModel {
+    lines:         Rope,
+    journal:       Vec<Edit>,
+    line_ending:   LineEnding,
+    final_newline: bool,
+    encoding:      Encoding,
+    /* Kept off the first line, and put back on saving. */
+    bom:           bool,
+    /* When the file was last written, as of reading or saving it, to
+       tell whether someone else has written it since. */
+    disk_modified: Option<time::SystemTime>,
 }
 
-impl Default for Position {
-    fn default() -> Self {
-        Self { column: Default::default(), row: Default::default() }
+impl EditingModel {
+    fn new() -> Self {
+        Self::with_lines(&[
+            "hi, mom".into(),
+            "Hello, world".into(),
+        ])
     }
-}
 
-struct NavigationModel {
-    cursor:      Position,
-    screen_size: ScreenSize,
-    viewport:    EditingViewport,
-}
+    fn with_lines(lines: &[String]) -> Self {
+        Self {
+            lines:         lines.iter().cloned().collect(),
+            journal:       vec![],
+            line_ending:   LineEnding::default(),
+            final_newline: false,
+            encoding:      Encoding::default(),
+            bom:           false,
+            disk_modified: None,
+        }
+    }
 
-impl NavigationModel {
-    fn is_topmost(&self)    -> bool { self.cursor.row == 0                               }
-    fn is_bottommost(&self) -> bool { self.cursor.row == self.screen_size.rows - 1       }
-    fn is_leftmost(&self)   -> bool { self.cursor.column == 0                            }
-    fn is_rightmost(&self)  -> bool { self.cursor.column == self.screen_size.columns - 1 }
-
-    fn is_recognized(direction: &KeyCode) -> bool {
-        matches!(
-            direction, 
-            KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right |
-            KeyCode::PageUp | KeyCode::PageDown
-        )
+    fn empty() -> Self {
+        Self::with_lines(&[String::new()])
     }
 
-    fn move_intended(&mut self, direction: &KeyCode) {
-        match direction {
-            KeyCode::Up    => {
-                if self.is_topmost() {
-                    self.viewport.scroll_up(1)
-                } else {
-                    self.cursor.move_up(1)
-                }
-            }
+    /* Remembers how the text ended its lines, and whether it ended
+       with one, so that serialize gives the same bytes back. */
+    fn from_text(text: &str) -> Self {
+        let bom = text.starts_with(BOM);
+        let text = text.strip_prefix(BOM).unwrap_or(text);
+        let final_newline = text.ends_with('\n');
+        let body = text.strip_suffix('\n').unwrap_or(text);
+        let lines = body.split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line).to_owned())
+            .collect::<Rope>();
 
-            KeyCode::Down  => {
-                if self.is_bottommost() {
-                    self.viewport.scroll_down(1)
-                } else {
-                    self.cursor.move_down(1)
-                }
+        Self {
+            lines,
+            journal:       vec![],
+            line_ending:   LineEnding::detect(text),
+            final_newline,
+            encoding:      Encoding::Utf8,
+            bom,
+            disk_modified: None,
+        }
+    }
+
+    /* The same as from_text, a line at a time, so that the text is
+       never in memory twice over: once read and once split up. Lines
+       that are not valid UTF-8 are taken as far as they are, with
+       U+FFFD for the rest. */
+    fn from_reader<R: io::BufRead>(mut reader: R) -> io::Result<Self> {
+        let mut chunk = vec![];
+        let mut error = None;
+        let mut encoding = Encoding::Utf8;
+        let mut bom = false;
+        let mut final_newline = false;
+        let (mut line_feeds, mut carriage_returns) = (0, 0);
+
+        let mut next_line = || -> io::Result<Option<String>> {
+            chunk.clear();
+            if reader.read_until(b'\n', &mut chunk)? == 0 {
+                return Ok(None);
+            }
+            if line_feeds == 0 && chunk.starts_with(BOM.as_bytes()) {
+                bom = true;
+                chunk.drain(..BOM.len());
             }
 
-            KeyCode::Left  => {
-                if self.is_leftmost() {
-                    self.viewport.scroll_left(1)
-                } else {
-                    self.cursor.move_left()
-                }
+            final_newline = chunk.ends_with(b"\n");
+            if final_newline {
+                line_feeds += 1;
+                carriage_returns += usize::from(chunk.ends_with(b"\r\n"));
+                chunk.pop();
+            }
+            if chunk.ends_with(b"\r") {
+                chunk.pop();
             }
 
-            KeyCode::Right => {
-                if self.is_rightmost() {
-                    self.viewport.scroll_right(1)
-                } else {
-                    self.cursor.move_right()
+            match String::from_utf8(chunk.clone()) {
+                Ok(line) => Ok(Some(line)),
+                Err(e)   => {
+                    encoding = Encoding::Unknown;
+                    Ok(Some(String::from_utf8_lossy(e.as_bytes()).into_owned()))
                 }
             }
+        };
 
-            KeyCode::PageUp => {
-                let page = self.screen_size.rows;
-                let scroll_by = page.saturating_sub(self.cursor.row);
-                self.cursor.move_up(page);
-                self.viewport.scroll_up(scroll_by);
-            }
+        let mut lines = iter::from_fn(|| next_line().unwrap_or_else(|e| {
+            error = Some(e);
+            None
+        }))
+        .collect::<Rope>();
 
-            KeyCode::PageDown => {
-                let page = self.screen_size.rows;
-                let scroll_by = self.cursor.row;
-                self.viewport.scroll_down(scroll_by);
-                let move_by = page.saturating_sub(self.cursor.row);
-                self.cursor.move_down(move_by);
-            }
+        if let Some(error) = error {
+            return Err(error);
+        }
 
-            _otherwise => unimplemented!(),
+        /* A final line break ends the last line rather than starting
+           another, but an empty file still has a line. */
+        if lines.len() == 0 {
+            lines.insert(0, String::new());
         }
-    }
 
-    fn screen_size_changed(&mut self, new_size: ScreenSize) -> elm::Cmd<Message> {
-        self.screen_size = new_size;
-        elm::Cmd::none()
+        Ok(Self {
+            lines,
+            journal:       vec![],
+            line_ending:   LineEnding::from_counts(line_feeds, carriage_returns),
+            final_newline,
+            encoding,
+            bom,
+            disk_modified: None,
+        })
     }
-}
 
-impl Default for NavigationModel {
-    fn default() -> Self {
+    /* Like from_text, but the text is kept whole and its lines are
+       only copied out one by one as they get edited. Quick to open
+       and light on memory for something mostly read, like a log;
+       the price is that the whole text stays around for as long as
+       the buffer does. */
+    fn indexed(mut text: String) -> Self {
+        let bom = text.starts_with(BOM);
+        if bom {
+            text.drain(..BOM.len());
+        }
+        let line_ending = LineEnding::detect(&text);
+        let final_newline = text.ends_with('\n');
+
         Self {
-            cursor:      Default::default(), 
-            screen_size: Default::default(),
-            viewport:    Default::default(),
+            lines:         Rope::indexed(text),
+            journal:       vec![],
+            line_ending,
+            final_newline,
+            encoding:      Encoding::Utf8,
+            bom,
+            disk_modified: None,
         }
     }
-}
 
-struct KeyEvent(event::KeyEvent);
+    /* A file that does not exist yet reads as empty; it is a new
+       buffer. */
+    fn read_file_or_empty(file_path: &path::Path) -> io::Result<Self> {
+        match Self::read_file(file_path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::from_text("")),
+            read                                          => read,
+        }
+    }
+
+    /* Big files are indexed rather than split up. */
+    fn read_file(file_path: &path::Path) -> io::Result<Self> {
+        let mut file = fs::File::open(file_path)?;
+
+        let metadata = file.metadata()?;
+        let disk_modified = metadata.modified().ok();
+        if metadata.len() < INDEXED_FILE_SIZE {
+            return Ok(Self { disk_modified, ..Self::from_reader(io::BufReader::new(file))? });
+        }
 
-impl From<&event::KeyEvent> for KeyEvent {
-    fn from(event: &event::KeyEvent) -> Self {
-        Self(event.clone())
+        let mut bytes = vec![];
+        io::Read::read_to_end(&mut file, &mut bytes)?;
+        match String::from_utf8(bytes) {
+            Ok(text) => Ok(Self { disk_modified, ..Self::indexed(text) }),
+            Err(e)   => Ok(Self {
+                encoding: Encoding::Unknown,
+                disk_modified,
+                ..Self::indexed(String::from_utf8_lossy(e.as_bytes()).into_owned())
+            }),
+        }
     }
-}
 
-impl Display for KeyEvent {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?} [{:?}]", self.0.code, self.0.modifiers)
+    /* Written by someone else since it was read or last saved. A file
+       that has gone away since can be written again without losing
+       anything. */
+    fn changed_on_disk(&self, file_path: &path::Path) -> bool {
+        modified_at(file_path).is_some_and(|modified| Some(modified) != self.disk_modified)
     }
-}
 
-struct KeyHistory {
-    events: Vec<KeyEvent>,
-    horizon: usize,
-}
+    /* Start of the next word, where runs of word chars and runs of
+       punctuation both count as words. The end of a line wraps to
+       the first word on the next. */
+    fn next_word_boundary(&self, row: usize, column: usize) -> Position {
+        let line = self.lines.get(row).map_or(vec![], |line| line.chars().collect::<Vec<_>>());
 
-impl KeyHistory {
-    fn record(&mut self, event: &event::KeyEvent) {
-        self.events.push(event.into());
-        if self.events.len() > self.horizon {
-            self.events.remove(0);
+        if column >= line.len() {
+            if row + 1 < self.lines.len() {
+                let next_line = &self.lines[row + 1];
+                let column = next_line.chars()
+                    .take_while(|c| c.is_whitespace())
+                    .count();
+                Position { column, row: row + 1 }
+            } else {
+                Position { column: line.len(), row }
+            }
+        } else {
+            let class = CharClass::of(line[column]);
+            let word_end = (column..line.len())
+                .find(|&i| CharClass::of(line[i]) != class)
+                .unwrap_or(line.len());
+            let column = (word_end..line.len())
+                .find(|&i| CharClass::of(line[i]) != CharClass::Whitespace)
+                .unwrap_or(line.len());
+            Position { column, row }
         }
     }
-}
 
-impl Display for KeyHistory {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "[")?;
-        for event in &self.events {
-            write!(f, "{},", event)?;
+    /* Start of the word before column; the start of a line wraps
+       to the end of the previous one. */
+    fn prev_word_boundary(&self, row: usize, column: usize) -> Position {
+        let line = self.lines.get(row).map_or(vec![], |line| line.chars().collect::<Vec<_>>());
+        let column = cmp::min(column, line.len());
+
+        let word_end = (0..column)
+            .rev()
+            .find(|&i| CharClass::of(line[i]) != CharClass::Whitespace);
+
+        match word_end {
+            Some(word_end) => {
+                let class = CharClass::of(line[word_end]);
+                let column = (0..word_end)
+                    .rev()
+                    .find(|&i| CharClass::of(line[i]) != class)
+                    .map_or(0, |i| i + 1);
+                Position { column, row }
+            }
+            None if row > 0 && row <= self.lines.len() =>
+                Position { column: self.line_char_len(row - 1), row: row - 1 },
+            None =>
+                Position { column: 0, row },
         }
-        write!(f, "]")
     }
-}
 
-impl Default for KeyHistory {
-    fn default() -> Self {
-        Self { events: Default::default(), horizon: 3 }
+    fn find_all(&self, query: &str) -> Vec<Position> {
+        if query.is_empty() {
+            return vec![];
+        }
+
+        self.iter_lines()
+            .enumerate()
+            .flat_map(|(row, line)|
+                line.match_indices(query).map(move |(index, _)|
+                    Position { column: line[..index].chars().count(), row }
+                )
+            )
+            .collect()
     }
-}
 
-struct Editor {
-    buffer_name: String,
-    contents:    EditingModel,
-    navigation:  NavigationModel,
-    key_history: KeyHistory,
-}
+    /* Replaces non-overlapping occurrences left to right, so "aa"
+       in "aaaa" is replaced twice. Answers the number replaced. */
+    fn replace_all(&mut self, from: &str, to: &str) -> usize {
+        if from.is_empty() {
+            return 0;
+        }
 
-impl Editor {
-    fn key_typed(&mut self, key: &event::KeyEvent) -> elm::Cmd<Message> {
-        match key {
-            event::KeyEvent {
-                code:      KeyCode::Char('q'),
-                modifiers: KeyModifiers::CONTROL,
-                ..
-            } => 
-                elm::Cmd::gtfo(),
-
-            event::KeyEvent {
-                code:      direction,
-                modifiers: KeyModifiers::NONE,
-                ..
-            } if NavigationModel::is_recognized(direction) => {
-                self.navigation.move_intended(direction);
-                elm::Cmd::none()
+        let mut count = 0;
+        for row in 0..self.lines.len() {
+            let line = &self.lines[row];
+            let matches = line.matches(from).count();
+            if matches > 0 {
+                let after = line.replace(from, to);
+                self.replace_line(row, after);
+                count += matches;
             }
-
-            ev @ event::KeyEvent { .. } =>
-                self.record_key_event(ev),
         }
+
+        count
     }
 
-    fn record_key_event(&mut self, ev: &event::KeyEvent) -> elm::Cmd<Message> {
-        self.key_history.record(ev);
-        elm::Cmd::none()
+    /* Replaces the first occurrence at or after position, wrapping
+       around to the top. Answers the position just past the
+       replacement. */
+    fn replace_next(&mut self, from: &str, to: &str, position: &Position) -> Option<Position> {
+        let matches = self.find_all(from);
+        let found = matches.iter()
+            .find(|candidate| !position.is_after(candidate))
+            .or_else(|| matches.first())?;
+
+        let mut after = self.lines[found.row].to_owned();
+        let start = byte_offset(&after, found.column);
+        after.replace_range(start..start + from.len(), to);
+        self.replace_line(found.row, after);
+
+        Some(Position { column: found.column + to.chars().count(), row: found.row })
     }
 
-    fn event_occurred(&mut self, event: &event::Event) -> elm::Cmd<Message> {
-        match event {
-            event::Event::Key(key) =>
-                self.key_typed(key),
-            event::Event::Resize(width, height) =>
-                self.navigation.screen_size_changed((*width, *height).into()),
-            _otherwise =>
-                elm::Cmd::none(),
+    fn serialize(&self) -> String {
+        let line_ending = self.line_ending.as_str();
+        let mut text = self.lines.join(line_ending);
+        if self.bom {
+            text.insert_str(0, BOM);
+        }
+        if self.final_newline {
+            text.push_str(line_ending);
         }
+        text
     }
 
-    fn render(&self, buffer: &mut RenderingBuffer) -> io::Result<()> {
-        let cursor_bounds = &self.navigation.screen_size;
+    /* The same, but ending in exactly one line break whatever the
+       buffer ends in: none gets one, and empty lines at the end go.
+       A buffer of nothing but empty lines comes out empty. */
+    fn serialize_with_final_newline(&self) -> String {
+        let line_ending = self.line_ending.as_str();
+        let kept = (0..self.lines.len())
+            .rev()
+            .find(|&row| !self.lines[row].is_empty())
+            .map_or(0, |row| row + 1);
 
-        /* At least consider putting the draw methods behind some
-           trait to cut down on the amount of code clutter. */
-
-        buffer
-           .queue(cursor::Hide)?
-           .queue(cursor::MoveTo(0, 0))?;
+        let mut text = if self.bom { BOM.to_owned() } else { String::new() };
+        for line in self.lines.iter().take(kept) {
+            text.push_str(line);
+            text.push_str(line_ending);
+        }
+        text
+    }
 
-        self.render_contents(buffer)?;
+    /* Everything as it would be saved, but next to the file rather
+       than over it. */
+    fn write_swap(&self, swap_path: &path::Path) -> io::Result<()> {
+        fs::write(swap_path, self.serialize())
+    }
 
-        let navigation_message = format!(
-            "size: {:?}, cursor: {:?}, view: {:?}",
-            cursor_bounds,
-            self.navigation.cursor,
-            self.navigation.viewport,
-        );
+    fn line(&self, row: usize) -> &str {
+        self.lines.get(row).unwrap_or("")
+    }
 
-        let key_message = format!("History: {}", self.key_history);
+    fn line_char_len(&self, row: usize) -> usize {
+        self.lines.get(row).map_or(0, |line| line.chars().count())
+    }
 
-        buffer
-            .queue(cursor::MoveTo(5, 10))?
-            .queue(style::Print(navigation_message))?
-            .queue(cursor::MoveTo(5, 15))?
-            .queue(style::Print(key_message))?
-            .queue(cursor::MoveTo(
-                self.navigation.cursor.column as u16,
-                self.navigation.cursor.row as u16,
-            ))?
-            .queue(cursor::Show)?;
+    fn line_count(&self) -> usize { self.lines.len() }
 
-        Ok(())
+    fn iter_lines(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter()
     }
 
-    fn render_contents(&self, buffer: &mut RenderingBuffer) -> io::Result<()> {
-        let cursor_bounds = &self.navigation.screen_size;
-        for i in 0..cursor_bounds.rows  {
-            let line = self.render_line(i as usize);
+    /* Runs of anything but whitespace. */
+    fn word_count(&self) -> usize {
+        self.iter_lines().map(|line| line.split_whitespace().count()).sum()
+    }
 
-            buffer.queue(style::Print(line))?
-                  .queue(terminal::Clear(terminal::ClearType::UntilNewLine))?;
+    /* Chars and bytes of the text as serialize gives it, line
+       breaks included. */
+    fn char_count(&self) -> usize {
+        let line_ending = self.line_ending.as_str();
+        let chars = self.iter_lines().map(|line| line.chars().count()).sum::<usize>();
+        chars + self.line_breaks() * line_ending.chars().count() + usize::from(self.bom)
+    }
 
-            if i < cursor_bounds.rows - 1 {
-                buffer.queue(style::Print("\r\n"))?;
-            }
-        }
+    fn byte_count(&self) -> usize {
+        let line_ending = self.line_ending.as_str();
+        let bytes = self.iter_lines().map(str::len).sum::<usize>();
+        bytes + self.line_breaks() * line_ending.len() + if self.bom { BOM.len() } else { 0 }
+    }
 
-        Ok(())
+    fn line_breaks(&self) -> usize {
+        self.lines.len() - 1 + usize::from(self.final_newline)
     }
 
-    fn render_line(&self, viewport_line_index: usize) -> &str {
-        let width = self.navigation.screen_size.columns as usize;
-        self.navigation.viewport
-            .select_and_clip(viewport_line_index, width, &self.contents.lines)
-            .unwrap_or("~")
+    /* Rows past the end grow the buffer, columns past the end
+       of the line append to it. */
+    fn insert_char(&mut self, row: usize, column: usize, c: char) {
+        self.grow_to(row);
+        let column = cmp::min(column, self.line_char_len(row));
+        self.perform(Edit::InsertChar { at: Position { column, row }, c });
     }
-}
 
-impl Default for Editor {
-    fn default() -> Self {
-        Self {
-            buffer_name: "Unnamed".to_owned(),
-            contents:    EditingModel::from_file(path::Path::new("src/main.rs")).unwrap(),
-            navigation:  NavigationModel::default(),
-            key_history: Default::default(),
+    /* Backspace; at the start of a line this joins it onto the
+       previous one. Answers where the cursor ends up. */
+    fn delete_char_before(&mut self, row: usize, column: usize) -> Position {
+        if row >= self.lines.len() {
+            return Position { column, row };
         }
-    }
-}
 
-#[derive(Clone)]
-enum Message {
-    SetBufferName(String),
-    ExternalEvent(event::Event),
-    SizedChanged(ScreenSize),
-}
+        let column = cmp::min(column, self.line_char_len(row));
+        if column > 0 {
+            let at = Position { column: column - 1, row };
+            let c = self.lines[row].chars().nth(at.column).unwrap_or_default();
+            self.perform(Edit::DeleteChar { at: at.clone(), c });
+            at
+        } else if row > 0 {
+            let at = Position { column: self.line_char_len(row - 1), row: row - 1 };
+            self.perform(Edit::JoinLine { at: at.clone() });
+            at
+        } else {
+            Position { column, row }
+        }
+    }
 
-impl Message {
-    
-}
+    /* Delete; at the end of a line this pulls the next one up. */
+    fn delete_char_at(&mut self, row: usize, column: usize) -> Position {
+        if row >= self.lines.len() {
+            return Position { column, row };
+        }
 
-impl elm::Application for Editor {
-    type Msg  = Message;
-    type View = tui::Screen;
+        let length = self.line_char_len(row);
+        let at = Position { column: cmp::min(column, length), row };
+        if column < length {
+            let c = self.lines[row].chars().nth(column).unwrap_or_default();
+            self.perform(Edit::DeleteChar { at: at.clone(), c });
+        } else if row + 1 < self.lines.len() {
+            self.perform(Edit::JoinLine { at: at.clone() });
+        }
 
-    fn init() -> (Self, elm::Cmd<Message>) {
-        (Editor::default(), ScreenSize::request())
+        at
     }
 
-    fn update(&mut self, message: &Message) -> elm::Cmd<Message> {
-        match message {
-            Message::SetBufferName(new_name) => {
-                self.buffer_name = new_name.clone();
-                elm::Cmd::none()
-            }
+    /* Enter; the tail of the line from column onwards moves to a
+       new line below. */
+    fn split_line(&mut self, row: usize, column: usize) -> Position {
+        self.grow_to(row);
+        let column = cmp::min(column, self.line_char_len(row));
+        self.perform(Edit::SplitLine { at: Position { column, row } });
 
-            Message::ExternalEvent(event) =>
-                self.event_occurred(event),
+        Position { column: 0, row: row + 1 }
+    }
 
-            Message::SizedChanged(size) =>
-                self.navigation.screen_size_changed(size.clone()),
+    /* Pulls the next line up onto row with a space between them, or
+       none when the next line is empty. Answers the column where they
+       meet, or None on the last line, which has nothing to join. */
+    fn join_lines(&mut self, row: usize) -> Option<usize> {
+        if row + 1 >= self.lines.len() {
+            return None;
         }
+
+        let column = self.line_char_len(row);
+        if !self.lines[row + 1].is_empty() {
+            self.perform(Edit::InsertChar { at: Position { column, row }, c: ' ' });
+        }
+        let length = self.line_char_len(row);
+        self.perform(Edit::JoinLine { at: Position { column: length, row } });
+
+        Some(column)
     }
 
-    fn view(&self, display: &Self::View) -> io::Result<()> {
-        self.render(&mut display.rendering_buffer())
+    /* A new line at row, pushing the ones from there on down. Rows
+       past the end append. */
+    fn insert_line(&mut self, row: usize, text: String) {
+        let row = cmp::min(row, self.lines.len());
+        self.perform(Edit::InsertLine { row, text });
     }
 
-}
+    /* Puts text in at row and column, which may take it over several
+       lines: the first joins what comes before column, the last what
+       comes after. Any line ending counts as one; tabs stay tabs.
+       Answers where the text ends. */
+    fn insert_text(&mut self, row: usize, column: usize, text: &str) -> Position {
+        self.grow_to(row);
+        let column = cmp::min(column, self.line_char_len(row));
 
-impl From<event::Event> for Message {
-    /* This thing could be smarter; it could re-map the key-events to something
-       more easily processable. */
-    fn from(value: event::Event) -> Self {
-        Message::ExternalEvent(value)
-    }
-}
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let mut pieces = text.split('\n').collect::<Vec<_>>();
+        let last = pieces.pop().unwrap_or_default();
 
-fn main() -> io::Result<()> {
-    let args = std::env::args();
-    println!("Args: {:?}", args);
+        let line = &self.lines[row];
+        let (head, tail) = line.split_at(byte_offset(line, column));
+        let (head, tail) = (head.to_owned(), tail.to_owned());
 
-    let out = io::BufWriter::with_capacity(16384, io::stdout());
-    tui::Screen::attach(out)?
-        .enter_raw_mode()?
-        .run_automat::<Editor>()
+        if pieces.is_empty() {
+            self.replace_line(row, format!("{}{}{}", head, last, tail));
+            return Position { column: column + last.chars().count(), row };
+        }
+
+        self.replace_line(row, format!("{}{}", head, pieces[0]));
+        for (offset, piece) in pieces.iter().enumerate().skip(1) {
+            self.insert_line(row + offset, piece.to_string());
+        }
+        let row = row + pieces.len();
+        self.insert_line(row, format!("{}{}", last, tail));
+
+        Position { column: last.chars().count(), row }
+    }
+
+    /* The text from start up to end, end not included; a position
+       past the end of a line stands for its line break. */
+    fn text_between(&self, start: &Position, end: &Position) -> String {
+        let slice = |row: usize, columns: Range<usize>| {
+            let line = self.line(row);
+            line[byte_offset(line, columns.start)..byte_offset(line, columns.end)].to_owned()
+        };
+
+        if start.row == end.row {
+            return slice(start.row, start.column..end.column);
+        }
+
+        let mut pieces = vec![slice(start.row, start.column..self.line_char_len(start.row))];
+        pieces.extend((start.row + 1..end.row).map(|row| self.line(row).to_owned()));
+        pieces.push(slice(end.row, 0..end.column));
+        pieces.join("\n")
+    }
+
+    /* Takes out what text_between would answer, joining what is left
+       of start's line and end's. */
+    fn delete_between(&mut self, start: &Position, end: &Position) {
+        if start.row >= self.lines.len() {
+            return;
+        }
+
+        let head = &self.lines[start.row];
+        let head = head[..byte_offset(head, start.column)].to_owned();
+        let tail = self.line(end.row);
+        let tail = &tail[byte_offset(tail, end.column)..];
+        self.replace_line(start.row, format!("{}{}", head, tail));
+
+        for row in (start.row + 1..=cmp::min(end.row, self.lines.len() - 1)).rev() {
+            let text = self.lines[row].to_owned();
+            self.perform(Edit::RemoveLine { row, text });
+        }
+    }
+
+    /* The bracket that pairs with the one at row, column, counting
+       brackets of the same kind in between so that nested pairs are
+       skipped. Other kinds are not counted, so that a ( and a ] are no
+       pair, as in vi. None when there is no bracket there or nothing
+       pairs with it. */
+    fn matching_bracket(&self, row: usize, column: usize) -> Option<Position> {
+        let bracket = self.line(row).chars().nth(column)?;
+        let (partner, forward) = match bracket {
+            '('        => (')', true),
+            '['        => (']', true),
+            '{'        => ('}', true),
+            ')'        => ('(', false),
+            ']'        => ('[', false),
+            '}'        => ('{', false),
+            _otherwise => return None,
+        };
+
+        let mut depth = 0;
+        let mut closes = |c: char| {
+            if c == bracket {
+                depth += 1;
+            } else if c == partner {
+                depth -= 1;
+            }
+            depth == 0
+        };
+
+        let from = Position { column, row };
+        if forward {
+            for row in from.row..self.lines.len() {
+                let line = &self.lines[row];
+                let skip = if row == from.row { from.column } else { 0 };
+                for (column, c) in line.chars().enumerate().skip(skip) {
+                    if closes(c) {
+                        return Some(Position { column, row });
+                    }
+                }
+            }
+        } else {
+            for row in (0..=from.row).rev() {
+                let line = &self.lines[row];
+                let end = if row == from.row { byte_offset(line, from.column + 1) } else { line.len() };
+                let mut column = line[..end].chars().count();
+                for c in line[..end].chars().rev() {
+                    column -= 1;
+                    if closes(c) {
+                        return Some(Position { column, row });
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /* Spaces and tabs at the end of any line go. Answers how many
+       lines lost some. */
+    fn strip_trailing_whitespace(&mut self) -> usize {
+        let mut stripped = 0;
+        for row in 0..self.lines.len() {
+            let line = &self.lines[row];
+            let kept = line.trim_end_matches([' ', '\t']);
+            if kept.len() < line.len() {
+                let after = kept.to_owned();
+                self.replace_line(row, after);
+                stripped += 1;
+            }
+        }
+
+        stripped
+    }
+
+    /* Drops everything from column to the end of the line. */
+    fn truncate_line(&mut self, row: usize, column: usize) {
+        if column >= self.line_char_len(row) {
+            return;
+        }
+
+        let line = &self.lines[row];
+        let after = line[..byte_offset(line, column)].to_owned();
+        self.replace_line(row, after);
+    }
+
+    /* Removes row altogether; the only line left is emptied instead,
+       since a buffer always has one. Answers the row the cursor goes
+       to: the line that took its place, or the new last line. */
+    fn delete_line(&mut self, row: usize) -> usize {
+        if row >= self.lines.len() {
+            return row;
+        }
+
+        if self.lines.len() == 1 {
+            self.replace_line(row, String::new());
+            return row;
+        }
+
+        let text = self.lines[row].to_owned();
+        self.perform(Edit::RemoveLine { row, text });
+        cmp::min(row, self.lines.len() - 1)
+    }
+
+    fn replace_line(&mut self, row: usize, after: String) {
+        let before = self.lines[row].to_owned();
+        self.perform(Edit::ReplaceLine { row, before, after });
+    }
+
+    /* Add empty lines until row exists. */
+    fn grow_to(&mut self, row: usize) {
+        for row in self.lines.len()..=row {
+            self.perform(Edit::InsertLine { row, text: String::new() });
+        }
+    }
+
+    fn perform(&mut self, edit: Edit) {
+        self.apply(&edit);
+        self.journal.push(edit);
+    }
+
+    /* The edits performed since the journal was last taken. */
+    fn take_journal(&mut self) -> Vec<Edit> {
+        mem::take(&mut self.journal)
+    }
+
+    /* Applies an edit without journaling it. */
+    fn apply(&mut self, edit: &Edit) {
+        match edit {
+            Edit::InsertChar { at, c } => {
+                let line = self.lines.line_mut(at.row);
+                line.insert(byte_offset(line, at.column), *c);
+            }
+
+            Edit::DeleteChar { at, .. } => {
+                let line = self.lines.line_mut(at.row);
+                line.remove(byte_offset(line, at.column));
+            }
+
+            Edit::SplitLine { at } => {
+                let line = self.lines.line_mut(at.row);
+                let tail = line.split_off(byte_offset(line, at.column));
+                self.lines.insert(at.row + 1, tail);
+            }
+
+            Edit::JoinLine { at } => {
+                let tail = self.lines.remove(at.row + 1);
+                self.lines.line_mut(at.row).push_str(&tail);
+            }
+
+            Edit::InsertLine { row, text } =>
+                self.lines.insert(*row, text.clone()),
+
+            Edit::RemoveLine { row, .. } => {
+                self.lines.remove(*row);
+            }
+
+            Edit::ReplaceLine { row, after, .. } =>
+                *self.lines.line_mut(*row) = after.clone(),
+        }
+    }
+}
+
+impl Default for EditingModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/* A single change to an EditingModel, recorded so that it can be
+   undone by applying its inverse. JoinLine joins the line below
+   onto at.row, whose length is at.column. */
+#[derive(Clone, Debug)]
+enum Edit {
+    InsertChar  { at: Position, c: char },
+    DeleteChar  { at: Position, c: char },
+    SplitLine   { at: Position },
+    JoinLine    { at: Position },
+    InsertLine  { row: usize, text: String },
+    RemoveLine  { row: usize, text: String },
+    ReplaceLine { row: usize, before: String, after: String },
+}
+
+impl Edit {
+    /* The line it happens on, or the first of two it joins. */
+    fn row(&self) -> usize {
+        match self {
+            Edit::InsertChar  { at, .. } |
+            Edit::DeleteChar  { at, .. } |
+            Edit::SplitLine   { at }     |
+            Edit::JoinLine    { at }     => at.row,
+            Edit::InsertLine  { row, .. } |
+            Edit::RemoveLine  { row, .. } |
+            Edit::ReplaceLine { row, .. } => *row,
+        }
+    }
+
+    fn invert(&self) -> Edit {
+        match self.clone() {
+            Edit::InsertChar { at, c }     => Edit::DeleteChar { at, c },
+            Edit::DeleteChar { at, c }     => Edit::InsertChar { at, c },
+            Edit::SplitLine { at }         => Edit::JoinLine { at },
+            Edit::JoinLine { at }          => Edit::SplitLine { at },
+            Edit::InsertLine { row, text } => Edit::RemoveLine { row, text },
+            Edit::RemoveLine { row, text } => Edit::InsertLine { row, text },
+            Edit::ReplaceLine { row, before, after } =>
+                Edit::ReplaceLine { row, before: after, after: before },
+        }
+    }
+}
+
+/* The edits made by one command, along with where the cursor was
+   before and after. */
+struct Change {
+    edits:  Vec<Edit>,
+    before: Position,
+    after:  Position,
+}
+
+#[derive(Default)]
+struct UndoHistory {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+impl UndoHistory {
+    /* A new change makes anything undone unreachable. */
+    fn record(&mut self, change: Change) {
+        self.undo.push(change);
+        self.redo.clear();
+    }
+
+    fn undo(&mut self, contents: &mut EditingModel) -> Option<Position> {
+        let change = self.undo.pop()?;
+        for edit in change.edits.iter().rev() {
+            contents.apply(&edit.invert());
+        }
+
+        let cursor = change.before.clone();
+        self.redo.push(change);
+        Some(cursor)
+    }
+
+    fn redo(&mut self, contents: &mut EditingModel) -> Option<Position> {
+        let change = self.redo.pop()?;
+        for edit in &change.edits {
+            contents.apply(edit);
+        }
+
+        let cursor = change.after.clone();
+        self.undo.push(change);
+        Some(cursor)
+    }
+}
+
+#[derive(PartialEq)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+impl CharClass {
+    fn of(c: char) -> Self {
+        if c.is_whitespace() {
+            Self::Whitespace
+        } else if c.is_alphanumeric() || c == '_' {
+            Self::Word
+        } else {
+            Self::Punctuation
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Position {
+    column:      usize,
+    row:         usize,
+}
+
+impl Position {
+    fn is_after(&self, other: &Position) -> bool {
+        (self.row, self.column) > (other.row, other.column)
+    }
+}
+
+impl Position {
+    fn move_up(&mut self, by: usize) { self.row = self.row.saturating_sub(by) }
+
+    fn move_down(&mut self, by: usize, bounds: &ScreenSize) {
+        self.row = cmp::min(self.row + by, bounds.rows.saturating_sub(1))
+    }
+}
+
+/* goal_column is the screen column the cursor was last put on by
+   anything but a vertical move; going up and down returns to it
+   wherever the line is long enough. scrolloff is how many lines
+   stepping up or down keeps in view past the cursor. */
+struct NavigationModel {
+    cursor:      Position,
+    screen_size: ScreenSize,
+    viewport:    EditingViewport,
+    scroll_step: usize,
+    scrolloff:   usize,
+    goal_column: usize,
+}
+
+impl NavigationModel {
+    fn is_topmost(&self)    -> bool { self.cursor.row == 0                                                }
+    fn is_bottommost(&self) -> bool { self.screen_size.rows.checked_sub(1) == Some(self.cursor.row)       }
+    fn is_rightmost(&self)  -> bool { self.screen_size.columns.checked_sub(1) == Some(self.cursor.column) }
+
+    fn cursor_in_buffer(&self) -> Position {
+        Position {
+            column: self.viewport.column_offset + self.cursor.column,
+            row:    self.viewport.row_offset + self.cursor.row,
+        }
+    }
+
+    /* Place the cursor on a buffer position, scrolling the viewport
+       just enough to bring it on screen. */
+    fn jump_to(&mut self, target: &Position) {
+        self.scroll_to(target);
+        self.goal_column = target.column;
+    }
+
+    /* The same, but the goal column stays where it was. */
+    fn scroll_to(&mut self, target: &Position) {
+        let rows    = cmp::max(self.screen_size.rows, 1);
+        let columns = cmp::max(self.screen_size.columns, 1);
+        let viewport = &mut self.viewport;
+
+        if target.row < viewport.row_offset {
+            viewport.row_offset = target.row;
+        } else if target.row >= viewport.row_offset + rows {
+            viewport.row_offset = target.row + 1 - rows;
+        }
+
+        /* Wrapped lines never scroll sideways; rows are sorted out by
+           fit_cursor, which knows how tall the lines are. */
+        if viewport.wrap {
+            viewport.column_offset = 0;
+        } else if target.column < viewport.column_offset {
+            viewport.column_offset = target.column;
+        } else if target.column >= viewport.column_offset + columns {
+            viewport.column_offset = target.column + 1 - columns;
+        }
+
+        self.cursor.row    = target.row - viewport.row_offset;
+        self.cursor.column = target.column - viewport.column_offset;
+    }
+
+    /* The buffer position under a screen cell of the text area. Past
+       the end of a line snaps to its end, below the last line to the
+       last line. */
+    fn position_at(&self, column: usize, row: usize, contents: &EditingModel) -> Position {
+        if self.viewport.wrap {
+            let rows = self.visual_rows(contents);
+            if let Some(shown) = rows.get(row).or(rows.last()) {
+                let last = if shown.columns.end == self.line_width(shown.row, contents) {
+                    shown.columns.end
+                } else {
+                    shown.columns.end.saturating_sub(1)
+                };
+                let column = cmp::min(shown.columns.start + column, last);
+                return Position { column, row: shown.row };
+            }
+        }
+
+        let row = cmp::min(self.viewport.row_offset + row, contents.line_count().saturating_sub(1));
+        let column = cmp::min(self.viewport.column_offset + column, self.line_width(row, contents));
+        Position { column, row }
+    }
+
+    fn wrapped_line(&self, row: usize, contents: &EditingModel) -> Vec<Range<usize>> {
+        let line = expand_tabs(contents.line(row), self.viewport.tab_width);
+        wrap_columns(&line, self.screen_size.columns)
+    }
+
+    /* What each row of the screen shows when lines wrap, from the
+       top of the viewport down to the bottom of the screen. */
+    fn visual_rows(&self, contents: &EditingModel) -> Vec<VisualRow> {
+        let mut rows = vec![];
+        for row in self.viewport.row_offset..contents.line_count() {
+            for columns in self.wrapped_line(row, contents) {
+                if rows.len() == self.screen_size.rows {
+                    return rows;
+                }
+                rows.push(VisualRow { row, columns });
+            }
+        }
+
+        rows
+    }
+
+    /* Where the cursor is drawn, relative to the text area. */
+    fn cursor_on_screen(&self, contents: &EditingModel) -> Position {
+        if !self.viewport.wrap {
+            return self.cursor.clone();
+        }
+
+        let here = self.cursor_in_buffer();
+        let rows = self.wrapped_line(here.row, contents);
+        let index = wrapped_row(&rows, here.column);
+        let above = (self.viewport.row_offset..here.row)
+            .map(|row| self.wrapped_line(row, contents).len())
+            .sum::<usize>();
+
+        Position { column: here.column - rows[index].start, row: above + index }
+    }
+
+    /* With wrapping on, a line can take more than one row, so
+       jump_to may leave the cursor below the screen. This scrolls
+       down until it isn't. */
+    fn fit_cursor(&mut self, contents: &EditingModel) {
+        if !self.viewport.wrap {
+            return;
+        }
+
+        let here = self.cursor_in_buffer();
+        loop {
+            self.cursor.row = here.row - self.viewport.row_offset;
+            if self.viewport.row_offset == here.row
+                || self.cursor_on_screen(contents).row < self.screen_size.rows
+            {
+                break;
+            }
+            self.viewport.row_offset += 1;
+        }
+    }
+
+    fn set_wrap(&mut self, wrap: bool) {
+        let here = self.cursor_in_buffer();
+        self.viewport.wrap = wrap;
+        self.jump_to(&here);
+    }
+
+    /* Up or down a row of the screen rather than a line, staying
+       as far into the row as before. */
+    fn move_visually(&mut self, up: bool, contents: &EditingModel) {
+        let here = self.cursor_in_buffer();
+        let rows = self.wrapped_line(here.row, contents);
+        let index = wrapped_row(&rows, here.column);
+        let into_row = here.column - rows[index].start;
+
+        let (row, rows, index) = if up && index > 0 {
+            (here.row, rows, index - 1)
+        } else if up && here.row > 0 {
+            let rows = self.wrapped_line(here.row - 1, contents);
+            let index = rows.len() - 1;
+            (here.row - 1, rows, index)
+        } else if !up && index + 1 < rows.len() {
+            (here.row, rows, index + 1)
+        } else if !up && here.row + 1 < contents.line_count() {
+            (here.row + 1, self.wrapped_line(here.row + 1, contents), 0)
+        } else {
+            return;
+        };
+
+        /* Only the last row of a line has room past its last char. */
+        let columns = &rows[index];
+        let last = if index + 1 == rows.len() { columns.end } else { columns.end.saturating_sub(1) };
+        let column = cmp::min(columns.start + into_row, cmp::max(last, columns.start));
+        self.jump_to(&Position { column, row });
+    }
+
+    fn line_width(&self, row: usize, contents: &EditingModel) -> usize {
+        let line = contents.line(row);
+        display_column(line, line.chars().count(), self.viewport.tab_width)
+    }
+
+    /* Buffer positions count columns in chars, the cursor counts them
+       on screen; the two part ways on tabs. */
+    fn to_buffer(&self, position: &Position, contents: &EditingModel) -> Position {
+        let line = contents.line(position.row);
+        let column = char_column(line, position.column, self.viewport.tab_width);
+        Position { column, row: position.row }
+    }
+
+    fn to_screen(&self, position: &Position, contents: &EditingModel) -> Position {
+        let line = contents.line(position.row);
+        let column = display_column(line, position.column, self.viewport.tab_width);
+        Position { column, row: position.row }
+    }
+
+    /* Keeps the cursor off the middle of a tab after moving onto a
+       different line. */
+    fn snap_to_char(&mut self, contents: &EditingModel) {
+        let here = self.cursor_in_buffer();
+        let snapped = self.to_screen(&self.to_buffer(&here, contents), contents);
+        if snapped != here {
+            self.scroll_to(&snapped);
+        }
+    }
+
+    /* Scrolling the view keeps the cursor on its line for as long as
+       that line stays on screen. */
+    fn scroll_view_up(&mut self, contents: &EditingModel) {
+        let row = self.cursor_in_buffer().row;
+        self.viewport.scroll_up(self.scroll_step);
+        self.keep_cursor_on(row);
+        self.snap_to_char(contents);
+    }
+
+    fn scroll_view_down(&mut self, contents: &EditingModel) {
+        let row = self.cursor_in_buffer().row;
+        self.viewport.scroll_down(self.scroll_step, Self::row_limit(contents));
+        self.keep_cursor_on(row);
+        self.snap_to_char(contents);
+    }
+
+    /* Should anything have scrolled the view past the end of the
+       file, or the file have shrunk out from under it, pulls it back
+       until the last line is at the top, with the cursor no further
+       down than that line. */
+    fn clamp_viewport(&mut self, contents: &EditingModel) {
+        let limit = Self::row_limit(contents);
+        if self.viewport.row_offset <= limit {
+            return;
+        }
+
+        let here = self.cursor_in_buffer();
+        self.viewport.row_offset = limit;
+        self.scroll_to(&Position { row: cmp::min(here.row, limit), ..here });
+        self.snap_to_char(contents);
+    }
+
+    /* The last line may scroll up to the top of the screen, but no
+       further. */
+    fn row_limit(contents: &EditingModel) -> usize {
+        contents.line_count().saturating_sub(1)
+    }
+
+    /* Far enough right that the end of the cursor's line can sit in
+       the rightmost column. */
+    fn column_limit(&self, contents: &EditingModel) -> usize {
+        let line_width = self.line_width(self.cursor_in_buffer().row, contents);
+        (line_width + 1).saturating_sub(self.screen_size.columns)
+    }
+
+    /* No more than leaves a row for the cursor between the two
+       margins. */
+    fn margin(&self) -> usize {
+        cmp::min(self.scrolloff, self.screen_size.rows.saturating_sub(1) / 2)
+    }
+
+    fn keep_cursor_on(&mut self, row: usize) {
+        let on_screen = row.saturating_sub(self.viewport.row_offset);
+        self.cursor.row = cmp::min(on_screen, self.screen_size.rows.saturating_sub(1));
+    }
+
+    /* Back out to the goal column on the line the cursor is on now,
+       or as near to it as the line is long. */
+    fn return_to_goal(&mut self, contents: &EditingModel) {
+        let mut target = self.cursor_in_buffer();
+        target.column = cmp::min(self.goal_column, self.line_width(target.row, contents));
+        self.scroll_to(&target);
+    }
+
+    fn move_intended(&mut self, direction: &Direction, contents: &EditingModel) {
+        match direction {
+            Direction::Up | Direction::Down if self.viewport.wrap =>
+                self.move_visually(direction == &Direction::Up, contents),
+
+            /* Within scrolloff of either edge the view scrolls
+               instead, for as long as there is more file that way. */
+            Direction::Up    => {
+                let in_margin = self.cursor.row <= self.margin() && self.viewport.row_offset > 0;
+                if self.is_topmost() || in_margin {
+                    self.viewport.scroll_up(1)
+                } else {
+                    self.cursor.move_up(1)
+                }
+                self.return_to_goal(contents);
+            }
+
+            Direction::Down  => {
+                let below = self.viewport.row_offset + self.screen_size.rows < contents.line_count();
+                let in_margin = self.cursor.row + self.margin() + 1 >= self.screen_size.rows && below;
+                if self.is_bottommost() || in_margin {
+                    self.viewport.scroll_down(1, Self::row_limit(contents))
+                } else {
+                    self.cursor.move_down(1, &self.screen_size)
+                }
+                self.return_to_goal(contents);
+            }
+
+            /* Left and right step over whole chars, tabs included. */
+            Direction::Left  => {
+                let here = self.cursor_in_buffer();
+                let mut target = self.to_buffer(&here, contents);
+                if self.to_screen(&target, contents) == here {
+                    target.column = target.column.saturating_sub(1);
+                }
+                self.jump_to(&self.to_screen(&target, contents));
+            }
+
+            Direction::Right => {
+                let here = self.cursor_in_buffer();
+                let mut target = self.to_buffer(&here, contents);
+                target.column += 1;
+                let target = self.to_screen(&target, contents);
+                let scroll = target.column - here.column;
+
+                let allowed = if self.viewport.wrap {
+                    target.column <= self.line_width(here.row, contents)
+                } else {
+                    !self.is_rightmost() || self.viewport.column_offset + scroll <= self.column_limit(contents)
+                };
+                if allowed {
+                    self.jump_to(&target);
+                }
+            }
+
+            /* A page is a screen less one line, which stays in view
+               for context. The cursor keeps its place on the screen
+               unless the view runs into either end of the file. */
+            Direction::PageUp => {
+                let page = cmp::max(self.screen_size.rows.saturating_sub(1), 1);
+                let mut target = self.cursor_in_buffer();
+                target.row = target.row.saturating_sub(page);
+                self.viewport.scroll_up(page);
+                self.scroll_to(&target);
+                self.return_to_goal(contents);
+            }
+
+            Direction::PageDown => {
+                let page = cmp::max(self.screen_size.rows.saturating_sub(1), 1);
+                let last_offset = contents.line_count().saturating_sub(self.screen_size.rows);
+                let mut target = self.cursor_in_buffer();
+                target.row = cmp::min(target.row + page, Self::row_limit(contents));
+                self.viewport.row_offset = cmp::max(
+                    self.viewport.row_offset,
+                    cmp::min(self.viewport.row_offset + page, last_offset),
+                );
+                self.scroll_to(&target);
+                self.return_to_goal(contents);
+            }
+
+            Direction::LineStart => {
+                let mut target = self.cursor_in_buffer();
+                target.column = 0;
+                self.jump_to(&target);
+            }
+
+            Direction::LineEnd => {
+                let mut target = self.cursor_in_buffer();
+                target.column = self.line_width(target.row, contents);
+                self.jump_to(&target);
+            }
+        }
+
+        self.snap_to_char(contents);
+    }
+
+    /* Put the cursor at the start of row, scrolling so that it
+       ends up in the middle of the screen where possible. */
+    fn go_to_line(&mut self, row: usize, contents: &EditingModel) {
+        let row = cmp::min(row, contents.line_count().saturating_sub(1));
+        self.jump_to(&Position { column: 0, row });
+        self.center_on_cursor(contents);
+    }
+
+    /* Scrolls the cursor's line to the middle of the screen, except
+       near either end of the file, where the screen stays full. */
+    fn center_on_cursor(&mut self, contents: &EditingModel) {
+        let here = self.cursor_in_buffer();
+        let rows = self.screen_size.rows;
+        let last_offset = contents.line_count().saturating_sub(rows);
+
+        self.viewport.row_offset = cmp::min(here.row.saturating_sub(rows / 2), last_offset);
+        self.cursor.row = here.row - self.viewport.row_offset;
+    }
+
+    /* A smaller screen may leave the cursor off the edge of it;
+       scroll so that it stays on the same spot in the buffer. */
+    fn screen_size_changed(&mut self, new_size: ScreenSize) {
+        let here = self.cursor_in_buffer();
+        self.screen_size = new_size;
+        self.scroll_to(&here);
+    }
+
+    /* Fresh, but set up the way other is, for a buffer opened after
+       the config was read. */
+    fn configured_like(other: &NavigationModel) -> Self {
+        let mut navigation = Self::default();
+        navigation.viewport.tab_width = other.viewport.tab_width;
+        navigation.viewport.wrap = other.viewport.wrap;
+        navigation.scroll_step = other.scroll_step;
+        navigation.scrolloff = other.scrolloff;
+        navigation
+    }
+}
+
+impl Default for NavigationModel {
+    fn default() -> Self {
+        Self {
+            cursor:      Default::default(),
+            screen_size: Default::default(),
+            viewport:    Default::default(),
+            scroll_step: 3,
+            scrolloff:   0,
+            goal_column: 0,
+        }
+    }
+}
+
+/* An event and when it was recorded. */
+struct KeyEvent(InputEvent, time::Instant);
+
+impl Display for KeyEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            InputEvent::Key(key) => write!(f, "{:?} [{:?}]", key.code, key.modifiers),
+            input                => write!(f, "{:?}", input),
+        }
+    }
+}
+
+/* The last horizon events, oldest first. With show_intervals, the
+   time since the one before goes in front of each, for telling how
+   far apart keys really arrive. */
+struct KeyHistory {
+    events:         VecDeque<KeyEvent>,
+    horizon:        usize,
+    show_intervals: bool,
+    /* Where the time each event was recorded at comes from. */
+    clock:          Box<dyn Fn() -> time::Instant>,
+}
+
+impl KeyHistory {
+    fn with_horizon<F>(horizon: usize, clock: F) -> Self
+    where
+        F: Fn() -> time::Instant + 'static,
+    {
+        Self { events: VecDeque::new(), horizon, show_intervals: false, clock: Box::new(clock) }
+    }
+
+    fn record(&mut self, event: &InputEvent) {
+        self.events.push_back(KeyEvent(event.clone(), (self.clock)()));
+        while self.events.len() > self.horizon {
+            self.events.pop_front();
+        }
+
+        if let Some(interval) = self.last_interval() {
+            log::debug!("{:?} after {}ms", event, interval.as_millis());
+        }
+    }
+
+    /* Between the last two events still kept. */
+    fn last_interval(&self) -> Option<time::Duration> {
+        let mut newest = self.events.iter().rev();
+        let KeyEvent(_, last) = newest.next()?;
+        let KeyEvent(_, before) = newest.next()?;
+        Some(last.saturating_duration_since(*before))
+    }
+}
+
+impl Display for KeyHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        let mut previous = None;
+        for event in &self.events {
+            if let Some(previous) = previous.filter(|_| self.show_intervals) {
+                write!(f, "+{}ms ", event.1.saturating_duration_since(previous).as_millis())?;
+            }
+            write!(f, "{},", event)?;
+            previous = Some(event.1);
+        }
+        write!(f, "]")
+    }
+}
+
+impl Default for KeyHistory {
+    fn default() -> Self {
+        Self::with_horizon(3, time::Instant::now)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum PromptKind {
+    GotoLine,
+    Search,
+    Replace,
+    /* Whether to take the swap file of the buffer at this index. */
+    Recover(usize),
+    Command,
+    /* Whether to throw away unsaved changes and read the file again. */
+    Reload,
+}
+
+/* A line of input read on the bottom row, turned into a Message
+   once submitted. */
+struct Prompt {
+    kind:   PromptKind,
+    input:  String,
+    /* Where the cursor was when it opened, for a search to go on
+       from while it is typed, and back to if it is called off. */
+    origin: Position,
+}
+
+impl Prompt {
+    fn new(kind: PromptKind) -> Self {
+        Self { kind, input: String::new(), origin: Position::default() }
+    }
+
+    fn label(&self) -> &str {
+        match self.kind {
+            PromptKind::GotoLine   => "Go to line: ",
+            PromptKind::Search     => "Search: ",
+            PromptKind::Replace    => "Replace (s/from/to/ or s/from/to/g): ",
+            PromptKind::Recover(_) => "Found unsaved changes in a swap file; recover them? (y/n): ",
+            PromptKind::Command    => ":",
+            PromptKind::Reload     => "Discard unsaved changes and reload? (y/n): ",
+        }
+    }
+
+    fn is_search(&self) -> bool {
+        matches!(self.kind, PromptKind::Search)
+    }
+
+    /* Line numbers are 1-based on screen, 0-based everywhere else. */
+    fn submit(&self) -> Option<Message> {
+        match self.kind {
+            PromptKind::GotoLine =>
+                self.input.trim().parse::<usize>().ok()
+                    .map(|line| Message::GotoLine(line.saturating_sub(1))),
+            PromptKind::Search =>
+                Some(Message::Search(self.input.clone())),
+            PromptKind::Replace =>
+                Self::parse_substitution(&self.input),
+            PromptKind::Recover(index) =>
+                self.input.trim().eq_ignore_ascii_case("y")
+                    .then_some(Message::RecoverSwap(index)),
+            PromptKind::Reload =>
+                self.input.trim().eq_ignore_ascii_case("y")
+                    .then_some(Message::Reload),
+            PromptKind::Command if self.input.trim().is_empty() =>
+                None,
+            PromptKind::Command =>
+                Some(Command::parse(&self.input)
+                    .map_or_else(|| Message::UnknownCommand(self.input.trim().to_owned()), Message::Command)),
+        }
+    }
+
+    fn parse_substitution(input: &str) -> Option<Message> {
+        let mut parts = input.strip_prefix("s/")?.splitn(3, '/');
+        let from = parts.next()?.to_owned();
+        let to = parts.next()?.to_owned();
+        let all = parts.next() == Some("g");
+
+        Some(Message::Replace { from, to, all })
+    }
+}
+
+/* What the : prompt understands, as in vi. A file name is the rest
+   of the line after the command, spaces and all. */
+#[derive(Clone, Debug, PartialEq)]
+enum Command {
+    Write(Option<path::PathBuf>),
+    ForceWrite,
+    Quit,
+    ForceQuit,
+    WriteQuit,
+    Edit(path::PathBuf),
+    Reload,
+}
+
+impl Command {
+    fn parse(input: &str) -> Option<Self> {
+        let input = input.trim();
+        let (name, file) = match input.split_once(char::is_whitespace) {
+            Some((name, file)) => (name, Some(path::PathBuf::from(file.trim_start()))),
+            None               => (input, None),
+        };
+
+        match (name, file) {
+            ("w" | "write", file)      => Some(Command::Write(file)),
+            ("w!" | "write!", None)    => Some(Command::ForceWrite),
+            ("q" | "quit", None)       => Some(Command::Quit),
+            ("q!" | "quit!", None)     => Some(Command::ForceQuit),
+            ("wq", None)               => Some(Command::WriteQuit),
+            ("e" | "edit", Some(file)) => Some(Command::Edit(file)),
+            ("e!" | "edit!", None)     => Some(Command::Reload),
+            _otherwise                 => None,
+        }
+    }
+}
+
+/* Enough for any count anyone means, without 99999999j keeping the
+   editor busy for a minute. */
+const MAX_COUNT: usize = 10_000;
+
+/* How long the first key of a sequence waits for the second. */
+const PENDING_KEY_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+/* The first key of a sequence like gg, held until the next key says
+   which sequence it is. Like StatusMessage, every key held gets a
+   serial, so that an older key's timeout leaves a newer one be. */
+#[derive(Default)]
+struct PendingKeys {
+    first:  Option<InputEvent>,
+    serial: usize,
+}
+
+impl PendingKeys {
+    fn hold(&mut self, key: InputEvent) -> usize {
+        self.first = Some(key);
+        self.serial += 1;
+        self.serial
+    }
+
+    fn take(&mut self) -> Option<InputEvent> {
+        self.first.take()
+    }
+
+    fn expire(&mut self, serial: usize) -> Option<InputEvent> {
+        if serial == self.serial { self.take() } else { None }
+    }
+}
+
+/* Text picked out by moving the cursor away from where it was when
+   selecting began. Both ends are selected, as in vi, wherever the
+   head is. */
+#[derive(Clone, Debug, PartialEq)]
+struct Selection {
+    anchor: Position,
+    head:   Position,
+}
+
+impl Selection {
+    fn at(cursor: Position) -> Self {
+        Self { anchor: cursor.clone(), head: cursor }
+    }
+
+    /* The first selected position and the one just past the last. */
+    fn bounds(&self, contents: &EditingModel) -> (Position, Position) {
+        let (start, last) = if self.head.is_after(&self.anchor) {
+            (&self.anchor, &self.head)
+        } else {
+            (&self.head, &self.anchor)
+        };
+
+        let end = if last.column < contents.line_char_len(last.row) {
+            Position { column: last.column + 1, row: last.row }
+        } else if last.row + 1 < contents.line_count() {
+            Position { column: 0, row: last.row + 1 }
+        } else {
+            Position { column: contents.line_char_len(last.row), row: last.row }
+        };
+        (start.clone(), end)
+    }
+
+    /* The chars of row that are selected: some of the first and last
+       lines, all of the ones in between. */
+    fn columns_on(&self, row: usize, contents: &EditingModel) -> Option<Range<usize>> {
+        let (start, end) = self.bounds(contents);
+        let length = contents.line_char_len(row);
+        match row {
+            row if row < start.row || row > end.row   => None,
+            row if row == end.row && row != start.row => Some(0..end.column),
+            row if row == end.row                     => Some(start.column..end.column),
+            row if row == start.row                   => Some(cmp::min(start.column, length)..length),
+            _otherwise                                => Some(0..length),
+        }
+    }
+}
+
+/* What yank put aside for paste. Whole lines paste as lines of their
+   own, above or below the cursor's; anything else goes in at the
+   cursor, wherever that is. */
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Register {
+    text:     String,
+    linewise: bool,
+}
+
+impl Register {
+    /* The clipboard has no notion of lines, so whole lines go there
+       with a line break after the last and come back as lines if they
+       end in one. */
+    fn to_clipboard(&self) -> String {
+        if self.linewise { format!("{}\n", self.text) } else { self.text.clone() }
+    }
+
+    fn from_clipboard(text: &str) -> Self {
+        let text = text.replace("\r\n", "\n");
+        match text.strip_suffix('\n') {
+            Some(lines) => Self { text: lines.to_owned(), linewise: true },
+            None        => Self { text, linewise: false },
+        }
+    }
+}
+
+/* How long a message stays on the status bar. */
+const STATUS_MESSAGE_TIMEOUT: time::Duration = time::Duration::from_secs(3);
+
+/* How long the status bar stays flashed for a visual bell. */
+const BELL_FLASH: time::Duration = time::Duration::from_millis(100);
+
+/* Turns on the status bar, one frame at a time, while a file loads. */
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+/* Whatever the status bar has to say, for a while. Every message shown
+   gets a new serial, so that when an older one's time is up, clearing
+   it leaves a newer one alone. */
+#[derive(Default)]
+struct StatusMessage {
+    text:   Option<String>,
+    serial: usize,
+}
+
+impl StatusMessage {
+    fn show(&mut self, text: String) {
+        self.text = Some(text);
+        self.serial += 1;
+    }
+
+    fn clear(&mut self) {
+        self.text = None;
+    }
+
+    fn expire(&mut self, serial: usize) {
+        if serial == self.serial {
+            self.clear();
+        }
+    }
+
+    fn text(&self) -> &str {
+        self.text.as_deref().unwrap_or("")
+    }
+}
+
+struct SearchModel {
+    query:   String,
+    matches: Vec<Position>,
+}
+
+impl SearchModel {
+    fn new(query: &str, contents: &EditingModel) -> Self {
+        Self { query: query.to_owned(), matches: contents.find_all(query) }
+    }
+
+    fn refresh(&mut self, contents: &EditingModel) {
+        self.matches = contents.find_all(&self.query);
+    }
+
+    /* The first match past from, wrapping around to the top. The
+       flag tells whether it had to wrap. */
+    fn next_after(&self, from: &Position) -> Option<(&Position, bool)> {
+        self.matches.iter()
+            .find(|position| position.is_after(from))
+            .map(|position| (position, false))
+            .or_else(|| self.matches.first().map(|position| (position, true)))
+    }
+
+    /* Char ranges of the matches within line. */
+    fn match_columns(&self, line: &str) -> Vec<Range<usize>> {
+        let length = self.query.chars().count();
+        if length == 0 {
+            return vec![];
+        }
+
+        line.match_indices(&self.query)
+            .map(|(index, _)| line[..index].chars().count())
+            .map(|column| column..column + length)
+            .collect()
+    }
+}
+
+struct OpenFile {
+    file_paths: Vec<path::PathBuf>,
+    read_only:  bool,
+}
+
+impl OpenFile {
+    /* Naming a file twice opens it once; two buffers saving over the
+       same file would each lose the other's edits. */
+    fn from_args<I: Iterator<Item = String>>(args: I) -> Self {
+        let mut file_paths: Vec<path::PathBuf> = vec![];
+        let mut read_only = false;
+        for arg in args.skip(1) {
+            if arg == "--readonly" {
+                read_only = true;
+                continue;
+            }
+
+            let file_path = path::PathBuf::from(arg);
+            if !file_paths.iter().any(|open| same_file(open, &file_path)) {
+                file_paths.push(file_path);
+            }
+        }
+
+        Self { file_paths, read_only }
+    }
+}
+
+/* How long typing has to pause before the swap file is written. */
+const SWAP_DELAY: time::Duration = time::Duration::from_secs(2);
+
+/* fs::write, except that a missing directory is named as such rather
+   than passed off as the file not being found. */
+fn write_file(file_path: &path::Path, text: String) -> io::Result<()> {
+    match file_path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        Some(parent) if !parent.is_dir() =>
+            Err(io::Error::new(io::ErrorKind::NotFound, format!("no such directory {}", parent.display()))),
+        _otherwise =>
+            fs::write(file_path, text),
+    }
+}
+
+/* Unsaved changes to dir/name are kept in dir/.name.swp, to be
+   recovered should the editor die before they are saved. */
+fn swap_path(file_path: &path::Path) -> path::PathBuf {
+    let name = file_path.file_name().unwrap_or_default().to_string_lossy();
+    file_path.with_file_name(format!(".{}.swp", name))
+}
+
+/* A swap file left behind is only worth recovering if it was written
+   after the file was last saved. */
+fn swap_is_newer(file_modified: Option<time::SystemTime>, swap_modified: Option<time::SystemTime>) -> bool {
+    match (file_modified, swap_modified) {
+        (Some(file), Some(swap)) => swap > file,
+        (None, Some(_))          => true,
+        (_, None)                => false,
+    }
+}
+
+fn modified_at(file_path: &path::Path) -> Option<time::SystemTime> {
+    fs::metadata(file_path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/* Files that don't exist yet can only be told apart by name. */
+fn same_file(a: &path::Path, b: &path::Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _otherwise     => a == b,
+    }
+}
+
+/* An open file and everything about it that the editor has to keep
+   apart from the other open files. */
+struct Buffer {
+    name:       String,
+    file_path:  Option<path::PathBuf>,
+    contents:   EditingModel,
+    loading:    bool,
+    navigation: NavigationModel,
+    modified:   bool,
+    /* Counts every change, undo and redo included, so a swap write
+       can tell whether another edit came in while it waited. */
+    changes:    usize,
+    history:    UndoHistory,
+    search:     Option<SearchModel>,
+    read_only:  bool,
+    /* Counts loads started, so that of two under way at once only the
+       later one's file is taken, whichever comes in last. */
+    loads:      usize,
+}
+
+impl Buffer {
+    fn new(file_path: Option<path::PathBuf>) -> Self {
+        let name = Self::name_for(file_path.as_deref());
+
+        Self {
+            name,
+            file_path,
+            contents:   EditingModel::empty(),
+            loading:    false,
+            navigation: NavigationModel::default(),
+            modified:   false,
+            changes:    0,
+            history:    UndoHistory::default(),
+            search:     None,
+            read_only:  false,
+            loads:      0,
+        }
+    }
+
+    fn name_for(file_path: Option<&path::Path>) -> String {
+        file_path
+            .and_then(|file_path| file_path.file_name())
+            .map_or("Unnamed".to_owned(), |name| name.to_string_lossy().into_owned())
+    }
+
+    /* Saves go to file_path from now on. The swap file stays with the
+       file it was for. */
+    fn set_file_path(&mut self, file_path: path::PathBuf) {
+        self.remove_swap();
+        self.name = Self::name_for(Some(&file_path));
+        self.file_path = Some(file_path);
+    }
+
+    /* The cursor in chars of the buffer, rather than columns on
+       screen. */
+    fn cursor(&self) -> Position {
+        let cursor = self.navigation.cursor_in_buffer();
+        self.navigation.to_buffer(&cursor, &self.contents)
+    }
+
+    fn place_cursor(&mut self, position: &Position) {
+        let target = self.navigation.to_screen(position, &self.contents);
+        self.navigation.jump_to(&target);
+    }
+
+    /* Everything a single message edited becomes one undo step. */
+    fn record_change(&mut self, before: Position) {
+        let edits = self.contents.take_journal();
+        if !edits.is_empty() {
+            let after = self.cursor();
+            self.history.record(Change { edits, before, after });
+            self.mark_modified();
+        }
+    }
+
+    /* Lines go below the cursor's line, or above it, with the cursor
+       on the first of them. Other text goes after the cursor or
+       before it, with the cursor on its last char. */
+    fn paste(&mut self, register: &Register, after: bool) {
+        let Register { text, linewise } = register;
+        let Position { row, column } = self.cursor();
+
+        if *linewise {
+            let row = cmp::min(row + usize::from(after), self.contents.line_count());
+            for (offset, line) in text.split('\n').enumerate() {
+                self.contents.insert_line(row + offset, line.to_owned());
+            }
+            self.place_cursor(&Position { column: 0, row });
+        } else if !text.is_empty() {
+            let column = cmp::min(column + usize::from(after), self.contents.line_char_len(row));
+            let end = self.contents.insert_text(row, column, text);
+            self.place_cursor(&Position { column: end.column.saturating_sub(1), ..end });
+        }
+    }
+
+    fn mark_modified(&mut self) {
+        self.modified = true;
+        self.changes += 1;
+        self.navigation.viewport.forget_anchors();
+    }
+
+    /* Swap files are small and best effort, so they are written and
+       removed on the spot rather than as effects. */
+    fn write_swap(&self) -> io::Result<()> {
+        match &self.file_path {
+            Some(file_path) if self.modified => self.contents.write_swap(&swap_path(file_path)),
+            _otherwise                       => Ok(()),
+        }
+    }
+
+    fn remove_swap(&self) {
+        if let Some(file_path) = &self.file_path {
+            let _ = fs::remove_file(swap_path(file_path));
+        }
+    }
+
+    /* Reads the file after the first frame is up, rather than before
+       there is anything on screen at all. index is where the buffer
+       sits in the editor, for the message to find it again. */
+    fn load(&mut self, index: usize) -> elm::Cmd<Message> {
+        self.read(index, false)
+    }
+
+    /* The same, except that the file has to be there; a reload that
+       finds nothing leaves the buffer as it was. */
+    fn reload(&mut self, index: usize) -> elm::Cmd<Message> {
+        self.read(index, true)
+    }
+
+    fn read(&mut self, index: usize, reload: bool) -> elm::Cmd<Message> {
+        if let Some(file_path) = self.file_path.clone() {
+            self.loading = true;
+            self.loads += 1;
+            let serial = self.loads;
+            elm::Resource::fetch(
+                move || {
+                    let contents = if reload {
+                        EditingModel::read_file(&file_path)
+                    } else {
+                        EditingModel::read_file_or_empty(&file_path)
+                    };
+                    contents.map(Handover::new)
+                },
+                move |outcome| Message::FileLoaded { index, serial, reload, outcome },
+            )
+        } else {
+            elm::Cmd::none()
+        }
+    }
+}
+
+/* In Insert mode every key that is not bound types itself; in Normal
+   mode keys are commands. Without modal editing the editor stays in
+   Insert mode throughout. */
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Normal,
+    Insert,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Layout {
+    Single,
+    /* Two panes side by side. The focused one shows the active
+       buffer, the other one shows other. */
+    VerticalSplit { other: usize, focus_left: bool },
+}
+
+/* Which of the active buffer's lines the next view has to draw again;
+   the rest it can copy from the frame before. The loop views after
+   every update that touches the model, so what one update changed is
+   all there is to draw. */
+#[derive(Debug, PartialEq)]
+enum Redraw {
+    All,
+    Rows(Vec<usize>),
+}
+
+/* Whatever, when it changes, moves or restyles every row of text at
+   once: scrolling, resizing, another buffer, lines coming or going
+   and the like. */
+#[derive(PartialEq)]
+struct Look {
+    screen_size:           ScreenSize,
+    active:                usize,
+    layout:                Layout,
+    show_line_numbers:     bool,
+    /* Relative numbers change all down the gutter whenever the
+       cursor changes lines. */
+    numbers_from:          Option<usize>,
+    highlight_cursor_line: bool,
+    list:                  bool,
+    row_offset:            usize,
+    column_offset:         usize,
+    tab_width:             usize,
+    wrap:                  bool,
+    loading:               bool,
+    line_count:            usize,
+    search:                Option<String>,
+    selection:             Option<Selection>,
+    brackets:              Option<(Position, Position)>,
+}
+
+/* Where on screen a buffer is shown: its first column and how many
+   columns it gets, divider not included. */
+struct Pane {
+    buffer:  usize,
+    left:    usize,
+    width:   usize,
+    focused: bool,
+}
+
+struct Editor {
+    screen_size:           ScreenSize,
+    buffers:               Vec<Buffer>,
+    active:                usize,
+    layout:                Layout,
+    key_history:           KeyHistory,
+    /* The key history is a debugging aid, so it can be kept off the
+       status bar; it goes on recording either way. */
+    show_key_history:      bool,
+    started:               time::Instant,
+    now:                   time::Instant,
+    prompt:                Option<Prompt>,
+    message:               StatusMessage,
+    autosave:              Option<time::Duration>,
+    keymap:                Keymap,
+    show_line_numbers:     bool,
+    /* Distances from the cursor's line in the gutter; with line
+       numbers on as well, that line keeps its own number. */
+    relative_numbers:      bool,
+    highlight_cursor_line: bool,
+    list:                  bool,
+    strip_on_save:         bool,
+    final_newline:         bool,
+    highlighter:           Option<Box<dyn Highlighter>>,
+    theme:                 Theme,
+    focused:               bool,
+    modal:                 bool,
+    mode:                  Mode,
+    pending:               PendingKeys,
+    count:                 Option<usize>,
+    register:              Register,
+    selection:             Option<Selection>,
+    /* Set by "+, for the yank or paste that comes next. */
+    use_clipboard:         bool,
+    /* Quit was asked for with changes unsaved, and asking again
+       straight after goes ahead regardless. */
+    quit_pending:          bool,
+    bell:                  Bell,
+    /* The bell went off in this update, and is heard or seen in the
+       frame that follows it. */
+    ringing:               bool,
+    /* Which frame of the spinner shows while a buffer loads. */
+    spinner:               usize,
+    redraw:                Redraw,
+}
+
+impl Editor {
+    fn configure(mut self, config: Config) -> Self {
+        for buffer in &mut self.buffers {
+            buffer.navigation.viewport.tab_width = config.tab_width;
+            buffer.navigation.viewport.wrap = config.wrap;
+            buffer.navigation.scroll_step = config.scroll_step;
+            buffer.navigation.scrolloff = config.scrolloff;
+        }
+        self.show_line_numbers = config.show_line_numbers;
+        self.relative_numbers = config.relative_numbers;
+        self.highlight_cursor_line = config.highlight_cursor_line;
+        self.list = config.list;
+        self.key_history = KeyHistory::with_horizon(config.key_history, time::Instant::now);
+        self.key_history.show_intervals = config.show_key_intervals;
+        self.show_key_history = config.show_key_history;
+        self.strip_on_save = config.strip_on_save;
+        self.final_newline = config.final_newline;
+        self.bell = config.bell;
+        self.theme = config.theme;
+        self.autosave = config.autosave;
+        self.modal = config.modal;
+        self.mode = if config.modal { Mode::Normal } else { Mode::Insert };
+        self.keymap = config.keymap;
+        self
+    }
+
+    fn buffer(&self) -> &Buffer {
+        &self.buffers[self.active]
+    }
+
+    fn buffer_mut(&mut self) -> &mut Buffer {
+        &mut self.buffers[self.active]
+    }
+
+    fn switch_buffer(&mut self, index: usize) -> elm::Cmd<Message> {
+        if index < self.buffers.len() {
+            self.active = index;
+        }
+        elm::Cmd::none()
+    }
+
+    /* Splits the screen first if it isn't, showing the next buffer
+       next to the active one. */
+    fn focus_other_pane(&mut self) -> elm::Cmd<Message> {
+        self.layout = match self.layout {
+            Layout::Single =>
+                Layout::VerticalSplit {
+                    other:      (self.active + 1) % self.buffers.len(),
+                    focus_left: true,
+                },
+            Layout::VerticalSplit { other, focus_left } => {
+                let other = mem::replace(&mut self.active, other);
+                Layout::VerticalSplit { other, focus_left: !focus_left }
+            }
+        };
+        elm::Cmd::none()
+    }
+
+    fn go_to_line(&mut self, row: usize) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        buffer.navigation.go_to_line(row, &buffer.contents);
+        elm::Cmd::none()
+    }
+
+    /* Like vi's g ^G. */
+    fn show_stats(&mut self) -> elm::Cmd<Message> {
+        let contents = &self.buffer().contents;
+        let stats = format!(
+            "{} lines, {} words, {} chars, {} bytes",
+            contents.line_count(),
+            contents.word_count(),
+            contents.char_count(),
+            contents.byte_count(),
+        );
+        self.message.show(stats);
+        elm::Cmd::none()
+    }
+
+    fn center_on_cursor(&mut self) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        buffer.navigation.center_on_cursor(&buffer.contents);
+        elm::Cmd::none()
+    }
+
+    fn toggle_cursor_line(&mut self) -> elm::Cmd<Message> {
+        self.highlight_cursor_line = !self.highlight_cursor_line;
+        elm::Cmd::none()
+    }
+
+    fn toggle_key_history(&mut self) -> elm::Cmd<Message> {
+        self.show_key_history = !self.show_key_history;
+        elm::Cmd::none()
+    }
+
+    fn toggle_list(&mut self) -> elm::Cmd<Message> {
+        self.list = !self.list;
+        elm::Cmd::none()
+    }
+
+    fn toggle_wrap(&mut self) -> elm::Cmd<Message> {
+        let navigation = &mut self.buffer_mut().navigation;
+        navigation.set_wrap(!navigation.viewport.wrap);
+        elm::Cmd::none()
+    }
+
+    fn toggle_read_only(&mut self) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        buffer.read_only = !buffer.read_only;
+        let state = if buffer.read_only { "on" } else { "off" };
+        self.message.show(format!("Read-only {}", state));
+        elm::Cmd::none()
+    }
+
+    fn unsplit(&mut self) -> elm::Cmd<Message> {
+        self.layout = Layout::Single;
+        elm::Cmd::none()
+    }
+
+    /* Left to right. An odd number of columns left over after the
+       divider gives the extra one to the left pane. */
+    fn panes(&self) -> Vec<Pane> {
+        let columns = self.screen_size.columns;
+        match self.layout {
+            Layout::Single =>
+                vec![Pane { buffer: self.active, left: 0, width: columns, focused: true }],
+            Layout::VerticalSplit { other, focus_left } => {
+                let right_width = columns.saturating_sub(1) / 2;
+                let left_width = columns.saturating_sub(right_width + 1);
+                let (left, right) = if focus_left { (self.active, other) } else { (other, self.active) };
+                vec![
+                    Pane { buffer: left,  left: 0,              width: left_width,  focused: focus_left  },
+                    Pane { buffer: right, left: left_width + 1, width: right_width, focused: !focus_left },
+                ]
+            }
+        }
+    }
+
+    fn focused_pane(&self) -> Pane {
+        self.panes().into_iter()
+            .find(|pane| pane.focused)
+            .expect("one pane always has focus")
+    }
+
+    /* The buffer after (or before) the active one, going round. */
+    fn neighbouring_buffer(&self, forward: bool) -> elm::Cmd<Message> {
+        let count = self.buffers.len();
+        let index = if forward {
+            (self.active + 1) % count
+        } else {
+            (self.active + count - 1) % count
+        };
+        elm::Cmd::dispatch(Message::SwitchBuffer(index))
+    }
+
+    fn key_typed(&mut self, key: &InputEvent) -> elm::Cmd<Message> {
+        if self.prompt.is_some() {
+            return self.prompt_key_typed(key);
+        }
+
+        self.message.clear();
+
+        if self.selection.is_some() {
+            if let Some(action) = self.keymap.lookup_selection(key) {
+                self.count = None;
+                return self.act(Some(action), key);
+            }
+        }
+
+        let action = match self.mode {
+            Mode::Normal                                             => return self.normal_key_typed(key),
+            Mode::Insert if self.modal && *key == InputEvent::Cancel => Some(Action::NormalMode),
+            Mode::Insert                                             => self.keymap.lookup(key),
+        };
+
+        self.act(action, key)
+    }
+
+    /* A key that starts a sequence waits for the next one. Should
+       that not finish the sequence, the first key counts on its own,
+       if it means anything by itself, and then the next one does. */
+    fn normal_key_typed(&mut self, key: &InputEvent) -> elm::Cmd<Message> {
+        if let Some(first) = self.pending.take() {
+            if let Some(action) = self.keymap.lookup_sequence(&first, key) {
+                return self.act_counted(Some(action), key);
+            }
+
+            let cmd = self.act_counted(self.keymap.lookup_normal(&first), &first);
+            return cmd.and_then(self.key_typed(key));
+        }
+
+        /* Digits make up a count, except for a 0 that would start one,
+           which still goes to the start of the line. */
+        if let InputEvent::Char(digit @ '0'..='9') = key {
+            if *digit != '0' || self.count.is_some() {
+                let digit = digit.to_digit(10).unwrap_or_default() as usize;
+                let count = self.count.unwrap_or(0).saturating_mul(10).saturating_add(digit);
+                self.count = Some(cmp::min(count, MAX_COUNT));
+                return elm::Cmd::none();
+            }
+        }
+
+        if self.keymap.starts_sequence(key) {
+            let serial = self.pending.hold(key.clone());
+            return elm::Cmd::delay(PENDING_KEY_TIMEOUT, Message::ExpirePendingKey(serial));
+        }
+
+        self.act_counted(self.keymap.lookup_normal(key), key)
+    }
+
+    /* Left waiting too long, a held key counts on its own. */
+    fn pending_key_expired(&mut self, serial: usize) -> elm::Cmd<Message> {
+        match self.pending.expire(serial) {
+            Some(first) => self.act_counted(self.keymap.lookup_normal(&first), &first),
+            None        => elm::Cmd::none(),
+        }
+    }
+
+    /* A count typed ahead of an action does it that many times over.
+       Going down stops on the last line, rather than running on into
+       the rows past it the way the arrow keys do; from the last line
+       itself it goes nowhere and rings the bell. */
+    fn act_counted(&mut self, action: Option<Action>, key: &InputEvent) -> elm::Cmd<Message> {
+        let mut count = self.count.take().unwrap_or(1);
+        match action {
+            None                   => return self.act(action, key),
+            Some(Action::MoveDown) => {
+                let buffer = self.buffer();
+                let last_row = buffer.contents.line_count().saturating_sub(1);
+                count = cmp::min(count, last_row.saturating_sub(buffer.cursor().row));
+                if count == 0 {
+                    return elm::Cmd::dispatch(Message::Bell);
+                }
+            }
+            _otherwise             => (),
+        }
+
+        let cmds = (0..count)
+            .map(|_| self.act(action, key))
+            .collect();
+        elm::Cmd::batch(cmds)
+    }
+
+    fn act(&mut self, action: Option<Action>, key: &InputEvent) -> elm::Cmd<Message> {
+        let active = self.active;
+
+        /* Anything typed into the empty buffer would be thrown away
+           once the file arrives. */
+        let cmd = match action {
+            Some(Action::Quit)                      => self.perform(Action::Quit),
+            Some(_) | None if self.buffer().loading => elm::Cmd::none(),
+            Some(action) if action.edits() && self.buffer().read_only => {
+                self.message.show("Buffer is read-only".to_owned());
+                elm::Cmd::none()
+            }
+            Some(action)                            => self.perform(action),
+            None                                    => self.record_key_event(key),
+        };
+
+        /* A selection follows the cursor about, and goes away once
+           the text under it changes or another buffer comes up. */
+        if action != Some(Action::UseClipboard) {
+            self.use_clipboard = false;
+        }
+        if action != Some(Action::Quit) {
+            self.quit_pending = false;
+        }
+
+        let edited = action.is_some_and(|action| action.edits());
+        if edited || self.active != active {
+            self.selection = None;
+        }
+        let cursor = self.cursor();
+        if let Some(selection) = &mut self.selection {
+            selection.head = cursor;
+        }
+
+        cmd
+    }
+
+    fn perform(&mut self, action: Action) -> elm::Cmd<Message> {
+        match action {
+            Action::Quit             => self.quit(),
+            Action::Save             => self.save(),
+            Action::GotoLine         => self.open_prompt(PromptKind::GotoLine),
+            Action::ShowStats        => self.show_stats(),
+            Action::Search           => self.open_prompt(PromptKind::Search),
+            Action::SearchNext       => elm::Cmd::dispatch(Message::SearchNext),
+            Action::ClearSearch      => {
+                self.buffer_mut().search = None;
+                elm::Cmd::none()
+            }
+            Action::Replace          => self.open_prompt(PromptKind::Replace),
+            Action::CommandLine      => self.open_prompt(PromptKind::Command),
+            Action::Undo             => self.undo(),
+            Action::Redo             => self.redo(),
+            Action::MoveUp           => self.move_cursor(&Direction::Up),
+            Action::MoveDown         => self.move_cursor(&Direction::Down),
+            Action::MoveLeft         => self.move_cursor(&Direction::Left),
+            Action::MoveRight        => self.move_cursor(&Direction::Right),
+            Action::PageUp           => self.move_cursor(&Direction::PageUp),
+            Action::PageDown         => self.move_cursor(&Direction::PageDown),
+            Action::LineStart        => self.move_cursor(&Direction::LineStart),
+            Action::LineEnd          => self.move_cursor(&Direction::LineEnd),
+            Action::CenterOnCursor   => self.center_on_cursor(),
+            Action::BufferStart      => self.go_to_line(0),
+            Action::BufferEnd        => self.go_to_line(self.buffer().contents.line_count().saturating_sub(1)),
+            Action::WordLeft         => self.move_by_word(&Direction::Left),
+            Action::WordRight        => self.move_by_word(&Direction::Right),
+            Action::MatchBracket     => self.match_bracket(),
+            Action::InsertChar(c)    => self.insert_char(c),
+            Action::DeleteCharBefore => self.delete_char_before(),
+            Action::DeleteCharAt     => self.delete_char_at(),
+            Action::SplitLine        => self.split_line(),
+            Action::JoinLines        => self.join_lines(),
+            Action::OpenLineBelow    => self.open_line(true),
+            Action::OpenLineAbove    => self.open_line(false),
+            Action::TruncateLine     => self.truncate_line(),
+            Action::DeleteLine       => self.delete_line(),
+            Action::StripWhitespace  => self.strip_whitespace(),
+            Action::NextBuffer       => self.neighbouring_buffer(true),
+            Action::PreviousBuffer   => self.neighbouring_buffer(false),
+            Action::FocusOtherPane   => self.focus_other_pane(),
+            Action::Unsplit          => self.unsplit(),
+            Action::ToggleReadOnly   => self.toggle_read_only(),
+            Action::ToggleWrap       => self.toggle_wrap(),
+            Action::ToggleCursorLine => self.toggle_cursor_line(),
+            Action::ToggleList       => self.toggle_list(),
+            Action::ToggleKeys       => self.toggle_key_history(),
+            Action::InsertMode       => self.switch_mode(Mode::Insert),
+            Action::Append           => self.append(),
+            Action::NormalMode       => self.switch_mode(Mode::Normal),
+            Action::YankLine         => self.yank_line(),
+            Action::PasteAfter       => self.paste(true),
+            Action::PasteBefore      => self.paste(false),
+            Action::ToggleSelection  => self.toggle_selection(),
+            Action::YankSelection    => self.take_selection(false),
+            Action::DeleteSelection  => self.take_selection(true),
+            Action::UseClipboard     => {
+                self.use_clipboard = true;
+                elm::Cmd::none()
+            }
+        }
+    }
+
+    /* Normal mode only exists with modal editing turned on. */
+    fn switch_mode(&mut self, mode: Mode) -> elm::Cmd<Message> {
+        if self.modal {
+            self.mode = mode;
+        }
+        elm::Cmd::none()
+    }
+
+    /* Insert after the char under the cursor rather than before it. */
+    fn append(&mut self) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        let Position { row, column } = buffer.cursor();
+        let column = cmp::min(column + 1, buffer.contents.line_char_len(row));
+        buffer.place_cursor(&Position { column, row });
+        self.switch_mode(Mode::Insert)
+    }
+
+    fn prompt_key_typed(&mut self, key: &InputEvent) -> elm::Cmd<Message> {
+        match key {
+            InputEvent::Cancel => {
+                if let Some(prompt) = self.prompt.take().filter(Prompt::is_search) {
+                    let buffer = self.buffer_mut();
+                    buffer.search = None;
+                    buffer.place_cursor(&prompt.origin);
+                }
+                elm::Cmd::none()
+            }
+
+            /* The search proper goes on from where it started, not
+               from the match it has been showing. */
+            InputEvent::Submit => {
+                let prompt = self.prompt.take();
+                if let Some(prompt) = prompt.as_ref().filter(|prompt| prompt.is_search()) {
+                    self.buffer_mut().place_cursor(&prompt.origin);
+                }
+                prompt
+                    .and_then(|prompt| prompt.submit())
+                    .map_or(elm::Cmd::none(), elm::Cmd::dispatch)
+            }
+
+            InputEvent::Erase => {
+                if let Some(prompt) = &mut self.prompt {
+                    prompt.input.pop();
+                }
+                self.search_as_typed();
+                elm::Cmd::none()
+            }
+
+            InputEvent::Char(c) => {
+                if let Some(prompt) = &mut self.prompt {
+                    prompt.input.push(*c);
+                }
+                self.search_as_typed();
+                elm::Cmd::none()
+            }
+
+            _otherwise =>
+                elm::Cmd::none(),
+        }
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind) -> elm::Cmd<Message> {
+        self.prompt = Some(Prompt { origin: self.cursor(), ..Prompt::new(kind) });
+        elm::Cmd::none()
+    }
+
+    /* While a search is typed, the cursor goes to the first match
+       after where it started, and back there when nothing matches. */
+    fn search_as_typed(&mut self) {
+        let Some(prompt) = self.prompt.as_ref().filter(|prompt| prompt.is_search()) else {
+            return;
+        };
+        let origin = prompt.origin.clone();
+        let query = prompt.input.clone();
+
+        let buffer = self.buffer_mut();
+        let search = SearchModel::new(&query, &buffer.contents);
+        let target = search.next_after(&origin).map_or(origin, |(position, _)| position.clone());
+        buffer.place_cursor(&target);
+        buffer.search = (!query.is_empty()).then_some(search);
+    }
+
+    fn cursor(&self) -> Position {
+        self.buffer().cursor()
+    }
+
+    fn search(&mut self, query: &str) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        buffer.search = Some(SearchModel::new(query, &buffer.contents));
+        self.search_next()
+    }
+
+    fn search_next(&mut self) -> elm::Cmd<Message> {
+        let buffer = &mut self.buffers[self.active];
+        let cursor = buffer.cursor();
+        if let Some(search) = &mut buffer.search {
+            search.refresh(&buffer.contents);
+
+            match search.next_after(&cursor) {
+                Some((position, wrapped)) => {
+                    if wrapped {
+                        self.message.show("Search hit bottom, continuing at top".to_owned());
+                    }
+                    let target = buffer.navigation.to_screen(position, &buffer.contents);
+                    buffer.navigation.jump_to(&target);
+                }
+                None =>
+                    self.message.show(format!("Pattern not found: {}", search.query)),
+            }
+        }
+
+        elm::Cmd::none()
+    }
+
+    fn replace(&mut self, from: &str, to: &str, all: bool) -> elm::Cmd<Message> {
+        let buffer = &mut self.buffers[self.active];
+        let cursor = buffer.cursor();
+
+        if all {
+            let count = buffer.contents.replace_all(from, to);
+            let column = cmp::min(cursor.column, buffer.contents.line_char_len(cursor.row));
+            buffer.place_cursor(&Position { column, row: cursor.row });
+            self.message.show(format!("Replaced {} occurrence(s)", count));
+        } else if let Some(position) = buffer.contents.replace_next(from, to, &cursor) {
+            buffer.place_cursor(&position);
+            self.message.show("Replaced 1 occurrence".to_owned());
+        } else {
+            self.message.show(format!("Pattern not found: {}", from));
+        }
+
+        elm::Cmd::none()
+    }
+
+    fn undo(&mut self) -> elm::Cmd<Message> {
+        let buffer = &mut self.buffers[self.active];
+        match buffer.history.undo(&mut buffer.contents) {
+            Some(cursor) => {
+                buffer.place_cursor(&cursor);
+                buffer.mark_modified();
+            }
+            None =>
+                self.message.show("Nothing to undo".to_owned()),
+        }
+
+        elm::Cmd::none()
+    }
+
+    fn redo(&mut self) -> elm::Cmd<Message> {
+        let buffer = &mut self.buffers[self.active];
+        match buffer.history.redo(&mut buffer.contents) {
+            Some(cursor) => {
+                buffer.place_cursor(&cursor);
+                buffer.mark_modified();
+            }
+            None =>
+                self.message.show("Nothing to redo".to_owned()),
+        }
+
+        elm::Cmd::none()
+    }
+
+    /* Up against the edge of the buffer, where the cursor can go no
+       further and there is nothing left to scroll, the bell rings. Not
+       for Home and End, though, which are where they were told to be. */
+    fn move_cursor(&mut self, direction: &Direction) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        let place = |buffer: &Buffer| (
+            buffer.cursor(),
+            buffer.navigation.viewport.row_offset,
+            buffer.navigation.viewport.column_offset,
+        );
+
+        let before = place(buffer);
+        buffer.navigation.move_intended(direction, &buffer.contents);
+
+        let stuck = place(buffer) == before;
+        if stuck && !matches!(direction, Direction::LineStart | Direction::LineEnd) {
+            elm::Cmd::dispatch(Message::Bell)
+        } else {
+            elm::Cmd::none()
+        }
+    }
+
+    fn move_by_word(&mut self, direction: &Direction) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        let Position { row, column } = buffer.cursor();
+        let target = if direction == &Direction::Left {
+            buffer.contents.prev_word_boundary(row, column)
+        } else {
+            buffer.contents.next_word_boundary(row, column)
+        };
+        buffer.place_cursor(&target);
+        elm::Cmd::none()
+    }
+
+    /* Over to the partner of the bracket under the cursor or, as in
+       vi, of the first one after it on its line. Nowhere when that
+       one has no partner, or there is none. */
+    fn match_bracket(&mut self) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        let Position { row, column } = buffer.cursor();
+        let target = buffer.contents.line(row)
+            .chars()
+            .enumerate()
+            .skip(column)
+            .find(|(_, c)| "()[]{}".contains(*c))
+            .and_then(|(column, _)| buffer.contents.matching_bracket(row, column));
+
+        if let Some(target) = target {
+            buffer.place_cursor(&target);
+        }
+        elm::Cmd::none()
+    }
+
+    fn insert_char(&mut self, c: char) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        let Position { row, column } = buffer.cursor();
+        buffer.contents.insert_char(row, column, c);
+        buffer.place_cursor(&Position { column: column + 1, row });
+        elm::Cmd::none()
+    }
+
+    fn delete_char_before(&mut self) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        let Position { row, column } = buffer.cursor();
+        let cursor = buffer.contents.delete_char_before(row, column);
+        buffer.place_cursor(&cursor);
+        elm::Cmd::none()
+    }
+
+    fn split_line(&mut self) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        let Position { row, column } = buffer.cursor();
+        let cursor = buffer.contents.split_line(row, column);
+        buffer.place_cursor(&cursor);
+        elm::Cmd::none()
+    }
+
+    fn join_lines(&mut self) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        let row = buffer.cursor().row;
+        if let Some(column) = buffer.contents.join_lines(row) {
+            buffer.place_cursor(&Position { column, row });
+        }
+        elm::Cmd::none()
+    }
+
+    /* An empty line below the cursor's, or above it, with the cursor
+       at its start. A line is opened to type into, so this goes to
+       Insert mode too. */
+    fn open_line(&mut self, below: bool) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        let row = buffer.cursor().row + usize::from(below);
+        buffer.contents.insert_line(row, String::new());
+        buffer.place_cursor(&Position { column: 0, row });
+        self.switch_mode(Mode::Insert)
+    }
+
+    fn truncate_line(&mut self) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        let Position { row, column } = buffer.cursor();
+        buffer.contents.truncate_line(row, column);
+        elm::Cmd::none()
+    }
+
+    fn delete_line(&mut self) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        let row = buffer.contents.delete_line(buffer.cursor().row);
+        buffer.place_cursor(&Position { column: 0, row });
+        elm::Cmd::none()
+    }
+
+    /* In Normal mode, as in vi, x stays on its line rather than
+       pulling the next one up. */
+    fn delete_char_at(&mut self) -> elm::Cmd<Message> {
+        let normal = self.mode == Mode::Normal;
+        let buffer = self.buffer_mut();
+        let Position { row, column } = buffer.cursor();
+        if normal && column >= buffer.contents.line_char_len(row) {
+            return elm::Cmd::none();
+        }
+        let cursor = buffer.contents.delete_char_at(row, column);
+        buffer.place_cursor(&cursor);
+        elm::Cmd::none()
+    }
+
+    fn yank_line(&mut self) -> elm::Cmd<Message> {
+        let buffer = self.buffer();
+        let text = buffer.contents.line(buffer.cursor().row).to_owned();
+        self.yank(Register { text, linewise: true })
+    }
+
+    fn toggle_selection(&mut self) -> elm::Cmd<Message> {
+        self.selection = match self.selection {
+            Some(_) => None,
+            None    => Some(Selection::at(self.cursor())),
+        };
+        elm::Cmd::none()
+    }
+
+    /* Yanks the selection, and deletes it too when asked, leaving the
+       cursor at its start either way. */
+    fn take_selection(&mut self, delete: bool) -> elm::Cmd<Message> {
+        let Some(selection) = self.selection.take() else {
+            return elm::Cmd::none();
+        };
+
+        let buffer = &mut self.buffers[self.active];
+        let (start, end) = selection.bounds(&buffer.contents);
+        let text = buffer.contents.text_between(&start, &end);
+        if delete {
+            buffer.contents.delete_between(&start, &end);
+        }
+        buffer.place_cursor(&start);
+        self.yank(Register { text, linewise: false })
+    }
+
+    /* Into the register, or onto the clipboard right after "+. */
+    fn yank(&mut self, register: Register) -> elm::Cmd<Message> {
+        if !mem::take(&mut self.use_clipboard) {
+            self.register = register;
+            return elm::Cmd::none();
+        }
+
+        let text = register.to_clipboard();
+        elm::Resource::fetch(
+            move || Clipboard::set(&text),
+            Message::ClipboardSet,
+        )
+    }
+
+    fn paste(&mut self, after: bool) -> elm::Cmd<Message> {
+        if !mem::take(&mut self.use_clipboard) {
+            self.buffers[self.active].paste(&self.register, after);
+            return elm::Cmd::none();
+        }
+
+        elm::Resource::fetch(
+            Clipboard::get,
+            move |outcome| Message::ClipboardGot { after, outcome },
+        )
+    }
+
+    fn clipboard_set(&mut self, outcome: &elm::Resource<()>) -> elm::Cmd<Message> {
+        if let elm::Resource::Failed(error) = outcome {
+            self.message.show(error.clone());
+        }
+        elm::Cmd::none()
+    }
+
+    /* The buffer may have gone read-only while the clipboard took its
+       time. */
+    fn clipboard_got(&mut self, after: bool, outcome: &elm::Resource<String>) -> elm::Cmd<Message> {
+        match outcome {
+            elm::Resource::Present(_) if self.buffer().read_only =>
+                self.message.show("Buffer is read-only".to_owned()),
+            elm::Resource::Present(text) =>
+                self.buffer_mut().paste(&Register::from_clipboard(text), after),
+            elm::Resource::Failed(error) =>
+                self.message.show(error.clone()),
+            elm::Resource::Unknown =>
+                (),
+        }
+        elm::Cmd::none()
+    }
+
+    fn load(&mut self) -> elm::Cmd<Message> {
+        let loads = self.buffers.iter_mut()
+            .enumerate()
+            .map(|(index, buffer)| buffer.load(index))
+            .collect();
+        elm::Cmd::batch(loads)
+    }
+
+    /* A load that another has overtaken is dropped, whenever it
+       turns up. A reload only throws away the edits, and their undo
+       history, once there is something to put in their place. */
+    fn file_loaded(
+        &mut self,
+        index:   usize,
+        serial:  usize,
+        reload:  bool,
+        outcome: &elm::Resource<Handover<EditingModel>>,
+    ) -> elm::Cmd<Message> {
+        let Some(buffer) = self.buffers.get_mut(index).filter(|buffer| buffer.loads == serial) else {
+            return elm::Cmd::none();
+        };
+
+        match outcome {
+            elm::Resource::Present(contents) => {
+                let Some(contents) = contents.take() else {
+                    return elm::Cmd::none();
+                };
+                if reload {
+                    buffer.remove_swap();
+                    buffer.history = UndoHistory::default();
+                    buffer.modified = false;
+                    if index == self.active {
+                        self.selection = None;
+                    }
+                }
+                buffer.contents = contents;
+                buffer.navigation.viewport.forget_anchors();
+                buffer.navigation.jump_to(&Position::default());
+                buffer.loading = false;
+
+                /* No point editing what cannot be saved. */
+                let unwritable = buffer.file_path.as_ref()
+                    .and_then(|file_path| fs::metadata(file_path).ok())
+                    .is_some_and(|metadata| metadata.permissions().readonly());
+                buffer.read_only |= unwritable;
+
+                /* Saving would write U+FFFD over whatever the bad bytes
+                   were. */
+                if buffer.contents.encoding == Encoding::Unknown {
+                    buffer.read_only = true;
+                    self.message.show(format!("{} is not UTF-8; opened read-only", buffer.name));
+                }
+
+                let recoverable = buffer.file_path.as_ref()
+                    .is_some_and(|file_path| swap_is_newer(modified_at(file_path), modified_at(&swap_path(file_path))));
+                if recoverable {
+                    self.prompt = Some(Prompt::new(PromptKind::Recover(index)));
+                }
+            }
+            elm::Resource::Failed(error) if reload => {
+                buffer.loading = false;
+                self.message.show(format!("Could not reload {}: {}", buffer.name, error));
+            }
+            elm::Resource::Failed(error) => {
+                /* Saving the empty buffer over a file that could not be
+                   read would lose it, so forget where it came from. */
+                buffer.file_path = None;
+                buffer.loading = false;
+                self.message.show(format!("Could not open {}: {}", buffer.name, error));
+            }
+            elm::Resource::Unknown =>
+                (),
+        }
+
+        elm::Cmd::none()
+    }
+
+    /* With changes unsaved it takes asking twice in a row. */
+    fn quit(&mut self) -> elm::Cmd<Message> {
+        let unsaved = self.buffers.iter().any(|buffer| buffer.modified);
+        if unsaved && !mem::replace(&mut self.quit_pending, true) {
+            self.message.show("Unsaved changes — quit again to discard, save to keep".to_owned());
+            return elm::Cmd::none();
+        }
+
+        self.leave()
+    }
+
+    /* Leaving on purpose, unlike a crash, means the swap files are
+       not wanted any more. */
+    fn leave(&mut self) -> elm::Cmd<Message> {
+        for buffer in &self.buffers {
+            buffer.remove_swap();
+        }
+        elm::Cmd::gtfo()
+    }
+
+    fn run_command(&mut self, command: &Command) -> elm::Cmd<Message> {
+        match command {
+            Command::Write(None)            => self.save(),
+            Command::Write(Some(file_path)) => elm::Cmd::dispatch(Message::SaveAs(file_path.clone())),
+            Command::ForceWrite             => self.force_save(),
+            Command::Quit                   => self.quit_unless_modified(),
+            Command::ForceQuit              => self.leave(),
+            Command::WriteQuit              => self.save().and_then(elm::Cmd::dispatch(Message::QuitIfSaved)),
+            Command::Edit(file_path)        => self.edit(file_path),
+            Command::Reload                 => self.ask_to_reload(),
+        }
+    }
+
+    /* Unlike Quit, :q takes no for an answer until it gets a !. */
+    fn quit_unless_modified(&mut self) -> elm::Cmd<Message> {
+        match self.buffers.iter().find(|buffer| buffer.modified) {
+            Some(buffer) => {
+                self.message.show(format!("No write since last change to {} (add ! to override)", buffer.name));
+                elm::Cmd::none()
+            }
+            None =>
+                self.leave(),
+        }
+    }
+
+    /* After the save of :wq, which has already said why should it
+       have failed. */
+    fn quit_if_saved(&mut self) -> elm::Cmd<Message> {
+        if self.buffer().modified {
+            elm::Cmd::none()
+        } else {
+            self.quit_unless_modified()
+        }
+    }
+
+    /* A file already open just comes to the front; any other gets a
+       buffer of its own. */
+    fn edit(&mut self, file_path: &path::Path) -> elm::Cmd<Message> {
+        let open = self.buffers.iter()
+            .position(|buffer| buffer.file_path.as_deref().is_some_and(|open| same_file(open, file_path)));
+        if let Some(index) = open {
+            return self.switch_buffer(index);
+        }
+
+        let mut buffer = Buffer::new(Some(file_path.to_owned()));
+        buffer.navigation = NavigationModel::configured_like(&self.buffer().navigation);
+        let index = self.buffers.len();
+        let load = buffer.load(index);
+        self.buffers.push(buffer);
+        self.active = index;
+        load
+    }
+
+    /* Unsaved changes only go once that is confirmed. */
+    fn ask_to_reload(&mut self) -> elm::Cmd<Message> {
+        if self.buffer().modified {
+            self.open_prompt(PromptKind::Reload)
+        } else {
+            self.reload()
+        }
+    }
+
+    /* Back to what is on disk, with the edits and their undo history
+       thrown away once the file is in. */
+    fn reload(&mut self) -> elm::Cmd<Message> {
+        if self.buffer().file_path.is_none() {
+            self.message.show("No file name".to_owned());
+            return elm::Cmd::none();
+        }
+
+        let index = self.active;
+        self.buffer_mut().reload(index)
+    }
+
+    fn unknown_command(&mut self, input: &str) -> elm::Cmd<Message> {
+        self.message.show(format!("Not an editor command: {}", input));
+        elm::Cmd::dispatch(Message::Bell)
+    }
+
+    /* Only once edits have let up for a while; changes is what the
+       buffer's count was when this was scheduled. */
+    fn write_swap(&mut self, index: usize, changes: usize) -> elm::Cmd<Message> {
+        let Some(buffer) = self.buffers.get(index) else {
+            return elm::Cmd::none();
+        };
+
+        if buffer.changes == changes {
+            if let Err(error) = buffer.write_swap() {
+                self.message.show(format!("Swap file failed: {}", error));
+            }
+        }
+        elm::Cmd::none()
+    }
+
+    fn recover_swap(&mut self, index: usize) -> elm::Cmd<Message> {
+        let Some(buffer) = self.buffers.get_mut(index) else {
+            return elm::Cmd::none();
+        };
+        let Some(file_path) = &buffer.file_path else {
+            return elm::Cmd::none();
+        };
+
+        match fs::read_to_string(swap_path(file_path)) {
+            Ok(text) => {
+                buffer.contents = EditingModel::from_text(&text);
+                buffer.history = UndoHistory::default();
+                buffer.navigation.jump_to(&Position::default());
+                buffer.mark_modified();
+                self.message.show(format!("Recovered {}", buffer.name));
+            }
+            Err(error) =>
+                self.message.show(format!("Could not recover {}: {}", buffer.name, error)),
+        }
+        elm::Cmd::none()
+    }
+
+    fn strip_whitespace(&mut self) -> elm::Cmd<Message> {
+        let stripped = self.strip_trailing_whitespace();
+        self.message.show(format!("Stripped trailing whitespace from {} line(s)", stripped));
+        elm::Cmd::none()
+    }
+
+    /* Keeps the cursor on its line, at most at the end of it. */
+    fn strip_trailing_whitespace(&mut self) -> usize {
+        let buffer = self.buffer_mut();
+        let cursor = buffer.cursor();
+        let stripped = buffer.contents.strip_trailing_whitespace();
+        let column = cmp::min(cursor.column, buffer.contents.line_char_len(cursor.row));
+        buffer.place_cursor(&Position { column, ..cursor });
+        stripped
+    }
+
+    /* Not over what someone else wrote to the file in the meantime,
+       unless that is what :w! asks for. */
+    fn save(&mut self) -> elm::Cmd<Message> {
+        let buffer = self.buffer();
+        let changed = !buffer.read_only && buffer.file_path.as_deref()
+            .is_some_and(|file_path| buffer.contents.changed_on_disk(file_path));
+        if changed {
+            self.message.show("File changed on disk — overwrite? (:w! to force)".to_owned());
+            return elm::Cmd::none();
+        }
+
+        self.force_save()
+    }
+
+    /* Only saving by hand strips whitespace; autosave could take
+       the space just typed from under the cursor. Not even :w! writes
+       a read-only buffer back, since one opened lossily would put
+       U+FFFD where the bytes it could not read were. */
+    fn force_save(&mut self) -> elm::Cmd<Message> {
+        if self.buffer().read_only {
+            self.message.show("Buffer is read-only".to_owned());
+            elm::Cmd::none()
+        } else if self.buffer().file_path.is_some() {
+            let stripped = self.strip_before_saving();
+            self.save_buffer(self.active, stripped, None)
+        } else {
+            self.message.show("No file name".to_owned());
+            elm::Cmd::none()
+        }
+    }
+
+    /* Like save, only somewhere else. The buffer takes the new name
+       once the file is written there, and not before. A read-only
+       buffer can go anywhere but back where it came from. */
+    fn save_as(&mut self, file_path: &path::Path) -> elm::Cmd<Message> {
+        let own_file = self.buffer().file_path.as_deref()
+            .is_some_and(|own| same_file(own, file_path));
+        if own_file && self.buffer().read_only {
+            self.message.show("Buffer is read-only".to_owned());
+            return elm::Cmd::none();
+        }
+
+        let stripped = self.strip_before_saving();
+        self.save_buffer(self.active, stripped, Some(file_path.to_owned()))
+    }
+
+    fn strip_before_saving(&mut self) -> usize {
+        if self.strip_on_save && !self.buffer().read_only {
+            self.strip_trailing_whitespace()
+        } else {
+            0
+        }
+    }
+
+    /* To the buffer's own file, or to save_as when there is one. */
+    fn save_buffer(&self, index: usize, stripped: usize, save_as: Option<path::PathBuf>) -> elm::Cmd<Message> {
+        let buffer = &self.buffers[index];
+        let Some(file_path) = save_as.clone().or_else(|| buffer.file_path.clone()) else {
+            return elm::Cmd::none();
+        };
+
+        /* The buffer stays as it is; only the file gets its final
+           line break seen to. */
+        let text = if self.final_newline {
+            buffer.contents.serialize_with_final_newline()
+        } else {
+            buffer.contents.serialize()
+        };
+        elm::Resource::fetch(
+            move || write_file(&file_path, text),
+            move |outcome| Message::Saved { index, stripped, save_as, outcome },
+        )
+    }
+
+    /* Saves every buffer with changes that has somewhere to go and
+       nobody else has written to; the rest are left alone, quietly. */
+    fn autosave(&mut self) -> elm::Cmd<Message> {
+        let saves = self.buffers.iter()
+            .enumerate()
+            .filter(|(_, buffer)| buffer.modified && !buffer.read_only && !buffer.loading)
+            .filter(|(_, buffer)| !buffer.file_path.as_deref()
+                .is_some_and(|file_path| buffer.contents.changed_on_disk(file_path)))
+            .map(|(index, _)| self.save_buffer(index, 0, None))
+            .collect();
+        elm::Cmd::batch(saves)
+    }
+
+    fn saved(
+        &mut self,
+        index:    usize,
+        stripped: usize,
+        save_as:  Option<&path::Path>,
+        outcome:  &elm::Resource<()>,
+    ) -> elm::Cmd<Message> {
+        let Some(buffer) = self.buffers.get_mut(index) else {
+            return elm::Cmd::none();
+        };
+
+        match outcome {
+            elm::Resource::Present(()) => {
+                if let Some(file_path) = save_as {
+                    buffer.set_file_path(file_path.to_owned());
+                }
+                buffer.contents.disk_modified = buffer.file_path.as_deref().and_then(modified_at);
+                buffer.modified = false;
+                buffer.remove_swap();
+                match stripped {
+                    0 => self.message.show(format!("Saved {}", buffer.name)),
+                    _ => self.message.show(format!(
+                        "Saved {}, stripped trailing whitespace from {} line(s)",
+                        buffer.name,
+                        stripped,
+                    )),
+                }
+            }
+            elm::Resource::Failed(error) =>
+                self.message.show(format!("Save failed: {}", error)),
+            elm::Resource::Unknown =>
+                (),
+        }
+
+        elm::Cmd::none()
+    }
+
+    /* A key that means nothing goes into the history, and rings the
+       bell. */
+    fn record_key_event(&mut self, ev: &InputEvent) -> elm::Cmd<Message> {
+        self.key_history.record(ev);
+        elm::Cmd::dispatch(Message::Bell)
+    }
+
+    fn ring(&mut self) -> elm::Cmd<Message> {
+        match self.bell {
+            Bell::Audible => {
+                self.ringing = true;
+                elm::Cmd::none()
+            }
+            Bell::Visual => {
+                self.ringing = true;
+                elm::Cmd::delay(BELL_FLASH, Message::BellFlashed)
+            }
+            Bell::Off =>
+                elm::Cmd::none(),
+        }
+    }
+
+    fn input_received(&mut self, input: &InputEvent) -> elm::Cmd<Message> {
+        match input {
+            InputEvent::Resize { columns, rows } =>
+                self.screen_size_changed((*columns, *rows).into()),
+            InputEvent::Click { column, row } =>
+                self.clicked(*column as usize, *row as usize),
+            InputEvent::Scroll(direction) =>
+                self.scrolled(direction),
+            InputEvent::Paste(text) =>
+                self.pasted(text),
+            InputEvent::Focus(focused) =>
+                self.focus_changed(*focused),
+            InputEvent::Ignored =>
+                elm::Cmd::none(),
+            key =>
+                self.key_typed(key),
+        }
+    }
+
+    /* A paste arrives in one piece, and so is undone in one piece. In
+       a prompt, which is a single line, line breaks are dropped. */
+    fn pasted(&mut self, text: &str) -> elm::Cmd<Message> {
+        if let Some(prompt) = &mut self.prompt {
+            prompt.input.extend(text.chars().filter(|c| !matches!(c, '\r' | '\n')));
+            self.search_as_typed();
+            return elm::Cmd::none();
+        }
+
+        self.message.clear();
+
+        if self.buffer().loading {
+            return elm::Cmd::none();
+        }
+        if self.buffer().read_only {
+            self.message.show("Buffer is read-only".to_owned());
+            return elm::Cmd::none();
+        }
+
+        let buffer = self.buffer_mut();
+        let Position { row, column } = buffer.cursor();
+        let cursor = buffer.contents.insert_text(row, column, text);
+        buffer.place_cursor(&cursor);
+        elm::Cmd::none()
+    }
+
+    fn focus_changed(&mut self, focused: bool) -> elm::Cmd<Message> {
+        self.focused = focused;
+        elm::Cmd::none()
+    }
+
+    fn clicked(&mut self, column: usize, row: usize) -> elm::Cmd<Message> {
+        let clicked_other = self.panes().iter()
+            .any(|pane| !pane.focused && (pane.left..pane.left + pane.width).contains(&column));
+        if clicked_other {
+            self.focus_other_pane();
+        }
+
+        let pane = self.focused_pane();
+        let column = column
+            .saturating_sub(pane.left)
+            .saturating_sub(self.gutter_width(self.buffer()));
+        let buffer = self.buffer_mut();
+        let target = buffer.navigation.position_at(column, row, &buffer.contents);
+        buffer.navigation.jump_to(&target);
+        buffer.navigation.snap_to_char(&buffer.contents);
+
+        elm::Cmd::none()
+    }
+
+    fn scrolled(&mut self, direction: &Direction) -> elm::Cmd<Message> {
+        let buffer = self.buffer_mut();
+        match direction {
+            Direction::Up   => buffer.navigation.scroll_view_up(&buffer.contents),
+            Direction::Down => buffer.navigation.scroll_view_down(&buffer.contents),
+            _otherwise      => (),
+        }
+
+        elm::Cmd::none()
+    }
+
+    fn screen_size_changed(&mut self, new_size: ScreenSize) -> elm::Cmd<Message> {
+        self.screen_size = new_size;
+        elm::Cmd::none()
+    }
+
+    /* Taken before and after an update, to tell whether it moved
+       everything. */
+    fn look(&self) -> Look {
+        let shown = self.buffer();
+        let viewport = &shown.navigation.viewport;
+        Look {
+            screen_size:           self.screen_size.clone(),
+            active:                self.active,
+            layout:                self.layout,
+            show_line_numbers:     self.show_line_numbers,
+            numbers_from:          self.relative_numbers.then(|| shown.cursor().row),
+            highlight_cursor_line: self.highlight_cursor_line,
+            list:                  self.list,
+            row_offset:            viewport.row_offset,
+            column_offset:         viewport.column_offset,
+            tab_width:             viewport.tab_width,
+            wrap:                  viewport.wrap,
+            loading:               shown.loading,
+            line_count:            shown.contents.line_count(),
+            search:                shown.search.as_ref().map(|search| search.query.clone()),
+            selection:             self.selection.clone(),
+            brackets:              self.matched_brackets(),
+        }
+    }
+
+    /* The bracket under the cursor and the one it pairs with. */
+    fn matched_brackets(&self) -> Option<(Position, Position)> {
+        let shown = self.buffer();
+        if shown.loading {
+            return None;
+        }
+
+        let cursor = shown.cursor();
+        let partner = shown.contents.matching_bracket(cursor.row, cursor.column)?;
+        Some((cursor, partner))
+    }
+
+    /* Whether the i-th row of text on screen looks as it did last
+       time. Only a single pane that does not wrap maps rows on screen
+       straight onto lines, so anything else is drawn again. */
+    fn row_unchanged(&self, i: usize) -> bool {
+        let viewport = &self.buffer().navigation.viewport;
+        match (&self.redraw, self.layout) {
+            (Redraw::Rows(rows), Layout::Single) if !viewport.wrap =>
+                !rows.contains(&(viewport.row_offset + i)),
+            _otherwise =>
+                false,
+        }
+    }
+
+    /* Columns taken by line numbers and the space after them. */
+    fn gutter_width(&self, shown: &Buffer) -> usize {
+        if self.show_line_numbers || self.relative_numbers {
+            shown.contents.line_count().to_string().len() + 1
+        } else {
+            0
+        }
+    }
+
+    /* The part of the screen that shows buffer text, which is what
+       the cursor navigates. The bottom row is left for the status
+       bar. */
+    fn text_area(&self, pane: &Pane) -> ScreenSize {
+        ScreenSize::new(
+            pane.width.saturating_sub(self.gutter_width(&self.buffers[pane.buffer])),
+            self.screen_size.rows.saturating_sub(1),
+        )
+    }
+
+    /* Focused pane last: when both panes show the same buffer, that
+       is the one it fits. Every update ends up here, so this is also
+       where the view is kept on the file. */
+    fn fit_text_area(&mut self) {
+        let mut panes = self.panes();
+        panes.sort_by_key(|pane| pane.focused);
+        for pane in panes {
+            let text_area = self.text_area(&pane);
+            let buffer = &mut self.buffers[pane.buffer];
+            buffer.navigation.screen_size_changed(text_area);
+            buffer.navigation.clamp_viewport(&buffer.contents);
+            buffer.navigation.fit_cursor(&buffer.contents);
+        }
+    }
+
+    fn render(&self, buffer: &mut CommandBuffer) -> io::Result<()> {
+        /* At least consider putting the draw methods behind some
+           trait to cut down on the amount of code clutter. */
+
+        buffer.resize(self.screen_size.columns, self.screen_size.rows)
+              .hide_cursor()?;
+
+        /* Until the first size arrives, and on a terminal squeezed to
+           nothing, there is nowhere to draw. */
+        if self.screen_size.rows == 0 || self.screen_size.columns == 0 {
+            return Ok(());
+        }
+
+        buffer.move_to(0, 0)?;
+
+        self.render_contents(buffer)?;
+
+        if let Some(prompt) = &self.prompt {
+            self.render_prompt(prompt, buffer)?;
+        } else {
+            self.render_status_bar(buffer)?;
+            let pane = self.focused_pane();
+            let cursor = self.buffer().navigation.cursor_on_screen(&self.buffer().contents);
+            buffer.move_to(pane.left + self.gutter_width(self.buffer()) + cursor.column, cursor.row)?;
+        }
+
+        /* With the terminal in the background, a blinking cursor only
+           gets in the way of whichever window does have focus. */
+        if self.focused {
+            buffer
+                .set_cursor_shape(self.cursor_shape())?
+                .show_cursor()?;
+        }
+
+        if self.ringing && self.bell == Bell::Audible {
+            buffer.ring_bell()?;
+        }
+
+        Ok(())
+    }
+
+    /* A bar between chars where typing inserts, a block on the char
+       that commands act on, and an underline where typing does
+       nothing at all. */
+    fn cursor_shape(&self) -> CursorShape {
+        match self.mode {
+            Mode::Insert if self.buffer().read_only => CursorShape::Underline,
+            Mode::Insert                            => CursorShape::Bar,
+            Mode::Normal                            => CursorShape::Block,
+        }
+    }
+
+    fn render_status_bar(&self, buffer: &mut CommandBuffer) -> io::Result<()> {
+        let bottom_row = self.screen_size.rows.saturating_sub(1);
+        let cursor = self.cursor();
+
+        let buffers = if self.buffers.len() > 1 {
+            format!(" [{}/{}]", self.active + 1, self.buffers.len())
+        } else {
+            String::new()
+        };
+        let mode = match (self.modal, self.mode) {
+            (false, _)           => "",
+            (true, Mode::Normal) => "NORMAL  ",
+            (true, Mode::Insert) => "INSERT  ",
+        };
+        let spinner = if self.buffer().loading {
+            format!(" {}", SPINNER[self.spinner % SPINNER.len()])
+        } else {
+            String::new()
+        };
+        let left = format!(
+            " {}{}{}{}{}{}  {}",
+            mode,
+            self.buffer().name,
+            spinner,
+            if self.buffer().modified { " [+]" } else { "" },
+            if self.buffer().read_only { " [RO]" } else { "" },
+            buffers,
+            self.message.text(),
+        );
+        let session = self.now.saturating_duration_since(self.started).as_secs();
+        let key_history = if self.show_key_history {
+            format!("{}  ", self.key_history)
+        } else {
+            String::new()
+        };
+        let right = format!(
+            "{}{:02}:{:02}  {}  {}:{} ",
+            key_history,
+            session / 60,
+            session % 60,
+            self.buffer().contents.encoding.name(),
+            cursor.row + 1,
+            cursor.column + 1,
+        );
+
+        let width = self.screen_size.columns;
+        let padding = width.saturating_sub(left.chars().count() + right.chars().count());
+        let status = format!("{}{}{}", left, " ".repeat(padding), right)
+            .chars()
+            .take(width)
+            .collect::<String>();
+
+        /* Out of reverse video for the flash of a visual bell. Dimmed
+           while the terminal is in the background, so it is plain at a
+           glance which window typing goes to. */
+        let mut bar = style::ContentStyle::new().with(self.theme.status_bar);
+        if !(self.ringing && self.bell == Bell::Visual) {
+            bar = bar.reverse();
+        }
+        if !self.focused {
+            bar = bar.dim();
+        }
+
+        buffer
+            .move_to(0, bottom_row)?
+            .print_styled(&status, bar)?;
+
+        Ok(())
+    }
+
+    fn render_prompt(&self, prompt: &Prompt, buffer: &mut CommandBuffer) -> io::Result<()> {
+        let bottom_row = self.screen_size.rows.saturating_sub(1);
+        let text = format!("{}{}", prompt.label(), prompt.input);
+        let cursor_column = text.chars().count();
+
+        buffer
+            .move_to(0, bottom_row)?
+            .print(&text)?
+            .move_to(cursor_column, bottom_row)?;
+
+        Ok(())
+    }
+
+    fn render_contents(&self, buffer: &mut CommandBuffer) -> io::Result<()> {
+        let panes = self.panes();
+        let wrapped = panes.iter()
+            .map(|pane| &self.buffers[pane.buffer])
+            .map(|shown| {
+                let wrap = shown.navigation.viewport.wrap && !shown.loading;
+                wrap.then(|| shown.navigation.visual_rows(&shown.contents))
+            })
+            .collect::<Vec<_>>();
+        let brackets = self.matched_brackets();
+
+        for i in 0..self.screen_size.rows.saturating_sub(1) {
+            if self.row_unchanged(i) && buffer.keep_row(i) {
+                buffer.print("\r\n")?;
+                continue;
+            }
+
+            for (index, pane) in panes.iter().enumerate() {
+                let shown = &self.buffers[pane.buffer];
+                if index > 0 {
+                    buffer.print_styled("│", style::ContentStyle::new().with(self.theme.divider))?;
+                }
+
+                let (row, line) = match &wrapped[index] {
+                    Some(rows) => {
+                        let shown_row = rows.get(i);
+                        let label = shown_row
+                            .filter(|shown_row| shown_row.columns.start == 0)
+                            .map(|shown_row| shown_row.row);
+                        self.render_gutter(pane, label, buffer)?;
+                        let row = shown_row.map_or(usize::MAX, |shown_row| shown_row.row);
+                        (row, shown_row.map(|shown_row| self.render_wrapped_row(pane, shown_row, brackets.as_ref())))
+                    }
+                    None => {
+                        let viewport = &shown.navigation.viewport;
+                        let row = viewport.row_offset + i;
+                        let label = (row < shown.contents.line_count()).then_some(row);
+                        self.render_gutter(pane, label, buffer)?;
+
+                        let visible = viewport.column_offset
+                            .. viewport.column_offset + shown.navigation.screen_size.columns;
+                        let line = self.render_line(shown, i).map(|line| (
+                            line,
+                            self.search_highlights(shown, row, visible.clone()),
+                            self.spans(
+                                pane,
+                                row,
+                                visible.clone(),
+                                brackets.as_ref(),
+                                &self.whitespace_markers(shown, row, visible.clone()),
+                            ),
+                            self.selected_columns(pane, row, visible),
+                        ));
+                        (row, line)
+                    }
+                };
+
+                /* The cursor's line, all of its rows when it wraps, gets
+                   a background of its own, right across the pane. Search
+                   matches on it are still reversed. */
+                let cursor_line = self.highlight_cursor_line
+                    && pane.focused
+                    && !shown.loading
+                    && row == shown.cursor().row;
+
+                /* Past the end of the buffer there is a dim ~ and nothing
+                   else, like vi. */
+                let text_width = self.text_area(pane).columns;
+                let printed = match line {
+                    _ if text_width == 0 => 0,
+                    Some((line, highlights, spans, selected)) if cursor_line => {
+                        buffer.set_background(self.theme.cursor_line)?;
+                        print_highlighted(buffer, &self.theme, &line, &highlights, &spans, &selected)?;
+                        let padding = text_width.saturating_sub(line.chars().count());
+                        buffer.print(&" ".repeat(padding))?
+                              .reset_colors()?;
+                        text_width
+                    }
+                    Some((line, highlights, spans, selected)) => {
+                        print_highlighted(buffer, &self.theme, &line, &highlights, &spans, &selected)?;
+                        line.chars().count()
+                    }
+                    None => {
+                        buffer.print_styled("~", style::ContentStyle::new().with(self.theme.end_of_buffer))?;
+                        1
+                    }
+                };
+
+                /* A pane with another one to its right pads out to its
+                   own edge, so the divider lands where it belongs. */
+                if index + 1 < panes.len() {
+                    let padding = text_width.saturating_sub(printed);
+                    buffer.print(&" ".repeat(padding))?;
+                }
+            }
+
+            buffer.print("\r\n")?;
+        }
+
+        Ok(())
+    }
+
+    /* Numbers row, if there is one; blank otherwise. Cut short in a
+       pane too narrow to hold it. */
+    fn render_gutter(&self, pane: &Pane, row: Option<usize>, buffer: &mut CommandBuffer) -> io::Result<()> {
+        let shown = &self.buffers[pane.buffer];
+        let width = self.gutter_width(shown);
+        if width == 0 || pane.width == 0 {
+            return Ok(());
+        }
+
+        let label = match row {
+            Some(row) if !shown.loading => format!("{:>1$} ", self.line_label(shown, row), width - 1),
+            _otherwise                  => " ".repeat(width),
+        };
+        let label = label.chars().take(pane.width).collect::<String>();
+
+        buffer.print_styled(&label, style::ContentStyle::new().with(self.theme.line_number))?;
+
+        Ok(())
+    }
+
+    /* Never wider than the line count, so the gutter fits either. */
+    fn line_label(&self, shown: &Buffer, row: usize) -> usize {
+        let cursor_row = shown.cursor().row;
+        match (self.relative_numbers, self.show_line_numbers) {
+            (true, true) if row == cursor_row => row + 1,
+            (true, _)                         => row.abs_diff(cursor_row),
+            (false, _)                        => row + 1,
+        }
+    }
+
+    /* Search matches in the visible screen columns of a row, counted
+       from the first visible one. */
+    fn search_highlights(&self, shown: &Buffer, row: usize, visible: Range<usize>) -> Vec<Range<usize>> {
+        let tab_width = shown.navigation.viewport.tab_width;
+
+        match (&shown.search, shown.contents.line(row)) {
+            (Some(search), line) =>
+                search.match_columns(line).into_iter()
+                    .map(|columns| clip_columns(
+                        display_column(line, columns.start, tab_width)
+                            .. display_column(line, columns.end, tab_width),
+                        &visible,
+                    ))
+                    .filter(|columns| !columns.is_empty())
+                    .collect(),
+            _otherwise =>
+                vec![],
+        }
+    }
+
+    /* What the highlighter makes of row, cut down to the visible
+       columns the same way as search matches. */
+    fn syntax_spans(&self, shown: &Buffer, row: usize, visible: Range<usize>) -> Vec<highlight::Span> {
+        let highlighter = match (&self.highlighter, &shown.file_path) {
+            (Some(highlighter), Some(file_path)) if !shown.loading && highlighter.handles(file_path) =>
+                highlighter,
+            _otherwise =>
+                return vec![],
+        };
+
+        let line = expand_tabs(shown.contents.line(row), shown.navigation.viewport.tab_width);
+        highlighter.spans(&line).into_iter()
+            .map(|(columns, style)| (clip_columns(columns, &visible), style))
+            .filter(|(columns, _)| !columns.is_empty())
+            .collect()
+    }
+
+    /* The selection's share of row, if it is in pane, in visible
+       screen columns like search matches. */
+    fn selected_columns(&self, pane: &Pane, row: usize, visible: Range<usize>) -> Range<usize> {
+        let shown = &self.buffers[pane.buffer];
+        let tab_width = shown.navigation.viewport.tab_width;
+
+        match &self.selection {
+            Some(selection) if pane.focused && pane.buffer == self.active => {
+                let line = shown.contents.line(row);
+                selection.columns_on(row, &shown.contents)
+                    .map_or(0..0, |columns| clip_columns(
+                        display_column(line, columns.start, tab_width)
+                            .. display_column(line, columns.end, tab_width),
+                        &visible,
+                    ))
+            }
+            _otherwise =>
+                0..0,
+        }
+    }
+
+    fn render_wrapped_row(
+        &self,
+        pane:      &Pane,
+        shown_row: &VisualRow,
+        brackets:  Option<&(Position, Position)>,
+    ) -> (Cow<'_, str>, Vec<Range<usize>>, Vec<highlight::Span>, Range<usize>) {
+        let shown = &self.buffers[pane.buffer];
+        let VisualRow { row, columns } = shown_row;
+        let line = expand_tabs(shown.contents.line(*row), shown.navigation.viewport.tab_width);
+        let slice = char_window(&line, columns.start, columns.len()).unwrap_or(0..0);
+        let markers = self.whitespace_markers(shown, *row, columns.clone());
+        let highlights = self.search_highlights(shown, *row, columns.clone());
+        let spans = self.spans(pane, *row, columns.clone(), brackets, &markers);
+        let selected = self.selected_columns(pane, *row, columns.clone());
+        (Cow::Owned(show_whitespace(&line[slice], &markers)), highlights, spans, selected)
+    }
+
+    /* None past the end of the buffer. */
+    fn render_line<'a>(&self, shown: &'a Buffer, viewport_line_index: usize) -> Option<Cow<'a, str>> {
+        if shown.loading {
+            return (viewport_line_index == 0).then_some(Cow::Borrowed("Loading…"));
+        }
+
+        let viewport = &shown.navigation.viewport;
+        if viewport.is_past_end(viewport_line_index, &shown.contents) {
+            return None;
+        }
+
+        let width = shown.navigation.screen_size.columns;
+        let mut clipped = viewport.select_and_clip(viewport_line_index, width, &shown.contents);
+        if self.list {
+            let row = viewport.row_offset + viewport_line_index;
+            let visible = viewport.column_offset..viewport.column_offset + width;
+            let markers = self.whitespace_markers(shown, row, visible);
+            clipped.text = Cow::Owned(show_whitespace(&clipped.text, &markers));
+        }
+        Some(clipped.with_markers())
+    }
+
+    /* In list mode, what goes over the whitespace of row in the
+       visible screen columns: → on the first column of each tab, ·
+       on each trailing space. Columns count from the first visible
+       one. */
+    fn whitespace_markers(&self, shown: &Buffer, row: usize, visible: Range<usize>) -> Vec<(usize, char)> {
+        if !self.list || shown.loading {
+            return vec![];
+        }
+
+        let line = shown.contents.line(row);
+        let trailing = line.trim_end_matches([' ', '\t']).len();
+        let tab_width = shown.navigation.viewport.tab_width;
+        let mut markers = vec![];
+        let mut column = 0;
+        for (index, c) in line.char_indices() {
+            if column >= visible.end {
+                break;
+            }
+            let marker = match c {
+                '\t'                    => Some('→'),
+                ' ' if index >= trailing => Some('·'),
+                _otherwise              => None,
+            };
+            if let Some(marker) = marker.filter(|_| visible.contains(&column)) {
+                markers.push((column - visible.start, marker));
+            }
+            column += char_width(c, column, tab_width);
+        }
+
+        markers
+    }
+
+    /* Everything on row in a style of its own: list mode's markers
+       first, so that they stay dim whatever else is highlighted
+       around them, then matching brackets in the focused pane, then
+       syntax. */
+    fn spans(
+        &self,
+        pane:     &Pane,
+        row:      usize,
+        visible:  Range<usize>,
+        brackets: Option<&(Position, Position)>,
+        markers:  &[(usize, char)],
+    ) -> Vec<highlight::Span> {
+        let shown = &self.buffers[pane.buffer];
+        let whitespace = highlight::Style { foreground: self.theme.whitespace, bold: false };
+        let bracket = highlight::Style { foreground: self.theme.bracket, bold: true };
+
+        let line = shown.contents.line(row);
+        let tab_width = shown.navigation.viewport.tab_width;
+        let brackets = brackets
+            .filter(|_| pane.focused && pane.buffer == self.active)
+            .map_or(vec![], |(cursor, partner)| vec![cursor, partner])
+            .into_iter()
+            .filter(|position| position.row == row)
+            .map(|position| display_column(line, position.column, tab_width))
+            .map(|column| (clip_columns(column..column + 1, &visible), bracket))
+            .filter(|(columns, _)| !columns.is_empty());
+
+        markers.iter()
+            .map(|&(column, _)| (column..column + 1, whitespace))
+            .chain(brackets)
+            .chain(self.syntax_spans(shown, row, visible.clone()))
+            .collect()
+    }
+}
+
+/* text with the chars at markers' columns swapped for the markers. */
+fn show_whitespace(text: &str, markers: &[(usize, char)]) -> String {
+    text.chars()
+        .enumerate()
+        .map(|(column, c)| markers.iter()
+            .find(|(marked, _)| *marked == column)
+            .map_or(c, |(_, marker)| *marker))
+        .collect()
+}
+
+/* columns as seen through visible, counted from its start. */
+fn clip_columns(columns: Range<usize>, visible: &Range<usize>) -> Range<usize> {
+    let start = cmp::max(columns.start, visible.start);
+    let end = cmp::min(columns.end, visible.end);
+    start.saturating_sub(visible.start)..end.saturating_sub(visible.start)
+}
+
+/* Print text in the theme's colour, with the char ranges in
+   highlights marked as search matches, those in spans in their style
+   and those in selected reversed. Each run is undone attribute by
+   attribute rather than with a blanket reset, which would take the
+   background of the cursor line with it. */
+fn print_highlighted<'a>(
+    buffer:     &'a mut CommandBuffer,
+    theme:      &Theme,
+    text:       &str,
+    highlights: &[Range<usize>],
+    spans:      &[highlight::Span],
+    selected:   &Range<usize>,
+) -> io::Result<&'a mut CommandBuffer> {
+    let look = |column: usize| (
+        spans.iter().find(|(columns, _)| columns.contains(&column)).map(|(_, style)| *style),
+        highlights.iter().any(|columns| columns.contains(&column)),
+        selected.contains(&column),
+    );
+
+    let length = text.chars().count();
+    let mut start = 0;
+    while start < length {
+        let (span, marked, chosen) = look(start);
+        let end = (start + 1..length)
+            .find(|&column| look(column) != (span, marked, chosen))
+            .unwrap_or(length);
+        let run = char_window(text, start, end - start).unwrap_or(0..0);
+
+        let mut look = style::ContentStyle::new();
+        if theme.text != style::Color::Reset {
+            look = look.with(theme.text);
+        }
+        if let Some(span) = span {
+            look = look.with(span.foreground);
+            if span.bold {
+                look = look.bold();
+            }
+        }
+        if marked {
+            look = look.with(theme.search_match).reverse();
+        } else if chosen {
+            look = look.reverse();
+        }
+
+        buffer.print_styled(&text[run], look)?;
+        start = end;
+    }
+
+    Ok(buffer)
+}
+
+impl From<OpenFile> for Editor {
+    fn from(open_file: OpenFile) -> Self {
+        let mut buffers = open_file.file_paths.into_iter()
+            .map(|file_path| Buffer::new(Some(file_path)))
+            .collect::<Vec<_>>();
+        if buffers.is_empty() {
+            buffers.push(Buffer::new(None));
+        }
+        for buffer in &mut buffers {
+            buffer.read_only = open_file.read_only;
+        }
+
+        Self {
+            screen_size:           ScreenSize::default(),
+            buffers,
+            active:                0,
+            layout:                Layout::Single,
+            key_history:           Default::default(),
+            show_key_history:      true,
+            started:               time::Instant::now(),
+            now:                   time::Instant::now(),
+            prompt:                None,
+            message:               StatusMessage::default(),
+            autosave:              None,
+            keymap:                Keymap::default(),
+            show_line_numbers:     true,
+            relative_numbers:      false,
+            highlight_cursor_line: true,
+            list:                  false,
+            strip_on_save:         false,
+            final_newline:         false,
+            highlighter:           Some(Box::new(RustKeywordHighlighter)),
+            theme:                 Theme::default(),
+            focused:               true,
+            modal:                 false,
+            mode:                  Mode::Insert,
+            pending:               PendingKeys::default(),
+            count:                 None,
+            register:              Register::default(),
+            selection:             None,
+            use_clipboard:         false,
+            quit_pending:          false,
+            bell:                  Bell::Off,
+            ringing:               false,
+            spinner:               0,
+            redraw:                Redraw::All,
+        }
+    }
+}
+
+/* Something too big to copy, passed along in a message that update
+   only gets to borrow. Whoever takes it first has it. It may be made
+   on a worker thread, hence the Mutex. */
+struct Handover<T>(Arc<Mutex<Option<T>>>);
+
+impl <T> Handover<T> {
+    fn new(value: T) -> Self {
+        Self(Arc::new(Mutex::new(Some(value))))
+    }
+
+    fn take(&self) -> Option<T> {
+        self.0.lock().ok()?.take()
+    }
+}
+
+impl <T> Clone for Handover<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+#[derive(Clone)]
+enum Message {
+    Input(InputEvent),
+    SizedChanged(ScreenSize),
+    Saved { index: usize, stripped: usize, save_as: Option<path::PathBuf>, outcome: elm::Resource<()> },
+    SaveAs(path::PathBuf),
+    FileLoaded { index: usize, serial: usize, reload: bool, outcome: elm::Resource<Handover<EditingModel>> },
+    SwitchBuffer(usize),
+    GotoLine(usize),
+    Search(String),
+    SearchNext,
+    Replace { from: String, to: String, all: bool },
+    Tick(time::Instant),
+    Frame(usize),
+    ExpireStatus(usize),
+    ExpirePendingKey(usize),
+    Autosave,
+    WriteSwap { index: usize, changes: usize },
+    RecoverSwap(usize),
+    ClipboardSet(elm::Resource<()>),
+    ClipboardGot { after: bool, outcome: elm::Resource<String> },
+    Bell,
+    BellFlashed,
+    Command(Command),
+    UnknownCommand(String),
+    QuitIfSaved,
+    Reload,
+}
+
+impl Message {
+    
+}
+
+impl elm::Application for Editor {
+    type Msg  = Message;
+    type View = CommandBuffer;
+    type Flags = (OpenFile, Config);
+
+    fn init((open_file, config): Self::Flags) -> (Self, elm::Cmd<Message>) {
+        let mut editor = Editor::from(open_file).configure(config);
+        let load = editor.load();
+        (editor, ScreenSize::request().and_then(load))
+    }
+
+    fn update(&mut self, message: &Message) -> elm::Cmd<Message> {
+        let active_before = self.active;
+        let cursor_before = self.cursor();
+        let serial_before = self.message.serial;
+        let changes_before = self.buffer().changes;
+        let look_before = self.look();
+
+        /* Whatever comes after the bell, the flash of it included,
+           puts the status bar back the way it was. */
+        self.ringing = false;
+
+        let cmd = match message {
+            Message::Input(input) =>
+                self.input_received(input),
+
+            Message::SizedChanged(size) =>
+                self.screen_size_changed(size.clone()),
+
+            Message::Saved { index, stripped, save_as, outcome } =>
+                self.saved(*index, *stripped, save_as.as_deref(), outcome),
+
+            Message::SaveAs(file_path) =>
+                self.save_as(file_path),
+
+            Message::FileLoaded { index, serial, reload, outcome } =>
+                self.file_loaded(*index, *serial, *reload, outcome),
+
+            Message::SwitchBuffer(index) =>
+                self.switch_buffer(*index),
+
+            Message::GotoLine(row) =>
+                self.go_to_line(*row),
+
+            Message::Search(query) =>
+                self.search(query),
+
+            Message::SearchNext =>
+                self.search_next(),
+
+            Message::Replace { from, to, all } =>
+                self.replace(from, to, *all),
+
+            Message::Tick(now) => {
+                self.now = *now;
+                elm::Cmd::none()
+            }
+
+            Message::Frame(frame) => {
+                self.spinner = *frame;
+                elm::Cmd::none()
+            }
+
+            Message::ExpireStatus(serial) => {
+                self.message.expire(*serial);
+                elm::Cmd::none()
+            }
+
+            Message::ExpirePendingKey(serial) =>
+                self.pending_key_expired(*serial),
+
+            Message::Autosave =>
+                self.autosave(),
+
+            Message::WriteSwap { index, changes } =>
+                self.write_swap(*index, *changes),
+
+            Message::RecoverSwap(index) =>
+                self.recover_swap(*index),
+
+            Message::ClipboardSet(outcome) =>
+                self.clipboard_set(outcome),
+
+            Message::ClipboardGot { after, outcome } =>
+                self.clipboard_got(*after, outcome),
+
+            Message::Bell =>
+                self.ring(),
+
+            Message::BellFlashed =>
+                elm::Cmd::none(),
+
+            Message::Command(command) =>
+                self.run_command(command),
+
+            Message::UnknownCommand(input) =>
+                self.unknown_command(input),
+
+            Message::QuitIfSaved =>
+                self.quit_if_saved(),
+
+            Message::Reload =>
+                self.reload(),
+        };
+
+        let journal = &self.buffers[active_before].contents.journal;
+        let journaled = !journal.is_empty();
+        let mut redrawn = journal.iter().map(Edit::row).collect::<Vec<_>>();
+
+        self.buffers[active_before].record_change(cursor_before.clone());
+
+        /* The swap file catches up once typing pauses. */
+        let changes = self.buffers[active_before].changes;
+        let cmd = if changes != changes_before {
+            cmd.and_then(elm::Cmd::delay(SWAP_DELAY, Message::WriteSwap { index: active_before, changes }))
+        } else {
+            cmd
+        };
+
+        /* Edits can change the gutter width and with it the
+           room left for text. */
+        self.fit_text_area();
+
+        /* Besides the lines edited, the cursor line's highlight moves
+           from one line to another. Undo and redo leave no journal
+           to go by. */
+        let unjournaled = changes - changes_before > usize::from(journaled);
+        self.redraw = if unjournaled || self.look() != look_before {
+            Redraw::All
+        } else {
+            redrawn.extend([cursor_before.row, self.cursor().row]);
+            redrawn.sort_unstable();
+            redrawn.dedup();
+            Redraw::Rows(redrawn)
+        };
+
+        /* A message just shown goes away on its own; should another
+           follow before then, this one's expiry misses it. */
+        if self.message.serial != serial_before {
+            let expire = Message::ExpireStatus(self.message.serial);
+            cmd.and_then(elm::Cmd::delay(STATUS_MESSAGE_TIMEOUT, expire))
+        } else {
+            cmd
+        }
+    }
+
+    fn view(&self, frame: &mut Self::View) -> io::Result<()> {
+        self.render(frame)
+    }
+
+    fn subscriptions(&self) -> elm::Sub<Message> {
+        let mut subscriptions = vec![elm::Sub::interval(time::Duration::from_secs(1), Message::Tick)];
+        if let Some(every) = self.autosave {
+            subscriptions.push(elm::Sub::interval(every, |_| Message::Autosave));
+        }
+        /* Only while there is something to wait for, or the loop
+           would never get to rest. */
+        if self.buffers.iter().any(|buffer| buffer.loading) {
+            subscriptions.push(elm::Sub::animation_frame(Message::Frame));
+        }
+        elm::Sub::batch(subscriptions)
+    }
+
+}
+
+impl From<event::Event> for Message {
+    fn from(value: event::Event) -> Self {
+        Message::Input(value.into())
+    }
+}
+
+fn main() -> io::Result<()> {
+    let open_file = OpenFile::from_args(env::args());
+    let config = Config::load()?;
+
+    tui::install_panic_hook();
+
+    let out = io::BufWriter::with_capacity(16384, io::stdout());
+    let mut screen = tui::Screen::attach(out)?
+        .with_poll_timeout(config.poll_timeout)
+        .enter_raw_mode()?
+        .enable_mouse()?;
+    if config.alternate_screen {
+        screen = screen.enter_alternate_screen()?;
+    }
+
+    screen.run_automat::<Editor>((open_file, config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elm::Application;
+    use std::{cell::Cell, rc::Rc};
+    use crossterm::{QueueableCommand, cursor, event::{KeyCode, KeyEvent, KeyModifiers}};
+
+    fn key(code: KeyCode) -> event::Event {
+        event::Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn ctrl(c: char) -> event::Event {
+        event::Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL))
+    }
+
+    /* Enter for each line break, Esc for \x1b and Backspace for \x08. */
+    fn typed(text: &str) -> impl Iterator<Item = event::Event> + '_ {
+        text.chars().map(|c| match c {
+            '\n'   => key(KeyCode::Enter),
+            '\x1b' => key(KeyCode::Esc),
+            '\x08' => key(KeyCode::Backspace),
+            c      => key(KeyCode::Char(c)),
+        })
+    }
+
+    /* The editor on an 80x10 screen with no file, after script and
+       whatever it set off has run its course. */
+    fn run(script: Vec<event::Event>) -> Editor {
+        run_with(vec![], Config::default(), script).0
+    }
+
+    /* The same with files open, and everything that was on screen
+       along the way, as text. The clock stands still, so the last
+       message is still up at the end. */
+    fn run_with(file_paths: Vec<path::PathBuf>, config: Config, script: Vec<event::Event>) -> (Editor, String) {
+        let steps = script.len() * 8 + 20;
+        let host = headless::ScriptedHost::new(iter::once(event::Event::Resize(80, 10)).chain(script))
+            .unwrap()
+            .with_clock_stopped();
+        let open_file = OpenFile { file_paths, read_only: false };
+        let editor = host.run_for::<Editor>((open_file, config), steps).unwrap();
+        (editor, host.text())
+    }
+
+    /* Carries cmd out where the test is, as the loop would without
+       workers; nothing that is put off ever comes due. */
+    fn settle(editor: &mut Editor, cmd: elm::Cmd<Message>) {
+        match cmd {
+            elm::Cmd::Suspend(effect) => {
+                let next = editor.update(&effect().unwrap());
+                settle(editor, next);
+            }
+            elm::Cmd::Dispatch(message) => {
+                let next = editor.update(&message);
+                settle(editor, next);
+            }
+            elm::Cmd::AndThen(then, first) => {
+                settle(editor, *first);
+                settle(editor, *then);
+            }
+            elm::Cmd::Batch(cmds) => {
+                for cmd in cmds {
+                    settle(editor, cmd);
+                }
+            }
+            _otherwise => (),
+        }
+    }
+
+    fn send(editor: &mut Editor, message: Message) {
+        let cmd = editor.update(&message);
+        settle(editor, cmd);
+    }
+
+    fn modal() -> Config {
+        Config { modal: true, ..Config::default() }
+    }
+
+    /* A directory of a test's own, gone again once the test is. */
+    struct Scratch(path::PathBuf);
+
+    impl Scratch {
+        fn new(test: &str) -> Self {
+            let dir = env::temp_dir().join(format!("rusty_spoon-{}-{}", test, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn file(&self, name: &str, contents: impl AsRef<[u8]>) -> path::PathBuf {
+            let file_path = self.0.join(name);
+            fs::write(&file_path, contents).unwrap();
+            file_path
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn arrow_keys_move_the_cursor() {
+        let moves = [KeyCode::Up, KeyCode::Up, KeyCode::Right, KeyCode::Down, KeyCode::Left, KeyCode::Left].map(key);
+        let editor = run(typed("one\ntwo\nthree").chain(moves).collect());
+
+        assert_eq!(editor.cursor(), Position { row: 1, column: 1 });
+    }
+
+    #[test]
+    fn invalid_utf8_opens_read_only_and_is_never_written_back() {
+        let scratch = Scratch::new("invalid_utf8");
+        let bytes = b"caf\xe9 \xff\xfe\n";
+        let file_path = scratch.file("latin1.txt", bytes);
+
+        let saves = [
+            vec![ctrl('s')],
+            typed(":w!\n").collect(),
+            typed(&format!(":w {}\n", file_path.display())).collect(),
+        ];
+        for save in saves {
+            let (editor, _) = run_with(vec![file_path.clone()], modal(), save);
+
+            assert!(editor.buffer().read_only);
+            assert_eq!(editor.buffer().contents.encoding, Encoding::Unknown);
+            assert_eq!(editor.buffer().contents.serialize(), "caf\u{fffd} \u{fffd}\u{fffd}\n");
+            assert_eq!(fs::read(&file_path).unwrap(), bytes);
+            assert_eq!(editor.message.text(), "Buffer is read-only");
+        }
+    }
+
+    #[test]
+    fn read_only_buffers_can_still_be_saved_elsewhere() {
+        let scratch = Scratch::new("read_only_save_as");
+        let file_path = scratch.file("latin1.txt", b"caf\xe9");
+        let copy = scratch.0.join("copy.txt");
+
+        run_with(vec![file_path], modal(), typed(&format!(":w {}\n", copy.display())).collect());
+
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "caf\u{fffd}");
+    }
+
+    #[test]
+    fn reload_replaces_edits_with_what_is_on_disk() {
+        let scratch = Scratch::new("reload");
+        let file_path = scratch.file("notes.txt", "one\ntwo");
+        let (mut editor, _) = run_with(vec![file_path.clone()], modal(), typed("xjx").collect());
+        assert_eq!(editor.buffer().contents.serialize(), "ne\nwo");
+
+        fs::write(&file_path, "three\nfour").unwrap();
+        send(&mut editor, Message::Reload);
+
+        assert_eq!(editor.buffer().contents.serialize(), "three\nfour");
+        assert!(!editor.buffer().modified);
+        assert!(editor.buffer().history.undo.is_empty());
+        assert_eq!(editor.cursor(), Position::default());
+    }
+
+    #[test]
+    fn failed_reload_keeps_the_buffer_as_it_was() {
+        let scratch = Scratch::new("failed_reload");
+        let file_path = scratch.file("notes.txt", "one\ntwo");
+        let (mut editor, _) = run_with(vec![file_path.clone()], modal(), typed("x").collect());
+
+        fs::remove_file(&file_path).unwrap();
+        send(&mut editor, Message::Reload);
+
+        assert_eq!(editor.buffer().contents.serialize(), "ne\ntwo");
+        assert_eq!(editor.buffer().file_path.as_deref(), Some(file_path.as_path()));
+        assert!(editor.buffer().modified);
+        assert!(!editor.buffer().loading);
+        assert!(editor.message.text().starts_with("Could not reload notes.txt"));
+    }
+
+    #[test]
+    fn editing_an_open_file_by_another_name_switches_to_it() {
+        let scratch = Scratch::new("edit_open_file");
+        let file_path = scratch.file("notes.txt", "one");
+        let other_name = scratch.0.join("..").join(scratch.0.file_name().unwrap()).join("notes.txt");
+        let (editor, _) = run_with(vec![file_path], modal(), typed(&format!(":e {}\n", other_name.display())).collect());
+
+        assert_eq!(editor.buffers.len(), 1);
+        assert_eq!(editor.active, 0);
+    }
+
+    #[test]
+    fn last_interval_is_between_the_last_two_events() {
+        let now = Rc::new(Cell::new(time::Instant::now()));
+        let clock = now.clone();
+        let mut history = KeyHistory::with_horizon(3, move || clock.get());
+
+        history.record(&InputEvent::Char('a'));
+        assert_eq!(history.last_interval(), None);
+
+        now.set(now.get() + time::Duration::from_millis(40));
+        history.record(&InputEvent::Char('b'));
+        now.set(now.get() + time::Duration::from_millis(125));
+        history.record(&InputEvent::Char('c'));
+
+        assert_eq!(history.last_interval(), Some(time::Duration::from_millis(125)));
+        history.show_intervals = true;
+        assert_eq!(history.to_string(), "[Char('a'),+40ms Char('b'),+125ms Char('c'),]");
+    }
+
+    #[test]
+    fn slash_searches_as_the_query_is_typed() {
+        let lines = "ialpha\nbeta\nalphabet\x1bgg";
+        let searching = |keys: &str| run_with(vec![], modal(), typed(&format!("{}{}", lines, keys)).collect()).0;
+
+        let editor = searching("/be");
+        assert!(editor.prompt.is_some());
+        assert_eq!(editor.cursor(), Position { row: 1, column: 0 });
+
+        let editor = searching("/alphab");
+        assert_eq!(editor.cursor(), Position { row: 2, column: 0 });
+
+        let editor = searching("/bez");
+        assert_eq!(editor.cursor(), Position { row: 0, column: 0 });
+
+        let editor = searching("/alphab\x08\x08");
+        assert_eq!(editor.cursor(), Position { row: 2, column: 0 });
+
+        let editor = searching("/be\x1b");
+        assert_eq!(editor.cursor(), Position { row: 0, column: 0 });
+        assert!(editor.buffer().search.is_none());
+
+        let editor = searching("/be\nn");
+        assert_eq!(editor.cursor(), Position { row: 2, column: 5 });
+    }
+
+    #[test]
+    fn slash_types_a_slash_without_modes() {
+        let script = typed("ab").chain([key(KeyCode::Home)]).chain(typed("/b")).collect();
+        let editor = run(script);
+
+        assert!(editor.prompt.is_none());
+        assert_eq!(editor.buffer().contents.serialize(), "/bab");
+        assert_eq!(editor.cursor(), Position { row: 0, column: 2 });
+    }
+
+    #[test]
+    fn slash_types_a_slash_in_insert_mode() {
+        let editor = run_with(vec![], modal(), typed("ia/b").collect()).0;
+
+        assert!(editor.prompt.is_none());
+        assert_eq!(editor.buffer().contents.serialize(), "a/b");
+    }
+
+    #[test]
+    fn search_scrolls_matches_past_the_viewport_into_view() {
+        let scratch = Scratch::new("search_viewport");
+        let mut text = (0..30).map(|row| format!("line {}", row)).collect::<Vec<_>>();
+        text[25] = format!("{}needle", " ".repeat(100));
+        let file_path = scratch.file("haystack.txt", text.join("\n"));
+
+        let (editor, _) = run_with(vec![file_path], modal(), typed("/needle\n").collect());
+        let viewport = &editor.buffer().navigation.viewport;
+
+        assert_eq!(editor.cursor(), Position { row: 25, column: 100 });
+        assert!(viewport.row_offset <= 25 && 25 < viewport.row_offset + 9);
+        assert!(viewport.column_offset > 0);
+    }
+
+    #[test]
+    fn reading_a_line_at_a_time_agrees_with_splitting() {
+        let texts = [
+            "", "\n", "one", "one\n", "one\n\n", "one\r\ntwo\r\n", "one\r\ntwo\nthree\r\n",
+            "stray\rcarriage", "\u{feff}bom\n", "café\nsociété", "\n\nblank lines first",
+        ];
+        for text in texts {
+            let read = EditingModel::from_reader(text.as_bytes()).unwrap();
+            let split = EditingModel::from_text(text);
+
+            assert_eq!(read.serialize(), split.serialize(), "{:?}", text);
+            assert_eq!(read.line_count(), split.line_count(), "{:?}", text);
+            assert_eq!(read.line_ending, split.line_ending, "{:?}", text);
+            assert_eq!(read.final_newline, split.final_newline, "{:?}", text);
+            assert_eq!(read.bom, split.bom, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn reading_bad_utf8_makes_do_with_replacements() {
+        let model = EditingModel::from_reader(&b"fine\nbad \xff here\n"[..]).unwrap();
+
+        assert_eq!(model.encoding, Encoding::Unknown);
+        assert_eq!(model.line(1), "bad \u{fffd} here");
+    }
+
+    /* Run with --ignored, ideally with --release; the bounds leave
+       room for a debug build on a slow machine. */
+    #[test]
+    #[ignore]
+    fn reading_100k_lines_keeps_up_with_splitting_them() {
+        let scratch = Scratch::new("read_100k_lines");
+        let text = (0..100_000)
+            .map(|row| format!("{:>6} the quick brown fox jumps over the lazy dog", row))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let file_path = scratch.file("big.txt", text);
+
+        let started = time::Instant::now();
+        let split = EditingModel::from_text(&fs::read_to_string(&file_path).unwrap());
+        let splitting = started.elapsed();
+
+        let started = time::Instant::now();
+        let read = EditingModel::read_file(&file_path).unwrap();
+        let reading = started.elapsed();
+
+        eprintln!("splitting {:?}, reading a line at a time {:?}", splitting, reading);
+        assert_eq!(read.line_count(), 100_000);
+        assert_eq!(read.serialize(), split.serialize());
+        assert!(reading < time::Duration::from_secs(1), "took {:?}", reading);
+        assert!(reading < splitting * 2, "{:?} against {:?}", reading, splitting);
+    }
+
+    /* A viewport that has never clipped anything, to check against. */
+    fn clip_afresh(contents: &EditingModel, column_offset: usize, width: usize) -> (String, bool, bool) {
+        let viewport = EditingViewport { column_offset, ..EditingViewport::default() };
+        let clipped = viewport.select_and_clip(0, width, contents);
+        (clipped.text.into_owned(), clipped.cut_left, clipped.cut_right)
+    }
+
+    #[test]
+    fn clipping_from_an_anchor_matches_clipping_from_the_start() {
+        let contents = EditingModel::from_text("a\tb日本語\tcafé société\t\tテスト end");
+        let width = 7;
+        let mut viewport = EditingViewport::default();
+        let offsets = (0..40).chain((0..40).rev()).chain([3, 30, 12, 0]);
+        for column_offset in offsets {
+            viewport.column_offset = column_offset;
+            let clipped = viewport.select_and_clip(0, width, &contents);
+
+            assert_eq!(
+                (clipped.text.into_owned(), clipped.cut_left, clipped.cut_right),
+                clip_afresh(&contents, column_offset, width),
+                "at {}",
+                column_offset,
+            );
+        }
+    }
+
+    #[test]
+    fn anchors_are_not_taken_to_another_line() {
+        let contents = EditingModel::from_text("a\tbcdefgh\nabcdefghij");
+        let mut viewport = EditingViewport { column_offset: 4, ..EditingViewport::default() };
+        assert_eq!(viewport.select_and_clip(0, 5, &contents).text, "bcdef");
+
+        viewport.row_offset = 1;
+        assert_eq!(viewport.select_and_clip(0, 5, &contents).text, "efghi");
+    }
+
+    /* Run with --ignored. Each step should cost about the width of
+       the screen, not the length of the line up to it. */
+    #[test]
+    #[ignore]
+    fn scrolling_right_across_a_megabyte_line_takes_bounded_time_per_step() {
+        let line = "{\"key\":\"välue\",\t\"n\":12345}".repeat(1024 * 1024 / 28);
+        let contents = EditingModel::from_text(&line);
+        let columns = line.chars().count();
+        let mut viewport = EditingViewport::default();
+
+        let mut steps = vec![];
+        for column_offset in (0..columns).step_by(500) {
+            viewport.column_offset = column_offset;
+            let started = time::Instant::now();
+            viewport.select_and_clip(0, 200, &contents);
+            steps.push(started.elapsed());
+        }
+
+        let slowest = steps.iter().max().unwrap();
+        let first = steps[..10].iter().max().unwrap();
+        let last = steps[steps.len() - 10..].iter().max().unwrap();
+        eprintln!("{} steps, slowest {:?}, first {:?}, last {:?}", steps.len(), slowest, first, last);
+        assert!(*slowest < time::Duration::from_millis(5), "slowest step took {:?}", slowest);
+        assert!(*last < *first * 10 + time::Duration::from_micros(200), "{:?} at the end, {:?} at the start", last, first);
+    }
+
+    #[test]
+    fn clipping_cuts_between_chars_not_bytes() {
+        for line in ["café société", "日本語テスト"] {
+            let contents = EditingModel::from_text(line);
+            let count = line.chars().count();
+            for column_offset in 0..=count + 2 {
+                for width in 0..=count + 2 {
+                    let (text, cut_left, cut_right) = clip_afresh(&contents, column_offset, width);
+                    let expected = line.chars().skip(column_offset).take(width).collect::<String>();
+
+                    assert_eq!(text, expected, "{:?} from {} for {}", line, column_offset, width);
+                    assert_eq!(cut_left, column_offset > 0, "{:?} from {}", line, column_offset);
+                    assert_eq!(cut_right, column_offset + width < count, "{:?} from {} for {}", line, column_offset, width);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn clipping_shows_the_chars_scrolled_to() {
+        let contents = EditingModel::from_text("café société\n日本語テスト");
+        let viewport = EditingViewport { column_offset: 3, ..EditingViewport::default() };
+
+        assert_eq!(viewport.select_and_clip(0, 6, &contents).text, "é soci");
+        assert_eq!(viewport.select_and_clip(1, 6, &contents).text, "テスト");
+    }
+
+    #[test]
+    fn chars_go_in_at_the_start_middle_and_end_of_a_line() {
+        let mut contents = EditingModel::from_text("bd");
+        contents.insert_char(0, 0, 'a');
+        contents.insert_char(0, 2, 'c');
+        contents.insert_char(0, 4, 'e');
+
+        assert_eq!(contents.serialize(), "abcde");
+    }
+
+    #[test]
+    fn chars_go_in_by_char_not_by_byte() {
+        let mut contents = EditingModel::from_text("日本\ncafé");
+        contents.insert_char(0, 1, '語');
+        contents.insert_char(1, 4, '!');
+        contents.insert_char(1, 99, '?');
+
+        assert_eq!(contents.serialize(), "日語本\ncafé!?");
+    }
+
+    #[test]
+    fn typing_moves_the_cursor_along() {
+        let editor = run(typed("ab").chain([key(KeyCode::Left)]).chain(typed("X")).collect());
+
+        assert_eq!(editor.buffer().contents.serialize(), "aXb");
+        assert_eq!(editor.cursor(), Position { row: 0, column: 2 });
+    }
+
+    #[test]
+    fn typing_goes_where_the_cursor_is_when_scrolled() {
+        let long = "x".repeat(100);
+        let script = typed(&long).chain(typed("\n\n\n\n\n\n\n\n\n\nend")).collect();
+        let editor = run(script);
+        let viewport = &editor.buffer().navigation.viewport;
+
+        assert!(viewport.row_offset > 0);
+        assert_eq!(editor.buffer().contents.line(0), long);
+        assert_eq!(editor.buffer().contents.line(10), "end");
+        assert_eq!(editor.cursor(), Position { row: 10, column: 3 });
+
+        let editor = run(typed(&long).chain([key(KeyCode::Left)]).chain(typed("Y")).collect());
+
+        assert!(editor.buffer().navigation.viewport.column_offset > 0);
+        assert_eq!(editor.buffer().contents.line(0), format!("{}Yx", "x".repeat(99)));
+        assert_eq!(editor.cursor(), Position { row: 0, column: 100 });
+    }
+
+    #[test]
+    fn backspace_at_the_very_start_does_nothing() {
+        let mut contents = EditingModel::from_text("one\ntwo");
+
+        assert_eq!(contents.delete_char_before(0, 0), Position { row: 0, column: 0 });
+        assert_eq!(contents.serialize(), "one\ntwo");
+    }
+
+    #[test]
+    fn backspace_deletes_the_char_before() {
+        let mut contents = EditingModel::from_text("café");
+
+        assert_eq!(contents.delete_char_before(0, 4), Position { row: 0, column: 3 });
+        assert_eq!(contents.delete_char_before(0, 1), Position { row: 0, column: 0 });
+        assert_eq!(contents.serialize(), "af");
+    }
+
+    #[test]
+    fn backspace_at_the_start_of_a_line_joins_it_to_the_one_above() {
+        let mut contents = EditingModel::from_text("日本\n語テスト\nend");
+
+        assert_eq!(contents.delete_char_before(1, 0), Position { row: 0, column: 2 });
+        assert_eq!(contents.line_count(), 2);
+        assert_eq!(contents.line(0), "日本語テスト");
+        assert_eq!(contents.line(1), "end");
+    }
+
+    #[test]
+    fn delete_removes_the_char_under_the_cursor() {
+        let mut contents = EditingModel::from_text("café");
+
+        assert_eq!(contents.delete_char_at(0, 3), Position { row: 0, column: 3 });
+        assert_eq!(contents.delete_char_at(0, 0), Position { row: 0, column: 0 });
+        assert_eq!(contents.serialize(), "af");
+    }
+
+    #[test]
+    fn delete_at_the_end_of_a_line_joins_the_next_one_on() {
+        let mut contents = EditingModel::from_text("one\ntwo");
+
+        assert_eq!(contents.delete_char_at(0, 3), Position { row: 0, column: 3 });
+        assert_eq!(contents.serialize(), "onetwo");
+
+        assert_eq!(contents.delete_char_at(0, 6), Position { row: 0, column: 6 });
+        assert_eq!(contents.serialize(), "onetwo");
+    }
+
+    #[test]
+    fn backspace_and_delete_keys_move_the_cursor() {
+        let script = typed("ab\ncd").chain([key(KeyCode::Home)]).chain(typed("\x08")).collect();
+        let editor = run(script);
+
+        assert_eq!(editor.buffer().contents.serialize(), "abcd");
+        assert_eq!(editor.cursor(), Position { row: 0, column: 2 });
+
+        let script = typed("abc").chain([key(KeyCode::Home), key(KeyCode::Delete)]).collect();
+        let editor = run(script);
+
+        assert_eq!(editor.buffer().contents.serialize(), "bc");
+        assert_eq!(editor.cursor(), Position { row: 0, column: 0 });
+    }
+
+    /* The text and the cursor after each of steps, every one run on
+       top of those before it. */
+    fn stages(steps: &[Vec<event::Event>]) -> Vec<(String, Position)> {
+        (1..=steps.len())
+            .map(|count| {
+                let editor = run(steps[..count].concat());
+                (editor.buffer().contents.serialize(), editor.cursor())
+            })
+            .collect()
+    }
+
+    fn at(text: &str, row: usize, column: usize) -> (String, Position) {
+        (text.to_owned(), Position { row, column })
+    }
+
+    #[test]
+    fn undo_and_redo_interleave_with_edits() {
+        let (undo, redo) = (|| vec![ctrl('z')], || vec![ctrl('y')]);
+        let stages = stages(&[
+            typed("ab").collect(),
+            undo(),
+            undo(),
+            redo(),
+            vec![key(KeyCode::Enter)],
+            vec![key(KeyCode::Char('c'))],
+            undo(),
+            vec![key(KeyCode::Backspace)],
+            undo(),
+            redo(),
+            [undo(), undo(), redo()].concat(),
+        ]);
+
+        assert_eq!(stages, vec![
+            at("ab", 0, 2),
+            at("a", 0, 1),
+            at("", 0, 0),
+            at("a", 0, 1),
+            at("a\n", 1, 0),
+            at("a\nc", 1, 1),
+            at("a\n", 1, 0),
+            at("a", 0, 1),
+            at("a\n", 1, 0),
+            at("a", 0, 1),
+            at("a\n", 1, 0),
+        ]);
+    }
+
+    #[test]
+    fn a_new_edit_after_undo_drops_what_could_be_redone() {
+        let editor = run(typed("ab").chain([ctrl('z')]).chain(typed("X")).chain([ctrl('y')]).collect());
+
+        assert_eq!(editor.buffer().contents.serialize(), "aX");
+        assert_eq!(editor.cursor(), Position { row: 0, column: 2 });
+        assert_eq!(editor.message.text(), "Nothing to redo");
+    }
+
+    #[test]
+    fn undo_puts_the_cursor_back_where_the_edit_was() {
+        let script = typed("one\ntwo")
+            .chain([key(KeyCode::Up), key(KeyCode::Home), key(KeyCode::Delete)])
+            .chain([key(KeyCode::Down), key(KeyCode::End), ctrl('z')])
+            .collect();
+        let editor = run(script);
+
+        assert_eq!(editor.buffer().contents.serialize(), "one\ntwo");
+        assert_eq!(editor.cursor(), Position { row: 0, column: 0 });
+    }
+
+    /* Only has to compile: both hosts take the editor's events and
+       frames, so the editor runs on either. */
+    fn runs_the_editor<H: elm::Host<Event = event::Event, Frame = tui::CommandBuffer>>(_host: &H) {}
+
+    #[test]
+    fn the_screen_and_the_scripted_host_are_both_hosts() {
+        let (screen, _) = tui::Screen::attach_string().unwrap();
+        runs_the_editor(&screen);
+        runs_the_editor(&headless::ScriptedHost::new([]).unwrap());
+    }
+
+    #[test]
+    fn the_editor_runs_a_turn_of_the_loop_on_the_screen() {
+        let (screen, output) = tui::Screen::attach_string().unwrap();
+        let open_file = OpenFile { file_paths: vec![], read_only: false };
+        let editor = screen.run_for::<Editor>((open_file, Config::default()), 1).unwrap();
+
+        assert_eq!(editor.buffers.len(), 1);
+        assert!(!output.borrow().is_empty());
+    }
+
+    #[test]
+    fn viewing_the_same_editor_twice_draws_nothing_the_second_time() {
+        let editor = run(typed("hello\nworld").collect());
+        let mut frame = tui::CommandBuffer::default();
+        editor.view(&mut frame).unwrap();
+        assert!(tui::strip_ansi(&frame.take().unwrap()).contains("world"));
+
+        editor.view(&mut frame).unwrap();
+        assert!(frame.take().unwrap().is_empty());
+    }
+
+    /* Everything rendered, escape sequences and all. */
+    fn rendered(script: Vec<event::Event>) -> Vec<u8> {
+        let steps = script.len() * 8 + 20;
+        let host = headless::ScriptedHost::new(iter::once(event::Event::Resize(80, 10)).chain(script))
+            .unwrap()
+            .with_clock_stopped();
+        let open_file = OpenFile { file_paths: vec![], read_only: false };
+        host.run_for::<Editor>((open_file, Config::default()), steps).unwrap();
+        host.output()
+    }
+
+    #[test]
+    fn a_message_that_changes_nothing_renders_nothing() {
+        let script = typed("hello").collect::<Vec<_>>();
+        let then_nothing = [script.clone(), vec![event::Event::Resize(80, 10)]].concat();
+
+        assert_eq!(rendered(then_nothing), rendered(script));
+    }
+
+    /* Where the cursor is on screen, and the top row in view, after
+       each of moves on thirty lines shown ten at a time. */
+    fn stepping(scrolloff: usize, moves: &[Direction]) -> Vec<(usize, usize)> {
+        let contents = EditingModel::from_text(&"line\n".repeat(30));
+        let mut navigation = NavigationModel {
+            screen_size: ScreenSize { columns: 80, rows: 10 },
+            scrolloff,
+            ..NavigationModel::default()
+        };
+        moves.iter()
+            .map(|direction| {
+                navigation.move_intended(direction, &contents);
+                (navigation.cursor.row, navigation.viewport.row_offset)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stepping_down_scrolls_with_three_lines_still_below() {
+        let steps = stepping(3, &[Direction::Down; 29]);
+
+        assert_eq!(steps[5], (6, 0));
+        assert_eq!(steps[6], (6, 1));
+        assert_eq!(steps[25], (6, 20));
+        assert_eq!(steps[26], (7, 20));
+        assert_eq!(steps[28], (9, 20));
+    }
+
+    #[test]
+    fn stepping_up_scrolls_with_three_lines_still_above() {
+        let moves = [[Direction::Down; 29], [Direction::Up; 29]].concat();
+        let steps = stepping(3, &moves)[29..].to_vec();
+
+        assert_eq!(steps[5], (3, 20));
+        assert_eq!(steps[6], (3, 19));
+        assert_eq!(steps[25], (3, 0));
+        assert_eq!(steps[26], (2, 0));
+        assert_eq!(steps[28], (0, 0));
+    }
+
+    #[test]
+    fn without_scrolloff_the_cursor_goes_to_the_edge_first() {
+        let steps = stepping(0, &[Direction::Down; 10]);
+
+        assert_eq!(steps[8], (9, 0));
+        assert_eq!(steps[9], (9, 1));
+    }
+
+    #[test]
+    fn scrolloff_leaves_the_cursor_a_row_between_the_margins() {
+        let steps = stepping(100, &[Direction::Down; 6]);
+
+        assert_eq!(steps[3], (4, 0));
+        assert_eq!(steps[4], (5, 0));
+        assert_eq!(steps[5], (5, 1));
+    }
+
+    #[test]
+    fn scrolloff_comes_from_the_config() {
+        let config = Config { scrolloff: 3, ..Config::default() };
+        let script = typed(&"\n".repeat(20))
+            .chain(iter::repeat_n(key(KeyCode::Up), 20))
+            .chain(iter::repeat_n(key(KeyCode::Down), 7))
+            .collect();
+        let (editor, _) = run_with(vec![], config, script);
+        let navigation = &editor.buffer().navigation;
+
+        assert_eq!(navigation.scrolloff, 3);
+        assert!(navigation.viewport.row_offset > 0);
+    }
+
+    /* The gutter labels of rows 5 to 15 of twenty lines, with the
+       cursor on row 10. */
+    fn labels(show_line_numbers: bool, relative_numbers: bool) -> Vec<usize> {
+        let scratch = Scratch::new(&format!("labels_{}_{}", show_line_numbers, relative_numbers));
+        let file_path = scratch.file("twenty.txt", "line\n".repeat(20));
+        let config = Config { show_line_numbers, relative_numbers, ..Config::default() };
+        let (editor, _) = run_with(vec![file_path], config, iter::repeat_n(key(KeyCode::Down), 10).collect());
+
+        assert_eq!(editor.buffer().cursor().row, 10);
+        (5..=15).map(|row| editor.line_label(editor.buffer(), row)).collect()
+    }
+
+    #[test]
+    fn relative_numbers_count_from_the_cursor() {
+        assert_eq!(labels(false, true), vec![5, 4, 3, 2, 1, 0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn hybrid_numbers_keep_the_cursor_line_absolute() {
+        assert_eq!(labels(true, true), vec![5, 4, 3, 2, 1, 11, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn absolute_numbers_count_from_one() {
+        assert_eq!(labels(true, false), (6..=16).collect::<Vec<_>>());
+    }
+
+    fn pos(row: usize, column: usize) -> Position {
+        Position { row, column }
+    }
+
+    #[test]
+    fn brackets_skip_nested_pairs_both_ways() {
+        let contents = EditingModel::from_text("f(a[1], (b), {c: (d)})");
+
+        assert_eq!(contents.matching_bracket(0, 1), Some(pos(0, 21)));
+        assert_eq!(contents.matching_bracket(0, 21), Some(pos(0, 1)));
+        assert_eq!(contents.matching_bracket(0, 8), Some(pos(0, 10)));
+        assert_eq!(contents.matching_bracket(0, 13), Some(pos(0, 20)));
+        assert_eq!(contents.matching_bracket(0, 19), Some(pos(0, 17)));
+    }
+
+    #[test]
+    fn brackets_pair_across_lines() {
+        let contents = EditingModel::from_text("fn main() {\n    if x {\n        y();\n    }\n}");
+
+        assert_eq!(contents.matching_bracket(0, 10), Some(pos(4, 0)));
+        assert_eq!(contents.matching_bracket(4, 0), Some(pos(0, 10)));
+        assert_eq!(contents.matching_bracket(1, 9), Some(pos(3, 4)));
+        assert_eq!(contents.matching_bracket(3, 4), Some(pos(1, 9)));
+    }
+
+    #[test]
+    fn brackets_count_in_chars_not_bytes() {
+        let contents = EditingModel::from_text("«(日本語)»");
+
+        assert_eq!(contents.matching_bracket(0, 1), Some(pos(0, 5)));
+        assert_eq!(contents.matching_bracket(0, 5), Some(pos(0, 1)));
+    }
+
+    #[test]
+    fn unbalanced_and_mismatched_brackets_have_no_partner() {
+        let unclosed = EditingModel::from_text("((a)\nb");
+        assert_eq!(unclosed.matching_bracket(0, 0), None);
+        assert_eq!(unclosed.matching_bracket(0, 1), Some(pos(0, 3)));
+
+        let mismatched = EditingModel::from_text("(b]");
+        assert_eq!(mismatched.matching_bracket(0, 0), None);
+        assert_eq!(mismatched.matching_bracket(0, 2), None);
+
+        let inside_out = EditingModel::from_text(")(");
+        assert_eq!(inside_out.matching_bracket(0, 0), None);
+        assert_eq!(inside_out.matching_bracket(0, 1), None);
+
+        assert_eq!(inside_out.matching_bracket(0, 9), None);
+        assert_eq!(EditingModel::from_text("x").matching_bracket(0, 0), None);
+    }
+
+    #[test]
+    fn the_bracket_under_the_cursor_is_shown_with_its_partner() {
+        let editor = run(typed("(a)b").chain([key(KeyCode::Home)]).collect());
+        assert_eq!(editor.matched_brackets(), Some((pos(0, 0), pos(0, 2))));
+
+        let editor = run(typed("(a)b").collect());
+        assert_eq!(editor.matched_brackets(), None);
+    }
+
+    /* Lines, words, chars and bytes. */
+    fn counts(text: &str) -> (usize, usize, usize, usize) {
+        let contents = EditingModel::from_text(text);
+        (contents.line_count(), contents.word_count(), contents.char_count(), contents.byte_count())
+    }
+
+    #[test]
+    fn an_empty_buffer_counts_one_empty_line() {
+        assert_eq!(counts(""), (1, 0, 0, 0));
+        assert_eq!(counts("\n"), (1, 0, 1, 1));
+    }
+
+    #[test]
+    fn whitespace_is_no_word() {
+        assert_eq!(counts("  \t \n\n   "), (3, 0, 9, 9));
+        assert_eq!(counts("one two  \nthree\t\n"), (2, 3, 17, 17));
+    }
+
+    #[test]
+    fn multibyte_text_counts_chars_and_bytes_apart() {
+        assert_eq!(counts("café société"), (1, 2, 12, 15));
+        assert_eq!(counts("日本語\u{3000}テスト"), (1, 2, 7, 21));
+    }
+
+    #[test]
+    fn counts_agree_with_what_would_be_saved() {
+        for text in ["a\r\nb\r\n", "\u{feff}bom\n", "no final newline", "日本\n語\n"] {
+            let contents = EditingModel::from_text(text);
+            let saved = contents.serialize();
+
+            assert_eq!(contents.char_count(), saved.chars().count(), "{:?}", text);
+            assert_eq!(contents.byte_count(), saved.len(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn stats_show_in_the_status_line() {
+        let alt_g = event::Event::Key(KeyEvent::new(KeyCode::Char('g'), KeyModifiers::ALT));
+        let editor = run(typed("héllo wörld\nbye").chain([alt_g]).collect());
+
+        assert_eq!(editor.message.text(), "2 lines, 3 words, 15 chars, 17 bytes");
+    }
+
+    /* Whatever is typed after the editor has quit never arrives, so
+       the text tells whether it did. */
+    #[test]
+    fn a_dirty_buffer_survives_the_first_quit() {
+        let editor = run(typed("a").chain([ctrl('q')]).chain(typed("b")).collect());
+
+        assert_eq!(editor.buffer().contents.serialize(), "ab");
+        assert!(!editor.quit_pending);
+
+        let editor = run(typed("a").chain([ctrl('q')]).collect());
+        assert!(editor.quit_pending);
+        assert_eq!(editor.message.text(), "Unsaved changes — quit again to discard, save to keep");
+    }
+
+    #[test]
+    fn a_dirty_buffer_quits_on_the_second_try() {
+        let editor = run(typed("a").chain([ctrl('q'), ctrl('q')]).chain(typed("b")).collect());
+
+        assert_eq!(editor.buffer().contents.serialize(), "a");
+    }
+
+    #[test]
+    fn any_other_key_calls_off_the_quit() {
+        let script = typed("a").chain([ctrl('q'), key(KeyCode::Left), ctrl('q')]).chain(typed("b")).collect();
+        let editor = run(script);
+
+        assert_eq!(editor.buffer().contents.serialize(), "ba");
+    }
+
+    #[test]
+    fn a_clean_buffer_quits_at_once() {
+        let editor = run([ctrl('q')].into_iter().chain(typed("b")).collect());
+
+        assert_eq!(editor.buffer().contents.serialize(), "");
+    }
+
+    #[test]
+    fn commands_parse_to_what_they_do() {
+        let file = |name: &str| Some(path::PathBuf::from(name));
+        for (input, command) in [
+            ("w",                Some(Command::Write(None))),
+            ("write",            Some(Command::Write(None))),
+            ("w notes.txt",      Some(Command::Write(file("notes.txt")))),
+            ("w  my notes.txt ", Some(Command::Write(file("my notes.txt")))),
+            ("w!",               Some(Command::ForceWrite)),
+            ("q",                Some(Command::Quit)),
+            (" quit ",           Some(Command::Quit)),
+            ("q!",               Some(Command::ForceQuit)),
+            ("quit!",            Some(Command::ForceQuit)),
+            ("wq",               Some(Command::WriteQuit)),
+            ("e notes.txt",      Some(Command::Edit(path::PathBuf::from("notes.txt")))),
+            ("e!",               Some(Command::Reload)),
+            ("e",                None),
+            ("q now",            None),
+            ("w! notes.txt",     None),
+            ("wq!",              None),
+            ("frob",             None),
+            ("",                 None),
+        ] {
+            assert_eq!(Command::parse(input), command, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn q_refuses_to_drop_changes_but_q_bang_does() {
+        let editor = run_with(vec![], modal(), typed("ia\x1b:q\n").collect()).0;
+        assert_eq!(editor.message.text(), "No write since last change to Unnamed (add ! to override)");
+
+        let editor = run_with(vec![], modal(), typed("ia\x1b:q\nib").collect()).0;
+        assert_eq!(editor.buffer().contents.serialize(), "ab");
+
+        let editor = run_with(vec![], modal(), typed("ia\x1b:q!\nib").collect()).0;
+        assert_eq!(editor.buffer().contents.serialize(), "a");
+    }
+
+    #[test]
+    fn wq_saves_and_quits() {
+        let scratch = Scratch::new("wq");
+        let file_path = scratch.file("notes.txt", "");
+        let (editor, _) = run_with(vec![file_path.clone()], modal(), typed("ihi\x1b:wq\nib").collect());
+
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "hi");
+        assert_eq!(editor.buffer().contents.serialize(), "hi");
+    }
+
+    #[test]
+    fn unknown_commands_are_named() {
+        let editor = run_with(vec![], modal(), typed(":frob\n").collect()).0;
+
+        assert_eq!(editor.message.text(), "Not an editor command: frob");
+    }
+
+    /* Someone else touched the file: it was modified an hour ago,
+       rather than whenever the editor read it. */
+    fn touch_behind_the_editors_back(file_path: &path::Path) {
+        let an_hour_ago = time::SystemTime::now() - time::Duration::from_secs(3600);
+        fs::File::options().write(true).open(file_path).unwrap().set_modified(an_hour_ago).unwrap();
+    }
+
+    #[test]
+    fn a_changed_mtime_blocks_w_but_not_w_bang() {
+        let scratch = Scratch::new("changed_mtime");
+        let file_path = scratch.file("notes.txt", "theirs");
+        let (mut editor, _) = run_with(vec![file_path.clone()], modal(), typed("iours \x1b").collect());
+        touch_behind_the_editors_back(&file_path);
+
+        send(&mut editor, Message::Command(Command::Write(None)));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "theirs");
+        assert_eq!(editor.message.text(), "File changed on disk — overwrite? (:w! to force)");
+        assert!(editor.buffer().modified);
+
+        send(&mut editor, Message::Command(Command::ForceWrite));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "ours theirs");
+        assert!(!editor.buffer().modified);
+    }
+
+    #[test]
+    fn after_a_save_the_new_mtime_is_the_one_to_go_by() {
+        let scratch = Scratch::new("saved_mtime");
+        let file_path = scratch.file("notes.txt", "one");
+        let (mut editor, _) = run_with(vec![file_path.clone()], modal(), typed("izero \x1b").collect());
+        touch_behind_the_editors_back(&file_path);
+        send(&mut editor, Message::Command(Command::ForceWrite));
+
+        send(&mut editor, Message::Command(Command::Write(None)));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "zero one");
+        assert_ne!(editor.message.text(), "File changed on disk — overwrite? (:w! to force)");
+    }
+
+    #[test]
+    fn an_untouched_file_saves_with_w() {
+        let scratch = Scratch::new("untouched_mtime");
+        let file_path = scratch.file("notes.txt", "one");
+        run_with(vec![file_path.clone()], modal(), typed("izero \x1b:w\n").collect());
+
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "zero one");
+    }
+
+    #[test]
+    fn only_the_latest_load_is_applied_whatever_order_they_finish_in() {
+        let scratch = Scratch::new("out_of_order");
+        let file_path = scratch.file("notes.txt", "first");
+        let (mut editor, _) = run_with(vec![file_path.clone()], modal(), vec![]);
+
+        fs::write(&file_path, "second").unwrap();
+        let older = editor.buffer_mut().reload(0);
+        fs::write(&file_path, "third").unwrap();
+        let newer = editor.buffer_mut().reload(0);
+        assert!(editor.buffer().loading);
+
+        settle(&mut editor, newer);
+        assert_eq!(editor.buffer().contents.serialize(), "third");
+        assert!(!editor.buffer().loading);
+
+        settle(&mut editor, older);
+        assert_eq!(editor.buffer().contents.serialize(), "third");
+    }
+
+    #[test]
+    fn a_stale_failure_is_not_reported() {
+        let scratch = Scratch::new("stale_failure");
+        let file_path = scratch.file("notes.txt", "first");
+        let (mut editor, _) = run_with(vec![file_path.clone()], modal(), vec![]);
+
+        fs::remove_file(&file_path).unwrap();
+        let elm::Cmd::Suspend(older) = editor.buffer_mut().reload(0) else {
+            panic!("a load should be an effect");
+        };
+        let failed = older().unwrap();
+        fs::write(&file_path, "second").unwrap();
+        let newer = editor.buffer_mut().reload(0);
+
+        settle(&mut editor, newer);
+        send(&mut editor, failed);
+
+        assert_eq!(editor.buffer().contents.serialize(), "second");
+        assert!(!editor.message.text().starts_with("Could not reload"));
+    }
+
+    fn animates(subscription: &elm::Sub<Message>) -> bool {
+        match subscription {
+            elm::Sub::AnimationFrame(_)    => true,
+            elm::Sub::Batch(subscriptions) => subscriptions.iter().any(animates),
+            _otherwise                     => false,
+        }
+    }
+
+    fn on_screen(editor: &Editor) -> String {
+        let mut frame = tui::CommandBuffer::default();
+        editor.view(&mut frame).unwrap();
+        tui::strip_ansi(&frame.take().unwrap())
+    }
+
+    #[test]
+    fn the_spinner_only_turns_while_a_load_is_pending() {
+        let scratch = Scratch::new("spinner");
+        let file_path = scratch.file("notes.txt", "hello");
+        let (mut editor, _) = run_with(vec![file_path], Config::default(), vec![]);
+        assert!(!animates(&editor.subscriptions()));
+        assert!(!on_screen(&editor).contains("notes.txt |"));
+
+        let load = editor.buffer_mut().reload(0);
+        assert!(animates(&editor.subscriptions()));
+        assert!(on_screen(&editor).contains("notes.txt |"));
+
+        send(&mut editor, Message::Frame(1));
+        assert!(on_screen(&editor).contains("notes.txt /"));
+        send(&mut editor, Message::Frame(4));
+        assert!(on_screen(&editor).contains("notes.txt |"));
+
+        settle(&mut editor, load);
+        assert!(!animates(&editor.subscriptions()));
+        assert!(!on_screen(&editor).contains("notes.txt |"));
+    }
+
+    /* The cursor after moves on text shown eighty by ten. */
+    fn navigating(text: &str, moves: &[Direction]) -> NavigationModel {
+        let contents = EditingModel::from_text(text);
+        let mut navigation = NavigationModel {
+            screen_size: ScreenSize { columns: 80, rows: 10 },
+            ..NavigationModel::default()
+        };
+        for direction in moves {
+            navigation.move_intended(direction, &contents);
+        }
+        navigation
+    }
+
+    /* On thirty lines of a hundred columns. */
+    fn pushing(moves: &[Direction]) -> NavigationModel {
+        navigating(&format!("{}\n", "x".repeat(100)).repeat(30), moves)
+    }
+
+    #[test]
+    fn pushing_down_past_the_edge_scrolls_until_the_last_line_is_on_top() {
+        let navigation = pushing(&[Direction::Down; 100]);
+        let further = pushing(&[Direction::Down; 200]);
+
+        assert_eq!(navigation.cursor.row, 9);
+        assert_eq!(navigation.viewport.row_offset, 29);
+        assert_eq!(further.cursor_in_buffer(), navigation.cursor_in_buffer());
+    }
+
+    #[test]
+    fn pushing_right_past_the_edge_scrolls_and_stops_at_the_end_of_the_line() {
+        let navigation = pushing(&[Direction::Right; 300]);
+
+        assert_eq!(navigation.cursor_in_buffer().column, 100);
+        assert_eq!(navigation.cursor.column, 79);
+        assert_eq!(navigation.viewport.column_offset, 21);
+    }
+
+    #[test]
+    fn the_cursor_never_leaves_the_screen() {
+        let moves = [Direction::Down, Direction::Right, Direction::Up, Direction::Left]
+            .map(|direction| vec![direction; 150])
+            .concat();
+        let mut navigation = pushing(&[]);
+        let contents = EditingModel::from_text(&format!("{}\n", "x".repeat(100)).repeat(30));
+        for direction in moves {
+            navigation.move_intended(&direction, &contents);
+            assert!(navigation.cursor.row < 10 && navigation.cursor.column < 80, "{:?}", navigation.cursor);
+        }
+
+        assert_eq!(navigation.cursor_in_buffer(), Position::default());
+    }
+
+    #[test]
+    fn moving_down_stops_at_the_bottom_of_the_screen() {
+        let mut position = Position { row: 7, column: 3 };
+        position.move_down(100, &ScreenSize { columns: 80, rows: 10 });
+        assert_eq!(position, Position { row: 9, column: 3 });
+
+        position.move_down(1, &ScreenSize { columns: 0, rows: 0 });
+        assert_eq!(position.row, 0);
+    }
+
+    #[test]
+    fn end_goes_to_the_end_of_a_short_line() {
+        let navigation = navigating("short\nmuch longer line", &[Direction::LineEnd]);
+
+        assert_eq!(navigation.cursor_in_buffer(), Position { row: 0, column: 5 });
+        assert_eq!(navigation.viewport.column_offset, 0);
+    }
+
+    #[test]
+    fn end_scrolls_to_the_end_of_a_long_line() {
+        let line = "y".repeat(200);
+        let navigation = navigating(&line, &[Direction::LineEnd]);
+
+        assert_eq!(navigation.cursor_in_buffer(), Position { row: 0, column: 200 });
+        assert_eq!(navigation.cursor.column, 79);
+        assert_eq!(navigation.viewport.column_offset, 121);
+    }
+
+    #[test]
+    fn home_goes_back_to_the_start_and_scrolls_back() {
+        let line = "y".repeat(200);
+        let navigation = navigating(&line, &[Direction::LineEnd, Direction::LineStart]);
+
+        assert_eq!(navigation.cursor_in_buffer(), Position::default());
+        assert_eq!(navigation.viewport.column_offset, 0);
+    }
+
+    #[test]
+    fn end_counts_chars_and_tabs_not_bytes() {
+        let navigation = navigating("日本語\tcafé", &[Direction::LineEnd]);
+
+        assert_eq!(navigation.cursor_in_buffer(), Position { row: 0, column: 8 });
+    }
+
+    #[test]
+    fn home_and_end_keys_move_along_the_line() {
+        let editor = run(typed("hello").chain([key(KeyCode::Home)]).chain(typed(">")).collect());
+        assert_eq!(editor.buffer().contents.serialize(), ">hello");
+
+        let script = typed("hello").chain([key(KeyCode::Home), key(KeyCode::End)]).chain(typed("!")).collect();
+        let editor = run(script);
+        assert_eq!(editor.buffer().contents.serialize(), "hello!");
+    }
+
+    /* Every stop from the top, following step until it stays put. */
+    fn word_stops(contents: &EditingModel, step: fn(&EditingModel, usize, usize) -> Position) -> Vec<Position> {
+        let mut stops = vec![];
+        let mut here = Position::default();
+        loop {
+            let next = step(contents, here.row, here.column);
+            if next == here {
+                return stops;
+            }
+            stops.push(next.clone());
+            here = next;
+        }
+    }
+
+    #[test]
+    fn words_forward_stop_at_words_and_punctuation() {
+        let contents = EditingModel::from_text("  foo.bar  baz");
+        let columns: Vec<_> = word_stops(&contents, EditingModel::next_word_boundary)
+            .iter()
+            .map(|stop| stop.column)
+            .collect();
+
+        assert_eq!(columns, [2, 5, 6, 11, 14]);
+    }
+
+    #[test]
+    fn words_backward_stop_at_words_and_punctuation() {
+        let contents = EditingModel::from_text("  foo.bar  baz");
+        let columns: Vec<_> = [14, 11, 6, 5, 2]
+            .iter()
+            .map(|&column| contents.prev_word_boundary(0, column).column)
+            .collect();
+
+        assert_eq!(columns, [11, 6, 5, 2, 0]);
+    }
+
+    #[test]
+    fn words_wrap_to_the_next_and_previous_lines() {
+        let contents = EditingModel::from_text("one\n  two");
+
+        assert_eq!(contents.next_word_boundary(0, 3), Position { row: 1, column: 2 });
+        assert_eq!(contents.prev_word_boundary(1, 2), Position { row: 0, column: 3 });
+        assert_eq!(contents.prev_word_boundary(0, 0), Position::default());
+        assert_eq!(contents.next_word_boundary(1, 5), Position { row: 1, column: 5 });
+    }
+
+    #[test]
+    fn ctrl_arrows_move_by_words() {
+        let ctrl_arrow = |code| event::Event::Key(KeyEvent::new(code, KeyModifiers::CONTROL));
+        let script = typed("  foo.bar  baz")
+            .chain([ctrl_arrow(KeyCode::Left), ctrl_arrow(KeyCode::Left)])
+            .chain(typed("|"))
+            .collect();
+
+        assert_eq!(run(script).buffer().contents.serialize(), "  foo.|bar  baz");
+    }
+
+    /* Where going to row of a thousand lines on ten rows leaves the
+       top of the screen and the cursor in the buffer. */
+    fn going_to(row: usize) -> (usize, Position) {
+        let contents = EditingModel::from_text(&"line\n".repeat(1000));
+        let mut navigation = NavigationModel {
+            screen_size: ScreenSize { columns: 80, rows: 10 },
+            ..NavigationModel::default()
+        };
+        navigation.go_to_line(row, &contents);
+        (navigation.viewport.row_offset, navigation.cursor_in_buffer())
+    }
+
+    #[test]
+    fn going_to_a_line_near_the_top_keeps_the_screen_still() {
+        assert_eq!(going_to(2), (0, Position { row: 2, column: 0 }));
+    }
+
+    #[test]
+    fn going_to_a_line_in_the_middle_centers_it() {
+        assert_eq!(going_to(500), (495, Position { row: 500, column: 0 }));
+    }
+
+    #[test]
+    fn going_to_a_line_near_the_bottom_keeps_the_screen_full() {
+        assert_eq!(going_to(997), (990, Position { row: 997, column: 0 }));
+    }
+
+    #[test]
+    fn going_past_the_end_goes_to_the_last_line() {
+        assert_eq!(going_to(5000), (990, Position { row: 999, column: 0 }));
+    }
+
+    #[test]
+    fn the_prompt_counts_lines_from_one() {
+        let text = "line\n".repeat(20);
+        let script = typed(&text).chain([ctrl('g')]).chain(typed("12\n")).collect();
+        let editor = run(script);
+
+        assert_eq!(editor.buffer().cursor(), Position { row: 11, column: 0 });
+    }
+
+    #[test]
+    fn replacing_all_takes_overlapping_candidates_left_to_right() {
+        let mut contents = EditingModel::from_text("aaaa\naaa");
+
+        assert_eq!(contents.replace_all("aa", "b"), 3);
+        assert_eq!(contents.serialize(), "bb\nba");
+    }
+
+    #[test]
+    fn replacing_all_with_a_different_length_keeps_lines_whole() {
+        let mut contents = EditingModel::from_text("foo bar foo\nbar\nfoo");
+
+        assert_eq!(contents.replace_all("foo", "quux"), 3);
+        assert_eq!(contents.serialize(), "quux bar quux\nbar\nquux");
+        assert_eq!(contents.replace_all("quux", ""), 3);
+        assert_eq!(contents.serialize(), " bar \nbar\n");
+        assert_eq!(contents.replace_all("", "x"), 0);
+    }
+
+    #[test]
+    fn replacing_next_starts_at_the_position_and_wraps() {
+        let mut contents = EditingModel::from_text("aaaa");
+        let after = contents.replace_next("aa", "b", &Position { row: 0, column: 1 });
+
+        assert_eq!(contents.serialize(), "aab");
+        assert_eq!(after, Some(Position { row: 0, column: 3 }));
+
+        let after = contents.replace_next("aa", "b", &Position { row: 0, column: 2 });
+        assert_eq!(contents.serialize(), "bb");
+        assert_eq!(after, Some(Position { row: 0, column: 1 }));
+        assert_eq!(contents.replace_next("aa", "b", &Position::default()), None);
+    }
+
+    #[test]
+    fn replacing_next_counts_columns_in_chars() {
+        let mut contents = EditingModel::from_text("café café");
+        let after = contents.replace_next("é", "ée", &Position { row: 0, column: 4 });
+
+        assert_eq!(contents.serialize(), "café cafée");
+        assert_eq!(after, Some(Position { row: 0, column: 10 }));
+    }
+
+    #[test]
+    fn replacing_all_keeps_the_cursor_on_the_line() {
+        let mut editor = run(typed("aaaa").collect());
+        send(&mut editor, Message::Replace { from: "aa".to_owned(), to: "b".to_owned(), all: true });
+
+        assert_eq!(editor.buffer().contents.serialize(), "bb");
+        assert_eq!(editor.buffer().cursor(), Position { row: 0, column: 2 });
+        assert_eq!(editor.message.text(), "Replaced 2 occurrence(s)");
+    }
+
+    #[test]
+    fn replacing_next_puts_the_cursor_after_the_replacement() {
+        let script = typed("one two").chain([key(KeyCode::Home)]).collect();
+        let mut editor = run(script);
+        send(&mut editor, Message::Replace { from: "two".to_owned(), to: "three".to_owned(), all: false });
+
+        assert_eq!(editor.buffer().contents.serialize(), "one three");
+        assert_eq!(editor.buffer().cursor(), Position { row: 0, column: 9 });
+        assert_eq!(editor.message.text(), "Replaced 1 occurrence");
+
+        send(&mut editor, Message::Replace { from: "four".to_owned(), to: "five".to_owned(), all: false });
+        assert_eq!(editor.message.text(), "Pattern not found: four");
+    }
+
+    #[test]
+    fn the_gutter_widens_when_the_file_reaches_a_hundred_lines() {
+        let scratch = Scratch::new("gutter_width");
+        let file_path = scratch.file("ninety_nine.txt", "line\n".repeat(99));
+        let config = Config { show_line_numbers: true, ..Config::default() };
+        let (mut editor, _) = run_with(vec![file_path], config, vec![]);
+        assert_eq!(editor.gutter_width(editor.buffer()), 3);
+        assert!(on_screen(&editor).contains(" 1 line"));
+
+        send(&mut editor, Message::GotoLine(98));
+        send(&mut editor, key(KeyCode::End).into());
+        send(&mut editor, key(KeyCode::Enter).into());
+        assert_eq!(editor.buffer().contents.line_count(), 100);
+        assert_eq!(editor.gutter_width(editor.buffer()), 4);
+
+        send(&mut editor, key(KeyCode::Backspace).into());
+        assert_eq!(editor.gutter_width(editor.buffer()), 3);
+    }
+
+    #[test]
+    fn the_gutter_is_blank_past_the_end_and_gone_when_turned_off() {
+        let scratch = Scratch::new("gutter_off");
+        let file_path = scratch.file("two.txt", "one\ntwo\n");
+        let config = Config { show_line_numbers: true, ..Config::default() };
+        let (mut editor, _) = run_with(vec![file_path], config, vec![]);
+        let screen = on_screen(&editor);
+        assert!(screen.contains("1 one"));
+        assert!(screen.contains("2 two"));
+        assert!(!screen.contains('3'));
+
+        editor.show_line_numbers = false;
+        assert_eq!(editor.gutter_width(editor.buffer()), 0);
+        assert!(!on_screen(&editor).contains("1 one"));
+    }
+
+    #[test]
+    fn an_edit_marks_the_buffer_modified_and_a_save_clears_it() {
+        let scratch = Scratch::new("modified_flag");
+        let file_path = scratch.file("notes.txt", "one");
+        let (mut editor, _) = run_with(vec![file_path.clone()], Config::default(), vec![]);
+        assert!(!editor.buffer().modified);
+        assert!(!on_screen(&editor).contains("[+]"));
+
+        send(&mut editor, key(KeyCode::Char('x')).into());
+        assert!(editor.buffer().modified);
+        assert!(on_screen(&editor).contains("notes.txt [+]"));
+
+        send(&mut editor, ctrl('s').into());
+        assert!(!editor.buffer().modified);
+        assert!(!on_screen(&editor).contains("[+]"));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "xone");
+    }
+
+    #[test]
+    fn a_failed_save_leaves_the_buffer_modified() {
+        let mut editor = run(typed("x").collect());
+        assert!(editor.buffer().modified);
+
+        let outcome = elm::Resource::Failed("disk full".to_owned());
+        send(&mut editor, Message::Saved { index: 0, stripped: 0, save_as: None, outcome });
+        assert!(editor.buffer().modified);
+        assert_eq!(editor.message.text(), "Save failed: disk full");
+    }
+
+    #[test]
+    fn ticks_keep_the_session_clock_going() {
+        let mut editor = run(vec![]);
+        assert!(on_screen(&editor).contains("00:00"));
+
+        let later = editor.started + time::Duration::from_secs(65);
+        send(&mut editor, Message::Tick(later));
+        assert!(on_screen(&editor).contains("01:05"));
+    }
+
+    #[test]
+    fn timed_out_polls_do_not_end_the_editor() {
+        let host = headless::ScriptedHost::new(iter::once(event::Event::Resize(80, 10)).chain(typed("hi")))
+            .unwrap()
+            .with_clock_stopped();
+        for _ in 0..3 {
+            host.fail_with(io::ErrorKind::TimedOut);
+        }
+        host.fail_with(io::ErrorKind::Interrupted);
+
+        let open_file = OpenFile { file_paths: vec![], read_only: false };
+        let editor = host.run_for::<Editor>((open_file, Config::default()), 100).unwrap();
+        assert!(host.is_exhausted());
+        assert_eq!(editor.buffer().contents.serialize(), "hi");
+    }
+
+    fn mouse(kind: event::MouseEventKind, column: u16, row: u16) -> event::Event {
+        event::Event::Mouse(event::MouseEvent { kind, column, row, modifiers: KeyModifiers::NONE })
+    }
+
+    /* Thirty numbered lines, the first ten a hundred long and the
+       rest two, seen from row 5 and column 10 of an 80x10 screen. */
+    fn scrolled_navigation() -> (NavigationModel, EditingModel) {
+        let text = (0..30)
+            .map(|row| if row < 10 { format!("{:02}{}", row, "x".repeat(98)) } else { format!("{:02}", row) })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut navigation = NavigationModel {
+            screen_size: ScreenSize { columns: 80, rows: 10 },
+            ..NavigationModel::default()
+        };
+        navigation.viewport.row_offset = 5;
+        navigation.viewport.column_offset = 10;
+        (navigation, EditingModel::from_text(&text))
+    }
+
+    #[test]
+    fn a_click_adds_the_viewport_offsets() {
+        let (navigation, contents) = scrolled_navigation();
+
+        assert_eq!(navigation.position_at(0, 0, &contents), pos(5, 10));
+        assert_eq!(navigation.position_at(3, 2, &contents), pos(7, 13));
+    }
+
+    #[test]
+    fn a_click_past_the_end_of_a_line_snaps_to_its_end() {
+        let (navigation, contents) = scrolled_navigation();
+
+        assert_eq!(navigation.position_at(79, 4, &contents), pos(9, 89));
+        assert_eq!(navigation.position_at(3, 6, &contents), pos(11, 2));
+        assert_eq!(navigation.position_at(79, 6, &contents), pos(11, 2));
+    }
+
+    #[test]
+    fn a_click_below_the_last_line_snaps_to_the_last_line() {
+        let (mut navigation, contents) = scrolled_navigation();
+        navigation.viewport.row_offset = 25;
+        navigation.viewport.column_offset = 0;
+
+        assert_eq!(navigation.position_at(1, 9, &contents), pos(29, 1));
+        assert_eq!(navigation.position_at(50, 9, &contents), pos(29, 2));
+    }
+
+    #[test]
+    fn clicking_moves_the_cursor_past_the_gutter() {
+        let scratch = Scratch::new("click");
+        let file_path = scratch.file("notes.txt", "one\ntwo\nthree\n");
+        let config = Config { show_line_numbers: true, ..Config::default() };
+        let script = vec![mouse(event::MouseEventKind::Down(event::MouseButton::Left), 4, 2)];
+        let (editor, _) = run_with(vec![file_path], config, script);
+
+        assert_eq!(editor.buffer().cursor(), pos(2, 2));
+
+        let script = vec![mouse(event::MouseEventKind::Down(event::MouseButton::Left), 40, 8)];
+        let (editor, _) = run_with(vec![scratch.file("again.txt", "one\ntwo\nthree\n")], Config::default(), script);
+        assert_eq!(editor.buffer().cursor(), pos(2, 5));
+    }
+
+    /* The viewport of thirty lines after the wheel turned each way
+       in turn, a notch at a time. */
+    fn wheeled(test: &str, config: Config, turns: &[(event::MouseEventKind, usize)]) -> Editor {
+        let scratch = Scratch::new(test);
+        let file_path = scratch.file("thirty.txt", "line\n".repeat(30));
+        let script = turns.iter()
+            .flat_map(|&(kind, notches)| iter::repeat_n(mouse(kind, 0, 0), notches))
+            .collect();
+        run_with(vec![file_path], config, script).0
+    }
+
+    #[test]
+    fn scrolling_up_never_goes_above_the_top() {
+        use event::MouseEventKind::{ScrollDown, ScrollUp};
+        for notches in [1, 3, 50] {
+            let editor = wheeled(&format!("wheel_up_{}", notches), Config::default(), &[(ScrollDown, 2), (ScrollUp, notches)]);
+            let expected = 6_usize.saturating_sub(3 * notches);
+            assert_eq!(editor.buffer().navigation.viewport.row_offset, expected);
+        }
+
+        let editor = wheeled("wheel_up_at_top", Config::default(), &[(ScrollUp, 10)]);
+        assert_eq!(editor.buffer().navigation.viewport.row_offset, 0);
+    }
+
+    #[test]
+    fn scrolling_down_stops_at_the_last_line() {
+        let editor = wheeled("wheel_down_to_end", Config::default(), &[(event::MouseEventKind::ScrollDown, 100)]);
+        let navigation = &editor.buffer().navigation;
+
+        assert_eq!(navigation.viewport.row_offset, 29);
+        assert_eq!(editor.buffer().cursor().row, 29);
+    }
+
+    #[test]
+    fn the_wheel_drags_the_cursor_along_to_keep_it_on_screen() {
+        let editor = wheeled("wheel_drags_cursor", Config::default(), &[(event::MouseEventKind::ScrollDown, 4)]);
+        assert_eq!(editor.buffer().navigation.viewport.row_offset, 12);
+        assert_eq!(editor.buffer().cursor().row, 12);
+    }
+
+    #[test]
+    fn the_wheel_scrolls_as_far_as_configured() {
+        let config = Config { scroll_step: 5, ..Config::default() };
+        let editor = wheeled("wheel_step", config, &[(event::MouseEventKind::ScrollDown, 2)]);
+        assert_eq!(editor.buffer().navigation.viewport.row_offset, 10);
+    }
+
+    #[test]
+    fn scrolling_down_the_viewport_stops_at_the_limit() {
+        let mut viewport = EditingViewport::default();
+        for _ in 0..10 {
+            viewport.scroll_down(7, 20);
+            assert!(viewport.row_offset <= 20);
+        }
+        assert_eq!(viewport.row_offset, 20);
+
+        viewport.row_offset = 30;
+        viewport.scroll_down(1, 20);
+        assert_eq!(viewport.row_offset, 30);
+    }
+
+    #[test]
+    fn no_moves_take_the_viewport_past_the_last_line() {
+        use Direction::*;
+        let directions = [Down, PageDown, Right, LineEnd, Up, PageUp, Left, LineStart, PageDown, PageDown, Down];
+        let mut seed = 11_usize;
+
+        for lines in [0, 1, 2, 9, 10, 11, 57] {
+            let text = (0..lines).map(|row| "y".repeat(row * 7)).collect::<Vec<_>>().join("\n");
+            let contents = EditingModel::from_text(&text);
+            let mut navigation = NavigationModel {
+                screen_size: ScreenSize { columns: 80, rows: 10 },
+                ..NavigationModel::default()
+            };
+
+            for _ in 0..500 {
+                seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+                navigation.move_intended(&directions[(seed >> 33) % directions.len()], &contents);
+
+                let viewport = &navigation.viewport;
+                assert!(viewport.row_offset <= contents.line_count().saturating_sub(1), "{} lines", lines);
+                let row = navigation.cursor_in_buffer().row;
+                assert!(viewport.column_offset <= navigation.line_width(row, &contents), "{} lines", lines);
+            }
+        }
+    }
+
+    #[test]
+    fn tabs_reach_to_the_next_stop() {
+        let line = "\tfoo\tbar";
+
+        assert_eq!(expand_tabs(line, 4), "    foo bar");
+        assert_eq!(expand_tabs(line, 8), "        foo     bar");
+        assert_eq!((0..=8).map(|column| display_column(line, column, 4)).collect::<Vec<_>>(), [0, 4, 5, 6, 7, 8, 9, 10, 11]);
+        assert_eq!((0..=8).map(|column| display_column(line, column, 8)).collect::<Vec<_>>(), [0, 8, 9, 10, 11, 16, 17, 18, 19]);
+    }
+
+    #[test]
+    fn screen_columns_inside_a_tab_belong_to_it() {
+        let line = "\tfoo\tbar";
+
+        assert_eq!((0..12).map(|column| char_column(line, column, 4)).collect::<Vec<_>>(), [0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(char_column(line, 7, 8), 0);
+        assert_eq!(char_column(line, 15, 8), 4);
+        assert_eq!(char_column(line, 16, 8), 5);
+    }
+
+    #[test]
+    fn clipping_expands_tabs_but_the_line_keeps_them() {
+        let contents = EditingModel::from_text("\tfoo\tbar");
+        for (tab_width, expected) in [(4, "  foo "), (8, "      ")] {
+            let viewport = EditingViewport { column_offset: 2, tab_width, ..EditingViewport::default() };
+            assert_eq!(viewport.select_and_clip(0, 6, &contents).text, expected);
+        }
+        assert_eq!(contents.line(0), "\tfoo\tbar");
+    }
+
+    #[test]
+    fn the_cursor_steps_over_a_tab_in_one_go() {
+        for (tab_width, stops) in [(4, [4, 5, 6, 7, 8]), (8, [8, 9, 10, 11, 16])] {
+            let contents = EditingModel::from_text("\tfoo\tbar");
+            let mut navigation = NavigationModel {
+                screen_size: ScreenSize { columns: 80, rows: 10 },
+                ..NavigationModel::default()
+            };
+            navigation.viewport.tab_width = tab_width;
+
+            let columns: Vec<_> = (0..5)
+                .map(|_| {
+                    navigation.move_intended(&Direction::Right, &contents);
+                    navigation.cursor.column
+                })
+                .collect();
+            assert_eq!(columns, stops);
+        }
+    }
+
+    #[test]
+    fn tabs_show_as_wide_as_configured() {
+        let scratch = Scratch::new("tabs_on_screen");
+        let file_path = scratch.file("tabs.txt", "\tfoo\tbar\n");
+        let config = Config { tab_width: 8, ..Config::default() };
+        let (editor, _) = run_with(vec![file_path], config, vec![]);
+
+        assert!(on_screen(&editor).contains("        foo     bar"));
+    }
+
+    #[test]
+    fn crlf_text_comes_back_byte_for_byte() {
+        for text in ["one\r\ntwo\r\n", "one\r\ntwo", "\r\n", "one\r\n\r\nthree\r\n"] {
+            let contents = EditingModel::from_text(text);
+
+            assert_eq!(contents.line_ending, LineEnding::CrLf, "{:?}", text);
+            assert!(contents.iter_lines().all(|line| !line.contains('\r')), "{:?}", text);
+            assert_eq!(contents.serialize(), text);
+        }
+    }
+
+    #[test]
+    fn the_line_ending_most_lines_use_wins() {
+        assert_eq!(LineEnding::detect("a\r\nb\r\nc\n"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect("a\nb\nc\r\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\r\nb\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("no breaks"), LineEnding::Lf);
+    }
+
+    #[test]
+    fn a_crlf_file_is_saved_with_crlf_even_after_edits() {
+        let scratch = Scratch::new("crlf_round_trip");
+        let original = b"one\r\ntwo\r\n";
+        let file_path = scratch.file("windows.txt", original);
+        let (mut editor, _) = run_with(vec![file_path.clone()], Config::default(), vec![]);
+
+        send(&mut editor, ctrl('s').into());
+        assert_eq!(fs::read(&file_path).unwrap(), original);
+
+        for event in [key(KeyCode::End), key(KeyCode::Enter), key(KeyCode::Char('!')), ctrl('s')] {
+            send(&mut editor, event.into());
+        }
+        assert_eq!(fs::read(&file_path).unwrap(), b"one\r\n!\r\ntwo\r\n");
+    }
+
+    /* What a terminal of columns by rows would show after output:
+       crossterm only ever moves the cursor with MoveTo and wipes the
+       screen with Clear(All), so that is all this plays back. */
+    fn grid(output: &[u8], columns: usize, rows: usize) -> Vec<String> {
+        let text = String::from_utf8_lossy(output);
+        let mut cells = vec![vec![' '; columns]; rows];
+        let (mut column, mut row) = (0, 0);
+        let mut chars = text.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '\x1b' {
+                if let Some(cell) = cells.get_mut(row).and_then(|line| line.get_mut(column)) {
+                    *cell = c;
+                }
+                column += 1;
+                continue;
+            }
+            if chars.next() != Some('[') {
+                continue;
+            }
+            let mut parameters = String::new();
+            for c in chars.by_ref() {
+                match c {
+                    'H' => {
+                        let mut at = parameters.split(';').map(|n| n.parse::<usize>().unwrap_or(1));
+                        row = at.next().unwrap_or(1) - 1;
+                        column = at.next().unwrap_or(1) - 1;
+                    }
+                    'J' if parameters == "2" => cells = vec![vec![' '; columns]; rows],
+                    c if ('@'..='~').contains(&c) => (),
+                    c => {
+                        parameters.push(c);
+                        continue;
+                    }
+                }
+                break;
+            }
+        }
+
+        cells.into_iter().map(|line| line.into_iter().collect()).collect()
+    }
+
+    #[test]
+    fn the_editor_renders_as_expected_on_a_small_screen() {
+        let host = headless::ScriptedHost::new(iter::once(event::Event::Resize(40, 5)).chain(typed("hello\nworld")))
+            .unwrap()
+            .with_clock_stopped();
+        let open_file = OpenFile { file_paths: vec![], read_only: false };
+        host.run_for::<Editor>((open_file, Config::default()), 100).unwrap();
+
+        assert_eq!(grid(&host.output(), 40, 5), [
+            "1 hello                                 ",
+            "2 world                                 ",
+            "  ~                                     ",
+            "  ~                                     ",
+            " Unnamed [+]      []  00:00  UTF-8  2:6 ",
+        ]);
+    }
+
+    #[test]
+    fn a_remapped_quit_key_quits_and_the_old_one_does_not() {
+        let keymap = Keymap::empty().bind(KeyCode::Char('c'), KeyModifiers::CONTROL, keymap::Action::Quit);
+        let config = Config { keymap, show_key_history: true, ..Config::default() };
+        let (editor, _) = run_with(vec![], config, vec![ctrl('q'), key(KeyCode::Char('a'))]);
+        assert_eq!(editor.buffer().contents.serialize(), "a");
+        assert!(editor.key_history.to_string().contains("Char('q')"));
+
+        let keymap = Keymap::empty().bind(KeyCode::Char('c'), KeyModifiers::CONTROL, keymap::Action::Quit);
+        let config = Config { keymap, ..Config::default() };
+        let (editor, _) = run_with(vec![], config, vec![ctrl('c'), key(KeyCode::Char('a'))]);
+        assert_eq!(editor.buffer().contents.serialize(), "");
+    }
+
+    /* An editor on notes.txt whose first load has been asked for but
+       not yet come back, and the serial that load will answer to. */
+    fn loading(test: &str) -> (Scratch, Editor, usize) {
+        let scratch = Scratch::new(test);
+        let file_path = scratch.file("notes.txt", "on disk");
+        let (mut editor, _) = run_with(vec![file_path], Config::default(), vec![]);
+        let _pending = editor.buffer_mut().load(0);
+        let serial = editor.buffer().loads;
+        (scratch, editor, serial)
+    }
+
+    #[test]
+    fn a_pending_load_shows_a_placeholder() {
+        let (_scratch, editor, _) = loading("load_placeholder");
+
+        assert!(editor.buffer().loading);
+        assert!(on_screen(&editor).contains("Loading…"));
+    }
+
+    #[test]
+    fn a_load_that_comes_back_fills_the_buffer() {
+        let (_scratch, mut editor, serial) = loading("load_present");
+        let outcome = elm::Resource::Present(Handover::new(EditingModel::from_text("loaded\ntext")));
+        send(&mut editor, Message::FileLoaded { index: 0, serial, reload: false, outcome });
+
+        assert!(!editor.buffer().loading);
+        assert_eq!(editor.buffer().contents.serialize(), "loaded\ntext");
+        assert_eq!(editor.buffer().cursor(), Position::default());
+        assert!(!on_screen(&editor).contains("Loading…"));
+    }
+
+    #[test]
+    fn a_load_that_failed_says_so_instead_of_panicking() {
+        let (_scratch, mut editor, serial) = loading("load_failed");
+        let outcome = elm::Resource::Failed("Permission denied".to_owned());
+        send(&mut editor, Message::FileLoaded { index: 0, serial, reload: false, outcome });
+
+        assert!(!editor.buffer().loading);
+        assert_eq!(editor.message.text(), "Could not open notes.txt: Permission denied");
+        assert!(editor.buffer().file_path.is_none());
+        assert!(on_screen(&editor).contains("Could not open notes.txt"));
+    }
+
+    /* An editor on one.txt and two.txt, holding "one" and "two". */
+    fn two_buffers(test: &str, script: Vec<event::Event>) -> (Scratch, Editor) {
+        let scratch = Scratch::new(test);
+        let files = vec![scratch.file("one.txt", "one"), scratch.file("two.txt", "two")];
+        let (editor, _) = run_with(files, Config::default(), script);
+        (scratch, editor)
+    }
+
+    #[test]
+    fn edits_in_one_buffer_leave_the_other_alone() {
+        let (_scratch, mut editor) = two_buffers("buffers_apart", typed("1 ").collect());
+        send(&mut editor, Message::SwitchBuffer(1));
+        assert_eq!(editor.buffer().contents.serialize(), "two");
+        assert!(!editor.buffer().modified);
+
+        send(&mut editor, key(KeyCode::Char('2')).into());
+        send(&mut editor, Message::SwitchBuffer(0));
+        assert_eq!(editor.buffer().contents.serialize(), "1 one");
+        assert_eq!(editor.buffers[1].contents.serialize(), "2two");
+        assert!(editor.buffers.iter().all(|buffer| buffer.modified));
+    }
+
+    #[test]
+    fn ctrl_n_and_ctrl_p_go_round_the_buffers() {
+        let (_scratch, mut editor) = two_buffers("buffers_round", vec![]);
+        assert!(on_screen(&editor).contains("one.txt [1/2]"));
+
+        let mut visited = vec![];
+        for event in [ctrl('n'), ctrl('n'), ctrl('p'), ctrl('p')] {
+            send(&mut editor, event.into());
+            visited.push(editor.active);
+        }
+        assert_eq!(visited, [1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn switching_to_a_buffer_that_is_not_there_does_nothing() {
+        let (_scratch, mut editor) = two_buffers("buffers_missing", vec![]);
+        send(&mut editor, Message::SwitchBuffer(2));
+
+        assert_eq!(editor.active, 0);
+    }
+
+    /* Where output last put the cursor, as (column, row). */
+    fn cursor_after(output: &[u8]) -> (usize, usize) {
+        let text = String::from_utf8_lossy(output);
+        text.split("\x1b[")
+            .filter_map(|sequence| {
+                let (row, column) = sequence.split_once('H')?.0.split_once(';')?;
+                Some((column.parse::<usize>().ok()? - 1, row.parse::<usize>().ok()? - 1))
+            })
+            .last()
+            .expect("the cursor was put somewhere")
+    }
+
+    /* The screen with two files side by side on it, columns wide,
+       after script, and where the cursor was left. */
+    fn split_screen(test: &str, columns: u16, script: Vec<event::Event>) -> (Vec<String>, (usize, usize)) {
+        let scratch = Scratch::new(test);
+        let files = vec![scratch.file("one.txt", "one"), scratch.file("two.txt", "two")];
+        let script = [event::Event::Resize(columns, 5), ctrl('w')].into_iter().chain(script);
+        let host = headless::ScriptedHost::new(script).unwrap().with_clock_stopped();
+        let open_file = OpenFile { file_paths: files, read_only: false };
+        host.run_for::<Editor>((open_file, Config::default()), 100).unwrap();
+
+        let output = host.output();
+        (grid(&output, columns as usize, 5), cursor_after(&output))
+    }
+
+    #[test]
+    fn the_divider_splits_an_even_screen_with_the_extra_column_left() {
+        let (screen, _) = split_screen("divider_even", 20, vec![]);
+
+        for row in &screen[..4] {
+            assert_eq!(row.chars().position(|c| c == '│'), Some(10), "{:?}", row);
+        }
+        assert!(screen[0].starts_with("1 one     │1 two"));
+    }
+
+    #[test]
+    fn the_divider_splits_an_odd_screen_down_the_middle() {
+        let (screen, _) = split_screen("divider_odd", 21, vec![]);
+
+        for row in &screen[..4] {
+            assert_eq!(row.chars().position(|c| c == '│'), Some(10), "{:?}", row);
+        }
+        assert_eq!(screen[0].chars().count(), 21);
+    }
+
+    #[test]
+    fn the_cursor_shows_only_in_the_focused_pane() {
+        let (_, cursor) = split_screen("divider_cursor_left", 21, vec![key(KeyCode::End)]);
+        assert_eq!(cursor, (5, 0));
+
+        let (_, cursor) = split_screen("divider_cursor_right", 21, vec![ctrl('w'), key(KeyCode::End)]);
+        assert_eq!(cursor, (16, 0));
+    }
+
+    /* Fifty lines in each of two buffers, the cursor down in the
+       first one far enough to have scrolled. */
+    fn scrolled_buffers(test: &str) -> (Scratch, Editor) {
+        let scratch = Scratch::new(test);
+        let text = (0..50).map(|row| format!("line {}\n", row)).collect::<String>();
+        let files = vec![scratch.file("one.txt", &text), scratch.file("two.txt", &text)];
+        let script = iter::repeat_n(key(KeyCode::Down), 30).chain(iter::repeat_n(key(KeyCode::Right), 3)).collect();
+        let (editor, _) = run_with(files, Config::default(), script);
+        (scratch, editor)
+    }
+
+    #[test]
+    fn switching_away_and_back_keeps_the_cursor_and_scroll() {
+        let (_scratch, mut editor) = scrolled_buffers("restore_cursor");
+        let cursor = editor.buffer().cursor();
+        let row_offset = editor.buffer().navigation.viewport.row_offset;
+        assert_eq!(cursor, pos(30, 3));
+        assert!(row_offset > 0);
+
+        send(&mut editor, Message::SwitchBuffer(1));
+        assert_eq!(editor.buffer().cursor(), Position::default());
+        send(&mut editor, key(KeyCode::PageDown).into());
+
+        send(&mut editor, Message::SwitchBuffer(0));
+        assert_eq!(editor.buffer().cursor(), cursor);
+        assert_eq!(editor.buffer().navigation.viewport.row_offset, row_offset);
+        assert_ne!(editor.buffers[1].cursor(), Position::default());
+    }
+
+    #[test]
+    fn a_resize_while_away_reaches_every_buffer() {
+        let (_scratch, mut editor) = scrolled_buffers("restore_resize");
+        send(&mut editor, Message::SwitchBuffer(1));
+        send(&mut editor, event::Event::Resize(40, 6).into());
+        send(&mut editor, Message::SwitchBuffer(0));
+
+        let navigation = &editor.buffer().navigation;
+        assert_eq!(navigation.screen_size, editor.buffers[1].navigation.screen_size);
+        assert_eq!(navigation.screen_size.rows, 5);
+        assert_eq!(editor.buffer().cursor(), pos(30, 3));
+        assert!(navigation.cursor.row < 5);
+    }
+
+    /* notes.txt, opened read-only or not, after script. */
+    fn edited(test: &str, read_only: bool, script: Vec<event::Event>) -> Editor {
+        let scratch = Scratch::new(test);
+        let file_path = scratch.file("notes.txt", "one\ntwo");
+        let host = headless::ScriptedHost::new(iter::once(event::Event::Resize(80, 10)).chain(script))
+            .unwrap()
+            .with_clock_stopped();
+        let open_file = OpenFile { file_paths: vec![file_path], read_only };
+        host.run_for::<Editor>((open_file, Config::default()), 100).unwrap()
+    }
+
+    #[test]
+    fn typing_and_erasing_do_nothing_to_a_read_only_buffer() {
+        let editing = [
+            vec![key(KeyCode::Char('x'))],
+            vec![key(KeyCode::Down), key(KeyCode::Backspace)],
+            vec![key(KeyCode::Delete)],
+            vec![key(KeyCode::Enter)],
+        ];
+        for (index, script) in editing.into_iter().enumerate() {
+            let editor = edited(&format!("read_only_{}", index), true, script);
+
+            assert_eq!(editor.buffer().contents.serialize(), "one\ntwo");
+            assert!(!editor.buffer().modified);
+            assert_eq!(editor.message.text(), "Buffer is read-only");
+        }
+    }
+
+    #[test]
+    fn typing_and_erasing_work_as_usual_otherwise() {
+        let editor = edited("writable_insert", false, vec![key(KeyCode::Char('x'))]);
+        assert_eq!(editor.buffer().contents.serialize(), "xone\ntwo");
+
+        let editor = edited("writable_erase", false, vec![key(KeyCode::Down), key(KeyCode::Backspace)]);
+        assert_eq!(editor.buffer().contents.serialize(), "onetwo");
+    }
+
+    #[test]
+    fn ctrl_r_toggles_read_only() {
+        let editor = edited("toggle_read_only", false, vec![ctrl('r'), key(KeyCode::Char('x'))]);
+        assert!(editor.buffer().read_only);
+        assert_eq!(editor.buffer().contents.serialize(), "one\ntwo");
+
+        let editor = edited("toggle_read_only_off", true, vec![ctrl('r'), key(KeyCode::Char('x'))]);
+        assert!(!editor.buffer().read_only);
+        assert_eq!(editor.buffer().contents.serialize(), "xone\ntwo");
+    }
+
+    #[test]
+    fn the_readonly_flag_is_picked_out_of_the_file_names() {
+        let args = ["rusty_spoon", "--readonly", "notes.txt"].map(String::from);
+        let open_file = OpenFile::from_args(args.into_iter());
+
+        assert!(open_file.read_only);
+        assert_eq!(open_file.file_paths, [path::PathBuf::from("notes.txt")]);
+    }
+
+    #[test]
+    fn a_long_line_wraps_into_as_many_rows_as_it_needs() {
+        assert_eq!(wrap_columns("", 10), vec![Range { start: 0, end: 0 }]);
+        assert_eq!(wrap_columns(&"x".repeat(10), 10).len(), 1);
+        assert_eq!(wrap_columns(&"x".repeat(25), 10), [0..10, 10..20, 20..25]);
+        assert_eq!(wrap_columns(&"x".repeat(100), 80).len(), 2);
+    }
+
+    #[test]
+    fn wrapping_breaks_after_a_blank_where_there_is_one() {
+        assert_eq!(wrap_columns("aaaa bbbb cccc", 10), [0..10, 10..14]);
+        assert_eq!(wrap_columns("aaaa bbbbbbbbbbbb", 10), [0..5, 5..15, 15..17]);
+    }
+
+    /* A line of 45 columns and a short one, wrapping on a screen 20
+       wide. */
+    fn wrapping() -> (NavigationModel, EditingModel) {
+        let contents = EditingModel::from_text(&format!("{}\nshort", "x".repeat(45)));
+        let mut navigation = NavigationModel {
+            screen_size: ScreenSize { columns: 20, rows: 5 },
+            ..NavigationModel::default()
+        };
+        navigation.set_wrap(true);
+        (navigation, contents)
+    }
+
+    #[test]
+    fn wrapped_lines_take_up_a_row_each_piece() {
+        let (navigation, contents) = wrapping();
+        let rows: Vec<_> = navigation.visual_rows(&contents).iter()
+            .map(|shown| (shown.row, shown.columns.clone()))
+            .collect();
+
+        assert_eq!(rows, [(0, 0..20), (0, 20..40), (0, 40..45), (1, 0..5)]);
+    }
+
+    #[test]
+    fn up_and_down_move_a_row_of_the_screen_at_a_time() {
+        let (mut navigation, contents) = wrapping();
+        navigation.move_intended(&Direction::Right, &contents);
+
+        let mut stops = vec![];
+        for direction in [Direction::Down, Direction::Down, Direction::Down, Direction::Up] {
+            navigation.move_intended(&direction, &contents);
+            stops.push(navigation.cursor_in_buffer());
+        }
+        assert_eq!(stops, [pos(0, 21), pos(0, 41), pos(1, 1), pos(0, 41)]);
+    }
+
+    #[test]
+    fn wrapped_text_shows_on_the_rows_below() {
+        let scratch = Scratch::new("wrap_on_screen");
+        let file_path = scratch.file("long.txt", format!("{}end\n", "word ".repeat(17)));
+        let config = Config { wrap: true, show_line_numbers: false, ..Config::default() };
+        let host = headless::ScriptedHost::new([event::Event::Resize(40, 5)]).unwrap().with_clock_stopped();
+        let open_file = OpenFile { file_paths: vec![file_path], read_only: false };
+        host.run_for::<Editor>((open_file, config), 100).unwrap();
+
+        let screen = grid(&host.output(), 40, 5);
+        assert_eq!(screen[0].trim_end(), "word word word word word word word word");
+        assert_eq!(screen[1].trim_end(), "word word word word word word word word");
+        assert_eq!(screen[2].trim_end(), "word end");
+        assert_eq!(screen[3].trim_end(), "~");
+    }
+
+    #[test]
+    fn only_rows_below_the_last_line_are_past_the_end() {
+        let contents = EditingModel::from_text("one\n\nthree\n");
+        let viewport = EditingViewport { row_offset: 1, ..EditingViewport::default() };
+
+        assert_eq!((0..4).map(|row| viewport.is_past_end(row, &contents)).collect::<Vec<_>>(), [false, false, true, true]);
+    }
+
+    #[test]
+    fn an_empty_line_renders_empty_and_past_the_end_renders_nothing() {
+        let scratch = Scratch::new("eof_marker");
+        let file_path = scratch.file("blank.txt", "one\n\nthree\n");
+        let config = Config { show_line_numbers: false, ..Config::default() };
+        let (editor, _) = run_with(vec![file_path], config, vec![]);
+        let shown = editor.buffer();
+
+        assert_eq!(editor.render_line(shown, 1).as_deref(), Some(""));
+        assert_eq!(editor.render_line(shown, 3), None);
+    }
+
+    #[test]
+    fn the_end_of_buffer_marker_only_shows_past_the_last_line_and_dimmer() {
+        let scratch = Scratch::new("eof_marker_screen");
+        let file_path = scratch.file("blank.txt", "one\n\nthree\n");
+        let config = Config { show_line_numbers: false, ..Config::default() };
+        let host = headless::ScriptedHost::new([event::Event::Resize(20, 6)]).unwrap().with_clock_stopped();
+        let open_file = OpenFile { file_paths: vec![file_path], read_only: false };
+        let editor = host.run_for::<Editor>((open_file, config), 100).unwrap();
+
+        let screen = grid(&host.output(), 20, 6);
+        let rows: Vec<_> = screen[..5].iter().map(|row| row.trim_end()).collect();
+        assert_eq!(rows, ["one", "", "three", "~", "~"]);
+
+        let mut dimmed = vec![];
+        dimmed.queue(style::SetForegroundColor(editor.theme.end_of_buffer)).unwrap()
+              .queue(style::Print('~')).unwrap();
+        assert!(host.output().windows(dimmed.len()).any(|window| window == dimmed));
+    }
+
+    /* A hundred digits seen ten columns at a time from column_offset,
+       markers and all. */
+    fn marked(column_offset: usize) -> (String, bool, bool) {
+        let contents = EditingModel::from_text(&"0123456789".repeat(10));
+        let viewport = EditingViewport { column_offset, ..EditingViewport::default() };
+        let clipped = viewport.select_and_clip(0, 10, &contents);
+        let (cut_left, cut_right) = (clipped.cut_left, clipped.cut_right);
+        (clipped.with_markers().into_owned(), cut_left, cut_right)
+    }
+
+    #[test]
+    fn more_to_the_right_puts_a_marker_in_the_last_column() {
+        assert_eq!(marked(0), ("012345678»".to_owned(), false, true));
+    }
+
+    #[test]
+    fn more_on_both_sides_puts_a_marker_at_either_end() {
+        assert_eq!(marked(5), ("«67890123»".to_owned(), true, true));
+        assert_eq!(marked(89), ("«01234567»".to_owned(), true, true));
+    }
+
+    #[test]
+    fn the_end_of_the_line_in_view_leaves_only_the_left_marker() {
+        assert_eq!(marked(90), ("«123456789".to_owned(), true, false));
+        assert_eq!(marked(95), ("«6789".to_owned(), true, false));
+    }
+
+    #[test]
+    fn a_line_entirely_off_to_the_left_is_a_lone_marker() {
+        assert_eq!(marked(100), ("«".to_owned(), true, false));
+        assert_eq!(marked(150), ("«".to_owned(), true, false));
+    }
+
+    #[test]
+    fn a_line_that_fits_has_no_markers() {
+        let contents = EditingModel::from_text("0123456789");
+        let clipped = EditingViewport::default().select_and_clip(0, 10, &contents);
+
+        assert_eq!(clipped.with_markers(), "0123456789");
+    }
+
+    /* Where centering on row of a hundred lines on ten rows leaves
+       the top of the screen, and the cursor on it. */
+    fn centering_on(row: usize) -> (usize, usize) {
+        let contents = EditingModel::from_text(&"line\n".repeat(100));
+        let mut navigation = NavigationModel {
+            screen_size: ScreenSize { columns: 80, rows: 10 },
+            ..NavigationModel::default()
+        };
+        navigation.jump_to(&pos(row, 0));
+        navigation.center_on_cursor(&contents);
+
+        assert_eq!(navigation.cursor_in_buffer(), pos(row, 0));
+        (navigation.viewport.row_offset, navigation.cursor.row)
+    }
+
+    #[test]
+    fn centering_puts_the_cursor_line_mid_screen() {
+        assert_eq!(centering_on(50), (45, 5));
+        assert_eq!(centering_on(9), (4, 5));
+    }
+
+    #[test]
+    fn centering_near_the_top_does_not_scroll_above_it() {
+        assert_eq!(centering_on(0), (0, 0));
+        assert_eq!(centering_on(2), (0, 2));
+    }
+
+    #[test]
+    fn centering_near_the_bottom_does_not_scroll_past_it() {
+        assert_eq!(centering_on(97), (90, 7));
+        assert_eq!(centering_on(99), (90, 9));
+    }
+
+    #[test]
+    fn ctrl_l_centers() {
+        let scratch = Scratch::new("ctrl_l");
+        let file_path = scratch.file("hundred.txt", "line\n".repeat(100));
+        let script = iter::repeat_n(key(KeyCode::Down), 40).chain([ctrl('l')]).collect();
+        let (editor, _) = run_with(vec![file_path], Config::default(), script);
+
+        assert_eq!(editor.buffer().navigation.viewport.row_offset, 36);
+        assert_eq!(editor.buffer().cursor().row, 40);
+    }
+
+    #[test]
+    fn shrinking_the_screen_keeps_the_cursor_where_it_was_in_the_buffer() {
+        let mut navigation = NavigationModel {
+            screen_size: ScreenSize { columns: 80, rows: 24 },
+            ..NavigationModel::default()
+        };
+        navigation.jump_to(&pos(23, 79));
+        assert!(navigation.is_bottommost() && navigation.is_rightmost());
+
+        navigation.screen_size_changed(ScreenSize { columns: 10, rows: 5 });
+        assert_eq!(navigation.cursor_in_buffer(), pos(23, 79));
+        assert_eq!(navigation.cursor, pos(4, 9));
+        assert_eq!((navigation.viewport.row_offset, navigation.viewport.column_offset), (19, 70));
+        assert!(navigation.is_bottommost() && navigation.is_rightmost());
+    }
+
+    #[test]
+    fn a_resize_event_keeps_the_cursor_on_the_smaller_screen() {
+        let scratch = Scratch::new("resize_smaller");
+        let file_path = scratch.file("wide.txt", format!("{}\n", "x".repeat(200)).repeat(100));
+        let moves = iter::repeat_n(key(KeyCode::Down), 22).chain(iter::repeat_n(key(KeyCode::Right), 79));
+        let script = [event::Event::Resize(80, 24)].into_iter()
+            .chain(moves)
+            .chain([event::Event::Resize(10, 5)])
+            .collect();
+        let (editor, _) = run_with(vec![file_path], Config { show_line_numbers: false, ..Config::default() }, script);
+        let navigation = &editor.buffer().navigation;
+
+        assert_eq!(editor.buffer().cursor(), pos(22, 79));
+        assert!(navigation.cursor.row < 4 && navigation.cursor.column < 10);
+    }
+
+    #[test]
+    fn a_zero_size_screen_has_no_edges_to_be_at() {
+        let navigation = NavigationModel::default();
+        assert_eq!(navigation.screen_size, ScreenSize { columns: 0, rows: 0 });
+
+        assert!(navigation.is_topmost());
+        assert!(!navigation.is_bottommost());
+        assert!(!navigation.is_rightmost());
+    }
+
+    #[test]
+    fn moving_about_on_a_zero_size_screen_does_not_panic() {
+        let contents = EditingModel::from_text("one\ntwo\nthree");
+        let mut navigation = NavigationModel::default();
+        use Direction::*;
+        for direction in [Down, Right, PageDown, LineEnd, Up, Left, PageUp, LineStart] {
+            navigation.move_intended(&direction, &contents);
+        }
+        navigation.center_on_cursor(&contents);
+        navigation.scroll_view_down(&contents);
+        navigation.scroll_view_up(&contents);
+    }
+
+    #[test]
+    fn nothing_is_drawn_before_the_size_is_known() {
+        let editor = Editor::init((OpenFile { file_paths: vec![], read_only: false }, Config::default())).0;
+        let mut frame = tui::CommandBuffer::default();
+        editor.view(&mut frame).unwrap();
+
+        assert_eq!(editor.screen_size, ScreenSize::default());
+        assert_eq!(tui::strip_ansi(&frame.take().unwrap()), "");
+    }
+
+    /* A thousand lines on ten rows, the cursor three rows down a
+       screen that starts at top. */
+    fn paging_from(top: usize) -> (NavigationModel, EditingModel) {
+        let contents = EditingModel::from_text(&"line\n".repeat(1000));
+        let mut navigation = NavigationModel {
+            screen_size: ScreenSize { columns: 80, rows: 10 },
+            ..NavigationModel::default()
+        };
+        navigation.viewport.row_offset = top;
+        navigation.cursor.row = 3;
+        (navigation, contents)
+    }
+
+    #[test]
+    fn a_page_down_is_a_screen_less_a_line_and_the_cursor_keeps_its_row() {
+        let (mut navigation, contents) = paging_from(500);
+        for page in 1..=5 {
+            navigation.move_intended(&Direction::PageDown, &contents);
+            assert_eq!(navigation.cursor_in_buffer().row, 503 + page * 9);
+            assert_eq!(navigation.cursor.row, 3);
+        }
+    }
+
+    #[test]
+    fn a_page_up_is_a_screen_less_a_line_and_the_cursor_keeps_its_row() {
+        let (mut navigation, contents) = paging_from(500);
+        for page in 1..=5 {
+            navigation.move_intended(&Direction::PageUp, &contents);
+            assert_eq!(navigation.cursor_in_buffer().row, 503 - page * 9);
+            assert_eq!(navigation.cursor.row, 3);
+        }
+    }
+
+    #[test]
+    fn paging_stops_at_either_end_of_the_file() {
+        let (mut navigation, contents) = paging_from(985);
+        for _ in 0..5 {
+            navigation.move_intended(&Direction::PageDown, &contents);
+        }
+        assert_eq!(navigation.cursor_in_buffer().row, 999);
+        assert_eq!(navigation.viewport.row_offset, 990);
+
+        let (mut navigation, contents) = paging_from(10);
+        for _ in 0..5 {
+            navigation.move_intended(&Direction::PageUp, &contents);
+        }
+        assert_eq!(navigation.cursor_in_buffer().row, 0);
+        assert_eq!(navigation.viewport.row_offset, 0);
+    }
+
+    #[test]
+    fn page_down_and_up_come_back_to_the_same_line() {
+        let (mut navigation, contents) = paging_from(500);
+        navigation.move_intended(&Direction::PageDown, &contents);
+        navigation.move_intended(&Direction::PageUp, &contents);
+
+        assert_eq!(navigation.cursor_in_buffer().row, 503);
+        assert_eq!(navigation.viewport.row_offset, 500);
+    }
+
+    #[test]
+    fn the_column_comes_back_after_crossing_a_short_line() {
+        use Direction::*;
+        let navigation = navigating("a long first line\nshort\nanother long line", &[LineEnd, Down]);
+        assert_eq!(navigation.cursor_in_buffer(), pos(1, 5));
+        assert_eq!(navigation.goal_column, 17);
+
+        let navigation = navigating("a long first line\nshort\nanother long line", &[LineEnd, Down, Down]);
+        assert_eq!(navigation.cursor_in_buffer(), pos(2, 17));
+
+        let navigation = navigating("a long first line\nshort\nanother long line", &[LineEnd, Down, Up]);
+        assert_eq!(navigation.cursor_in_buffer(), pos(0, 17));
+    }
+
+    #[test]
+    fn an_empty_line_in_between_still_remembers_the_column() {
+        use Direction::*;
+        let navigation = navigating("0123456789\n\n01234", &[LineEnd, Left, Left, Down, Down]);
+
+        assert_eq!(navigation.cursor_in_buffer(), pos(2, 5));
+        assert_eq!(navigation.goal_column, 8);
+    }
+
+    #[test]
+    fn moving_sideways_sets_a_new_goal() {
+        use Direction::*;
+        let navigation = navigating("a long first line\nshort\nanother long line", &[LineEnd, Down, Left, Down]);
+
+        assert_eq!(navigation.cursor_in_buffer(), pos(2, 4));
+    }
+
+    #[test]
+    fn paging_keeps_the_goal_column_too() {
+        use Direction::*;
+        let text = ["0123456789", "", "0123456789"].repeat(10).join("\n");
+        let navigation = navigating(&text, &[LineEnd, Left, PageDown]);
+
+        assert_eq!(navigation.cursor_in_buffer(), pos(9, 9));
+    }
+
+    #[test]
+    fn joining_two_lines_puts_a_space_between() {
+        let mut contents = EditingModel::from_text("foo\nbar\nbaz");
+
+        assert_eq!(contents.join_lines(0), Some(3));
+        assert_eq!(contents.serialize(), "foo bar\nbaz");
+    }
+
+    #[test]
+    fn joining_an_empty_line_adds_no_space() {
+        let mut contents = EditingModel::from_text("foo\n\nbar");
+
+        assert_eq!(contents.join_lines(0), Some(3));
+        assert_eq!(contents.serialize(), "foo\nbar");
+    }
+
+    #[test]
+    fn joining_the_last_line_does_nothing() {
+        let mut contents = EditingModel::from_text("foo\nbar");
+
+        assert_eq!(contents.join_lines(1), None);
+        assert_eq!(contents.join_lines(7), None);
+        assert_eq!(contents.serialize(), "foo\nbar");
+    }
+
+    #[test]
+    fn j_in_normal_mode_joins_and_leaves_the_cursor_at_the_join() {
+        let scratch = Scratch::new("join_key");
+        let file_path = scratch.file("notes.txt", "foo\nbar\n");
+        let (editor, _) = run_with(vec![file_path], modal(), typed("J").collect());
+
+        assert_eq!(editor.buffer().contents.serialize(), "foo bar\n");
+        assert_eq!(editor.buffer().cursor(), pos(0, 3));
+    }
+
+    #[test]
+    fn a_join_is_undone_in_one_go() {
+        let scratch = Scratch::new("join_undo");
+        let file_path = scratch.file("notes.txt", "foo\nbar\n");
+        let (editor, _) = run_with(vec![file_path], modal(), typed("Ju").collect());
+
+        assert_eq!(editor.buffer().contents.serialize(), "foo\nbar\n");
+    }
+
+    #[test]
+    fn truncating_cuts_the_line_at_the_column() {
+        let mut contents = EditingModel::from_text("café société");
+        contents.truncate_line(0, 4);
+        assert_eq!(contents.serialize(), "café");
+
+        contents.truncate_line(0, 10);
+        assert_eq!(contents.serialize(), "café");
+    }
+
+    #[test]
+    fn truncating_the_only_line_from_the_start_leaves_it_empty() {
+        let mut contents = EditingModel::from_text("only");
+        contents.truncate_line(0, 0);
+
+        assert_eq!(contents.line_count(), 1);
+        assert_eq!(contents.serialize(), "");
+    }
+
+    #[test]
+    fn deleting_a_line_moves_to_the_one_that_took_its_place() {
+        let mut contents = EditingModel::from_text("one\ntwo\nthree");
+        assert_eq!(contents.delete_line(1), 1);
+        assert_eq!(contents.serialize(), "one\nthree");
+
+        assert_eq!(contents.delete_line(1), 0);
+        assert_eq!(contents.serialize(), "one");
+    }
+
+    #[test]
+    fn deleting_the_only_line_leaves_an_empty_one() {
+        let mut contents = EditingModel::from_text("only");
+
+        assert_eq!(contents.delete_line(0), 0);
+        assert_eq!(contents.line_count(), 1);
+        assert_eq!(contents.line(0), "");
+        assert_eq!(contents.delete_line(0), 0);
+        assert_eq!(contents.line_count(), 1);
+    }
+
+    #[test]
+    fn ctrl_k_and_alt_k_delete_through_the_editor() {
+        let alt_k = event::Event::Key(KeyEvent::new(KeyCode::Char('k'), KeyModifiers::ALT));
+        let script = typed("one two\nthree")
+            .chain([key(KeyCode::Up), key(KeyCode::Home)])
+            .chain(iter::repeat_n(key(KeyCode::Right), 3))
+            .chain([ctrl('k')])
+            .collect();
+        let editor = run(script);
+        assert_eq!(editor.buffer().contents.serialize(), "one\nthree");
+
+        let editor = run(typed("only").chain([alt_k.clone(), alt_k]).collect());
+        assert_eq!(editor.buffer().contents.serialize(), "");
+        assert_eq!(editor.buffer().cursor(), pos(0, 0));
+    }
+
+    #[test]
+    fn inserting_a_line_past_the_end_appends_it() {
+        let mut contents = EditingModel::from_text("one\ntwo");
+        contents.insert_line(2, "three".to_owned());
+        contents.insert_line(10, "four".to_owned());
+
+        assert_eq!(contents.serialize(), "one\ntwo\nthree\nfour");
+    }
+
+    #[test]
+    fn inserting_a_line_at_the_top_pushes_the_rest_down() {
+        let mut contents = EditingModel::from_text("one\ntwo");
+        contents.insert_line(0, "zero".to_owned());
+
+        assert_eq!(contents.serialize(), "zero\none\ntwo");
+    }
+
+    /* A modal editor on lines, after script. */
+    fn opening(test: &str, lines: usize, script: &str) -> Editor {
+        let scratch = Scratch::new(test);
+        let text = (1..=lines).map(|row| format!("line {}\n", row)).collect::<String>();
+        let file_path = scratch.file("notes.txt", text);
+        run_with(vec![file_path], modal(), typed(script).collect()).0
+    }
+
+    #[test]
+    fn o_on_the_last_line_grows_the_buffer_and_goes_there() {
+        let editor = opening("open_below", 2, "jo");
+
+        assert_eq!(editor.buffer().contents.serialize(), "line 1\nline 2\n\n");
+        assert_eq!(editor.buffer().cursor(), pos(2, 0));
+        assert_eq!(editor.mode, Mode::Insert);
+    }
+
+    #[test]
+    fn capital_o_on_the_first_line_opens_above_it() {
+        let editor = opening("open_above", 2, "Onew\x1b");
+
+        assert_eq!(editor.buffer().contents.serialize(), "new\nline 1\nline 2\n");
+        assert_eq!(editor.buffer().cursor().row, 0);
+    }
+
+    #[test]
+    fn opening_a_line_below_the_screen_scrolls_to_it() {
+        let editor = opening("open_scrolls", 9, "Go");
+
+        assert_eq!(editor.buffer().cursor(), pos(9, 0));
+        assert_eq!(editor.buffer().navigation.viewport.row_offset, 1);
+    }
+
+    /* Output cut up wherever it moves the cursor, each piece with
+       the row it was moved to. */
+    fn rows_drawn(output: &[u8]) -> Vec<(usize, String)> {
+        let text = String::from_utf8_lossy(output);
+        let mut pieces: Vec<(usize, String)> = vec![];
+        for (index, sequence) in text.split("\x1b[").enumerate() {
+            let moved_to = sequence.split_once('H')
+                .and_then(|(at, _)| at.split_once(';'))
+                .and_then(|(row, column)| Some((row.parse::<usize>().ok()?, column.parse::<usize>().ok()?)));
+            let sequence = if index == 0 { sequence.to_owned() } else { format!("\x1b[{}", sequence) };
+            match (moved_to, pieces.last_mut()) {
+                (Some((row, _)), _)      => pieces.push((row - 1, sequence)),
+                (None, Some((_, piece))) => piece.push_str(&sequence),
+                (None, None)             => (),
+            }
+        }
+        pieces
+    }
+
+    /* Three lines with the cursor on the middle one, drawn afresh,
+       and what sets the cursor line's background. */
+    fn cursor_line_output(highlight_cursor_line: bool) -> (Vec<(usize, String)>, String) {
+        let scratch = Scratch::new(&format!("cursor_line_{}", highlight_cursor_line));
+        let file_path = scratch.file("notes.txt", "one\ntwo\nthree\n");
+        let config = Config { highlight_cursor_line, ..Config::default() };
+        let (editor, _) = run_with(vec![file_path], config, vec![key(KeyCode::Down)]);
+
+        let mut frame = tui::CommandBuffer::default();
+        editor.view(&mut frame).unwrap();
+        let mut background = vec![];
+        background.queue(style::SetBackgroundColor(editor.theme.cursor_line)).unwrap();
+        (rows_drawn(&frame.take().unwrap()), String::from_utf8(background).unwrap())
+    }
+
+    #[test]
+    fn the_cursor_line_and_only_it_gets_the_background() {
+        let (rows, background) = cursor_line_output(true);
+        let highlighted: Vec<_> = rows.iter()
+            .filter(|(_, piece)| piece.contains(&background))
+            .map(|(row, _)| *row)
+            .collect();
+        assert_eq!(highlighted, [1]);
+
+        let (_, below) = rows.iter().find(|(row, _)| *row == 2).unwrap();
+        assert!(below.starts_with("\x1b[3;1H\x1b[0m"), "{:?}", below);
+    }
+
+    #[test]
+    fn no_background_when_the_highlight_is_off() {
+        let (rows, background) = cursor_line_output(false);
+
+        assert!(rows.iter().all(|(_, piece)| !piece.contains(&background)));
+    }
+
+    #[test]
+    fn keyword_spans_are_clipped_to_the_visible_columns() {
+        let visible = 1..14;
+        let spans: Vec<_> = RustKeywordHighlighter.spans("fn main() { let x = 1; } impl").into_iter()
+            .map(|(columns, _)| clip_columns(columns, &visible))
+            .filter(|columns| !columns.is_empty())
+            .collect();
+
+        assert_eq!(spans, [0..1, 11..13]);
+    }
+
+    fn status_bar_output(theme: Theme) -> String {
+        let (editor, _) = run_with(vec![], Config { theme, ..Config::default() }, vec![]);
+        let mut frame = tui::CommandBuffer::default();
+        frame.resize(80, 10);
+        editor.render_status_bar(&mut frame).unwrap();
+        String::from_utf8(frame.take().unwrap()).unwrap()
+    }
+
+    fn foreground(color: style::Color) -> String {
+        let mut command = vec![];
+        command.queue(style::SetForegroundColor(color)).unwrap();
+        String::from_utf8(command).unwrap()
+    }
+
+    #[test]
+    fn swapping_themes_changes_the_status_bar_colour() {
+        let (dark, light) = (Theme::default_dark(), Theme::default_light());
+        assert_ne!(dark.status_bar, light.status_bar);
+
+        let dark_bar = status_bar_output(dark.clone());
+        let light_bar = status_bar_output(light.clone());
+
+        assert!(dark_bar.contains(&foreground(dark.status_bar)), "{:?}", dark_bar);
+        assert!(!dark_bar.contains(&foreground(light.status_bar)), "{:?}", dark_bar);
+        assert!(light_bar.contains(&foreground(light.status_bar)), "{:?}", light_bar);
+        assert!(!light_bar.contains(&foreground(dark.status_bar)), "{:?}", light_bar);
+    }
+
+    #[test]
+    fn focus_events_flip_the_focused_flag() {
+        let mut editor = run(vec![]);
+        assert!(editor.focused);
+
+        send(&mut editor, event::Event::FocusLost.into());
+        assert!(!editor.focused);
+        send(&mut editor, event::Event::FocusLost.into());
+        assert!(!editor.focused);
+
+        send(&mut editor, event::Event::FocusGained.into());
+        assert!(editor.focused);
+    }
+
+    fn pasting(text: &str, after: Vec<event::Event>) -> Editor {
+        let script = typed("abcd")
+            .chain([key(KeyCode::Left), key(KeyCode::Left), event::Event::Paste(text.to_owned())])
+            .chain(after)
+            .collect();
+        run(script)
+    }
+
+    #[test]
+    fn a_two_line_paste_mid_line_splits_it_around_the_text() {
+        let editor = pasting("one\ntwo", vec![]);
+
+        assert_eq!(editor.buffer().contents.serialize(), "abone\ntwocd");
+        assert_eq!(editor.cursor(), pos(1, 3));
+    }
+
+    #[test]
+    fn pasted_crlf_breaks_lines_once_and_tabs_stay() {
+        let editor = pasting("one\r\n\ttwo", vec![]);
+
+        assert_eq!(editor.buffer().contents.serialize(), "abone\n\ttwocd");
+    }
+
+    #[test]
+    fn a_paste_is_undone_in_one_go() {
+        let editor = pasting("one\ntwo", vec![ctrl('z')]);
+
+        assert_eq!(editor.buffer().contents.serialize(), "abcd");
+        assert_eq!(editor.cursor(), pos(0, 2));
+    }
+
+    /* The messages cmd puts off for later, leaving the rest out. */
+    fn delayed(cmd: elm::Cmd<Message>) -> Vec<Message> {
+        match cmd {
+            elm::Cmd::Delay(_, message)    => vec![message],
+            elm::Cmd::AndThen(then, first) => [delayed(*first), delayed(*then)].concat(),
+            elm::Cmd::Batch(cmds)          => cmds.into_iter().flat_map(delayed).collect(),
+            _otherwise                     => vec![],
+        }
+    }
+
+    #[test]
+    fn a_second_message_replaces_the_first_and_outlives_its_expiry() {
+        let mut editor = run(vec![]);
+        let first = delayed(editor.update(&ctrl('z').into()));
+        assert_eq!(editor.message.text(), "Nothing to undo");
+        let second = delayed(editor.update(&ctrl('y').into()));
+        assert_eq!(editor.message.text(), "Nothing to redo");
+        assert!(matches!(first[..], [Message::ExpireStatus(_)]));
+        assert!(matches!(second[..], [Message::ExpireStatus(_)]));
+
+        for expiry in first {
+            send(&mut editor, expiry);
+        }
+        assert_eq!(editor.message.text(), "Nothing to redo");
+
+        for expiry in second {
+            send(&mut editor, expiry);
+        }
+        assert_eq!(editor.message.text(), "");
+    }
+
+    /* An x typed into a file, then the clock left to run for a while,
+       as it does once the script is out. */
+    fn left_running(test: &str, autosave: Option<time::Duration>) -> (Editor, String) {
+        let scratch = Scratch::new(test);
+        let file_path = scratch.file("notes.txt", "one\ntwo");
+        let host = headless::ScriptedHost::new([event::Event::Resize(80, 10), key(KeyCode::Char('x'))]).unwrap();
+        let open_file = OpenFile { file_paths: vec![file_path.clone()], read_only: false };
+        let config = Config { autosave, ..Config::default() };
+        let editor = host.run_for::<Editor>((open_file, config), 100).unwrap();
+        (editor, fs::read_to_string(file_path).unwrap())
+    }
+
+    #[test]
+    fn autosave_writes_a_changed_buffer_once_the_interval_is_up() {
+        let (editor, on_disk) = left_running("autosave_due", Some(time::Duration::from_secs(5)));
+
+        assert_eq!(on_disk, "xone\ntwo");
+        assert!(!editor.buffer().modified);
+    }
+
+    #[test]
+    fn without_autosave_the_file_is_left_alone() {
+        let (editor, on_disk) = left_running("autosave_off", None);
+
+        assert_eq!(on_disk, "one\ntwo");
+        assert!(editor.buffer().modified);
+    }
+
+    #[test]
+    fn autosave_has_nothing_to_do_for_clean_or_nameless_buffers() {
+        let scratch = Scratch::new("autosave_idle");
+        let file_path = scratch.file("notes.txt", "one\ntwo");
+        let (mut clean, _) = run_with(vec![file_path], Config::default(), vec![]);
+        let mut nameless = run(typed("x").collect());
+        assert!(nameless.buffer().modified);
+
+        for editor in [&mut clean, &mut nameless] {
+            match editor.autosave() {
+                elm::Cmd::Batch(saves) => assert!(saves.iter().all(|save| matches!(save, elm::Cmd::None))),
+                _otherwise             => panic!("autosave comes up with a batch"),
+            }
+        }
+    }
+
+    #[test]
+    fn a_swap_file_is_written_once_typing_pauses_and_gone_after_a_save() {
+        let scratch = Scratch::new("swap_written");
+        let file_path = scratch.file("notes.txt", "one\ntwo");
+        let swap = swap_path(&file_path);
+        let host = headless::ScriptedHost::new([event::Event::Resize(80, 10), key(KeyCode::Char('x'))]).unwrap();
+        let open_file = OpenFile { file_paths: vec![file_path.clone()], read_only: false };
+        let mut editor = host.run_for::<Editor>((open_file, Config::default()), 100).unwrap();
+        assert_eq!(fs::read_to_string(&swap).unwrap(), "xone\ntwo");
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "one\ntwo");
+
+        send(&mut editor, ctrl('s').into());
+        assert!(!swap.exists());
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "xone\ntwo");
+    }
+
+    #[test]
+    fn only_a_swap_file_newer_than_the_file_is_worth_recovering() {
+        let now = time::SystemTime::now();
+        let earlier = now - time::Duration::from_secs(60);
+
+        assert!(swap_is_newer(Some(earlier), Some(now)));
+        assert!(!swap_is_newer(Some(now), Some(earlier)));
+        assert!(!swap_is_newer(Some(now), Some(now)));
+        assert!(swap_is_newer(None, Some(now)));
+        assert!(!swap_is_newer(Some(now), None));
+        assert!(!swap_is_newer(None, None));
+    }
+
+    #[test]
+    fn opening_a_file_with_a_newer_swap_file_offers_to_recover_it() {
+        let scratch = Scratch::new("swap_recover");
+        let file_path = scratch.file("notes.txt", "saved");
+        touch_behind_the_editors_back(&file_path);
+        scratch.file(".notes.txt.swp", "unsaved");
+
+        let (mut editor, _) = run_with(vec![file_path], Config::default(), vec![]);
+        assert!(matches!(editor.prompt, Some(Prompt { kind: PromptKind::Recover(0), .. })));
+
+        send(&mut editor, key(KeyCode::Char('y')).into());
+        send(&mut editor, key(KeyCode::Enter).into());
+        assert_eq!(editor.buffer().contents.serialize(), "unsaved");
+        assert!(editor.buffer().modified);
+    }
+
+    #[test]
+    fn an_older_swap_file_is_not_offered() {
+        let scratch = Scratch::new("swap_stale");
+        let swap = scratch.file(".notes.txt.swp", "unsaved");
+        touch_behind_the_editors_back(&swap);
+        let file_path = scratch.file("notes.txt", "saved");
+
+        let (editor, _) = run_with(vec![file_path], Config::default(), vec![]);
+        assert!(editor.prompt.is_none());
+    }
+
+    fn queued_shape(editor: &Editor) -> Vec<u8> {
+        let mut frame = tui::CommandBuffer::default();
+        frame.resize(80, 10);
+        editor.view(&mut frame).unwrap();
+        frame.take().unwrap()
+    }
+
+    fn shape(style: cursor::SetCursorStyle) -> Vec<u8> {
+        let mut command = vec![];
+        command.queue(style).unwrap();
+        command
+    }
+
+    fn contains(output: &[u8], command: &[u8]) -> bool {
+        output.windows(command.len()).any(|window| window == command)
+    }
+
+    #[test]
+    fn each_mode_queues_its_own_cursor_shape() {
+        let (block, bar) = (shape(cursor::SetCursorStyle::SteadyBlock), shape(cursor::SetCursorStyle::SteadyBar));
+
+        let (normal, _) = run_with(vec![], modal(), vec![]);
+        let output = queued_shape(&normal);
+        assert!(contains(&output, &block) && !contains(&output, &bar));
+
+        let (insert, _) = run_with(vec![], modal(), typed("i").collect());
+        let output = queued_shape(&insert);
+        assert!(contains(&output, &bar) && !contains(&output, &block));
+    }
+
+    #[test]
+    fn insert_mode_on_a_read_only_buffer_queues_an_underline() {
+        let scratch = Scratch::new("underline_cursor");
+        let file_path = scratch.file("notes.txt", "one");
+        let (editor, _) = run_with(vec![file_path], Config::default(), vec![ctrl('r')]);
+        assert!(editor.buffer().read_only);
+
+        assert!(contains(&queued_shape(&editor), &shape(cursor::SetCursorStyle::SteadyUnderScore)));
+    }
+
+    fn modal_script(test: &str, script: &str) -> Editor {
+        let scratch = Scratch::new(test);
+        let file_path = scratch.file("notes.txt", "abc\ndef");
+        run_with(vec![file_path], modal(), typed(script).collect()).0
+    }
+
+    #[test]
+    fn in_normal_mode_letters_are_commands() {
+        let editor = modal_script("normal_letters", "lxjx");
+
+        assert_eq!(editor.buffer().contents.serialize(), "ac\ndf");
+        assert_eq!(editor.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn i_and_a_insert_before_and_after_the_cursor_until_esc() {
+        let editor = modal_script("insert_append", "li1\x1ba2\x1bx");
+
+        assert_eq!(editor.buffer().contents.serialize(), "a1b2\ndef");
+        assert_eq!(editor.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn o_opens_a_line_below_to_type_into() {
+        let editor = modal_script("open_below", "onew");
+
+        assert_eq!(editor.buffer().contents.serialize(), "abc\nnew\ndef");
+        assert_eq!(editor.mode, Mode::Insert);
+    }
+
+    #[test]
+    fn the_status_bar_says_which_mode_it_is() {
+        assert!(on_screen(&modal_script("mode_normal", "")).contains("NORMAL"));
+        assert!(on_screen(&modal_script("mode_insert", "i")).contains("INSERT"));
+        assert!(on_screen(&modal_script("mode_back", "i\x1b")).contains("NORMAL"));
+    }
+
+    fn sequence(script: &str) -> Editor {
+        run_with(vec![], modal(), typed(&format!("ione\ntwo\nthree\x1b{}", script)).collect()).0
+    }
+
+    #[test]
+    fn gg_goes_to_the_top() {
+        let editor = sequence("gg");
+
+        assert_eq!(editor.cursor(), pos(0, 0));
+        assert_eq!(editor.buffer().contents.serialize(), "one\ntwo\nthree");
+    }
+
+    #[test]
+    fn dd_deletes_the_line() {
+        let editor = sequence("kdd");
+
+        assert_eq!(editor.buffer().contents.serialize(), "one\nthree");
+        assert_eq!(editor.cursor().row, 1);
+    }
+
+    #[test]
+    fn a_lone_d_lets_the_next_key_go_through() {
+        let editor = sequence("k0dx");
+
+        assert_eq!(editor.buffer().contents.serialize(), "one\nwo\nthree");
+        assert!(editor.pending.first.is_none());
+
+        let editor = sequence("ggdj");
+        assert_eq!(editor.buffer().contents.serialize(), "one\ntwo\nthree");
+        assert_eq!(editor.cursor(), pos(1, 0));
+    }
+
+    #[test]
+    fn a_count_past_the_bottom_stops_on_the_last_line() {
+        let editor = sequence("gg10j");
+
+        assert_eq!(editor.cursor().row, 2);
+        assert_eq!(editor.count, None);
+    }
+
+    #[test]
+    fn a_count_of_x_near_the_end_of_the_line_stops_at_the_end() {
+        let editor = sequence("ggl3x");
+
+        assert_eq!(editor.buffer().contents.serialize(), "o\ntwo\nthree");
+        assert_eq!(editor.count, None);
+    }
+
+    #[test]
+    fn zero_goes_to_the_start_unless_it_is_part_of_a_count() {
+        let editor = sequence("ggll0x");
+        assert_eq!(editor.buffer().contents.serialize(), "ne\ntwo\nthree");
+
+        let editor = sequence("gg10x");
+        assert_eq!(editor.buffer().contents.serialize(), "\ntwo\nthree");
+    }
+
+    #[test]
+    fn a_yanked_line_pastes_as_a_line_below_or_above() {
+        let editor = sequence("ggyyp");
+        assert_eq!(editor.buffer().contents.serialize(), "one\none\ntwo\nthree");
+        assert_eq!(editor.register, Register { text: "one".to_owned(), linewise: true });
+
+        let editor = sequence("ggjyyP");
+        assert_eq!(editor.buffer().contents.serialize(), "one\ntwo\ntwo\nthree");
+    }
+
+    #[test]
+    fn a_yanked_selection_pastes_inside_the_line() {
+        let editor = sequence("ggvlyjp");
+
+        assert_eq!(editor.register, Register { text: "on".to_owned(), linewise: false });
+        assert_eq!(editor.buffer().contents.serialize(), "one\ntonwo\nthree");
+    }
+
+    fn selected(anchor: Position, head: Position) -> Vec<Option<Range<usize>>> {
+        let contents = EditingModel::from_text("alpha\nbeta\ngamma\ndelta");
+        let selection = Selection { anchor, head };
+        (0..4).map(|row| selection.columns_on(row, &contents)).collect()
+    }
+
+    #[test]
+    fn a_selection_on_one_line_takes_both_ends_whichever_way_it_went() {
+        let contents = EditingModel::from_text("alpha\nbeta");
+        let forwards = Selection { anchor: pos(0, 1), head: pos(0, 3) };
+        let backwards = Selection { anchor: pos(0, 3), head: pos(0, 1) };
+
+        assert_eq!(forwards.bounds(&contents), (pos(0, 1), pos(0, 4)));
+        assert_eq!(backwards.bounds(&contents), (pos(0, 1), pos(0, 4)));
+        assert_eq!(selected(pos(1, 1), pos(1, 2)), [None, Some(1..3), None, None]);
+    }
+
+    #[test]
+    fn a_selection_over_lines_takes_part_of_the_ends_and_all_between() {
+        assert_eq!(selected(pos(0, 3), pos(2, 1)), [Some(3..5), Some(0..4), Some(0..2), None]);
+        assert_eq!(selected(pos(2, 1), pos(0, 3)), [Some(3..5), Some(0..4), Some(0..2), None]);
+    }
+
+    #[test]
+    fn a_selection_ending_on_the_last_char_takes_the_line_break() {
+        let contents = EditingModel::from_text("alpha\nbeta");
+        let selection = Selection { anchor: pos(0, 2), head: pos(0, 5) };
+
+        assert_eq!(selection.bounds(&contents), (pos(0, 2), pos(1, 0)));
+        assert_eq!(selection.columns_on(1, &contents), Some(0..0));
+    }
+
+    #[test]
+    fn the_selected_columns_are_clipped_to_what_is_visible() {
+        let visible = 2..4;
+
+        assert_eq!(clip_columns(3..5, &visible), 1..2);
+        assert!(clip_columns(0..2, &visible).is_empty());
+    }
+
+    /* How many effects cmd would set off, nested ones included. */
+    fn effects(cmd: &elm::Cmd<Message>) -> usize {
+        match cmd {
+            elm::Cmd::Suspend(_)           => 1,
+            elm::Cmd::AndThen(then, first) => effects(first) + effects(then),
+            elm::Cmd::Batch(cmds)          => cmds.iter().map(effects).sum(),
+            _otherwise                     => 0,
+        }
+    }
+
+    /* What the last key of script comes up with, after the rest. */
+    fn last_key(script: &str) -> (Editor, elm::Cmd<Message>) {
+        let (init, last) = script.split_at(script.len() - 1);
+        let mut editor = sequence(init);
+        let cmd = editor.update(&key(KeyCode::Char(last.chars().next().unwrap())).into());
+        (editor, cmd)
+    }
+
+    #[test]
+    fn yank_and_paste_use_the_register_unless_told_otherwise() {
+        let (editor, cmd) = last_key("ggyy");
+        assert_eq!(effects(&cmd), 0);
+        assert_eq!(editor.register.text, "one");
+
+        let (editor, cmd) = last_key("ggyyp");
+        assert_eq!(effects(&cmd), 0);
+        assert_eq!(editor.buffer().contents.serialize(), "one\none\ntwo\nthree");
+    }
+
+    #[test]
+    fn quote_plus_sends_yank_and_paste_to_the_clipboard() {
+        let (editor, cmd) = last_key("gg\"+yy");
+        assert_eq!(effects(&cmd), 1);
+        assert_eq!(editor.register.text, "");
+
+        let (editor, cmd) = last_key("gg\"+p");
+        assert_eq!(effects(&cmd), 1);
+        assert_eq!(editor.buffer().contents.serialize(), "one\ntwo\nthree");
+        assert!(!editor.use_clipboard);
+    }
+
+    #[cfg(not(feature = "clipboard"))]
+    #[test]
+    fn without_the_clipboard_built_in_quote_plus_says_so() {
+        let editor = sequence("gg\"+yy");
+
+        assert_eq!(editor.message.text(), "clipboard support not built in");
+        assert_eq!(editor.register.text, "");
+    }
+
+    #[test]
+    fn lines_go_to_the_clipboard_with_a_line_break_and_come_back_as_lines() {
+        let line = Register { text: "one".to_owned(), linewise: true };
+        let chars = Register { text: "on".to_owned(), linewise: false };
+
+        assert_eq!(line.to_clipboard(), "one\n");
+        assert_eq!(chars.to_clipboard(), "on");
+        assert_eq!(Register::from_clipboard("one\r\n"), line);
+        assert_eq!(Register::from_clipboard("on"), chars);
+    }
+
+    #[test]
+    fn typing_a_char_draws_only_its_line_again() {
+        let scratch = Scratch::new("one_row_redrawn");
+        let file_path = scratch.file("notes.txt", "one\ntwo\nthree");
+        let (mut editor, _) = run_with(vec![file_path], Config::default(), vec![key(KeyCode::Down)]);
+        let mut frame = tui::CommandBuffer::default();
+        editor.view(&mut frame).unwrap();
+        frame.take().unwrap();
+
+        send(&mut editor, key(KeyCode::Char('x')).into());
+        assert_eq!(editor.redraw, Redraw::Rows(vec![1]));
+        assert!((0..9).all(|i| editor.row_unchanged(i) == (i != 1)));
+
+        editor.view(&mut frame).unwrap();
+        let mut rows: Vec<_> = rows_drawn(&frame.take().unwrap()).into_iter().map(|(row, _)| row).collect();
+        rows.dedup();
+        /* The line, the status bar, then the cursor back on the line. */
+        assert_eq!(rows, [1, 9, 1]);
+    }
+
+    fn listed(test: &str, text: &str) -> (Editor, String) {
+        let scratch = Scratch::new(test);
+        let file_path = scratch.file("notes.txt", text);
+        let config = Config { list: true, show_line_numbers: false, ..Config::default() };
+        let (editor, _) = run_with(vec![file_path], config, vec![]);
+        let screen = on_screen(&editor);
+        (editor, screen)
+    }
+
+    #[test]
+    fn trailing_spaces_show_as_dots_but_spaces_between_words_do_not() {
+        let (editor, screen) = listed("list_trailing", "a b  \nc");
+
+        assert!(screen.starts_with("a b·· "), "{:?}", screen);
+        assert_eq!(editor.buffer().contents.serialize(), "a b  \nc");
+    }
+
+    #[test]
+    fn tabs_show_as_an_arrow_and_padding() {
+        let (editor, screen) = listed("list_tabs", "\tx\ny");
+
+        assert!(screen.starts_with("→   x "), "{:?}", screen);
+        assert_eq!(editor.buffer().contents.serialize(), "\tx\ny");
+    }
+
+    #[test]
+    fn stripping_takes_trailing_spaces_and_tabs_and_counts_the_lines() {
+        let mut contents = EditingModel::from_text("spaces  \ntab\t\nnone\nmixed \t \n\t\nin side");
+
+        assert_eq!(contents.strip_trailing_whitespace(), 4);
+        assert_eq!(contents.serialize(), "spaces\ntab\nnone\nmixed\n\nin side");
+        assert_eq!(contents.strip_trailing_whitespace(), 0);
+    }
+
+    fn saved_with(test: &str, strip_on_save: bool) -> (Editor, String) {
+        let scratch = Scratch::new(test);
+        let file_path = scratch.file("notes.txt", "one  \ntwo\t\nthree");
+        let config = Config { strip_on_save, ..Config::default() };
+        let (editor, _) = run_with(vec![file_path.clone()], config, vec![ctrl('s')]);
+        (editor, fs::read_to_string(file_path).unwrap())
+    }
+
+    #[test]
+    fn saving_strips_trailing_whitespace_only_when_asked_to() {
+        let (editor, on_disk) = saved_with("strip_on_save", true);
+        assert_eq!(on_disk, "one\ntwo\nthree");
+        assert_eq!(editor.message.text(), "Saved notes.txt, stripped trailing whitespace from 2 line(s)");
+
+        let (editor, on_disk) = saved_with("no_strip_on_save", false);
+        assert_eq!(on_disk, "one  \ntwo\t\nthree");
+        assert_eq!(editor.message.text(), "Saved notes.txt");
+    }
+
+    #[test]
+    fn any_number_of_trailing_blank_lines_comes_to_one_line_break() {
+        let finished = |text: &str| EditingModel::from_text(text).serialize_with_final_newline();
+
+        assert_eq!(finished("a\nb"), "a\nb\n");
+        assert_eq!(finished("a\nb\n"), "a\nb\n");
+        assert_eq!(finished("a\nb\n\n\n"), "a\nb\n");
+        assert_eq!(finished("a\r\nb\r\n\r\n"), "a\r\nb\r\n");
+        assert_eq!(finished("a\r\nb"), "a\r\nb\r\n");
+    }
+
+    #[test]
+    fn saving_with_a_final_newline_leaves_the_buffer_as_it_was() {
+        let scratch = Scratch::new("final_newline");
+        let file_path = scratch.file("notes.txt", "one\ntwo");
+        let config = Config { final_newline: true, ..Config::default() };
+        let (editor, _) = run_with(vec![file_path.clone()], config, vec![ctrl('s')]);
+
+        assert_eq!(fs::read_to_string(file_path).unwrap(), "one\ntwo\n");
+        assert_eq!(editor.buffer().contents.line_count(), 2);
+        assert_eq!(editor.buffer().contents.serialize(), "one\ntwo");
+    }
+
+    fn percent(test: &str, text: &str, script: &str) -> Editor {
+        let scratch = Scratch::new(test);
+        let file_path = scratch.file("code.txt", text);
+        run_with(vec![file_path], modal(), typed(script).collect()).0
+    }
+
+    #[test]
+    fn percent_jumps_forwards_and_back_between_partners() {
+        let text = "f(a, [b]) {\n}";
+
+        assert_eq!(percent("percent_forward", text, "%").cursor(), pos(0, 8));
+        assert_eq!(percent("percent_back", text, "%%").cursor(), pos(0, 1));
+        assert_eq!(percent("percent_lines", text, "$h%").cursor(), pos(1, 0));
+    }
+
+    #[test]
+    fn percent_off_a_bracket_jumps_from_the_next_one_on_the_line() {
+        assert_eq!(percent("percent_ahead", "let x = [1, 2];", "%").cursor(), pos(0, 13));
+    }
+
+    #[test]
+    fn percent_with_no_bracket_ahead_stays_put() {
+        assert_eq!(percent("percent_none", "no brackets\n(here)", "ll%").cursor(), pos(0, 2));
+        assert_eq!(percent("percent_behind", "(a) b", "$%").cursor(), pos(0, 5));
+    }
+
+    #[test]
+    fn percent_scrolls_to_a_partner_off_the_screen() {
+        let text = format!("{{\n{}}}", "x\n".repeat(30));
+        let editor = percent("percent_scrolls", &text, "%");
+
+        assert_eq!(editor.cursor(), pos(31, 0));
+        assert!(editor.buffer().navigation.viewport.row_offset > 0);
+    }
+
+    #[test]
+    fn a_view_scrolled_past_the_end_is_pulled_back_to_the_last_line() {
+        let contents = EditingModel::from_text("one\ntwo\nthree\nfour\nfive");
+        let mut navigation = navigating("one\ntwo\nthree\nfour\nfive", &[]);
+        navigation.viewport.row_offset = 50;
+
+        navigation.clamp_viewport(&contents);
+        assert_eq!(navigation.viewport.row_offset, 4);
+        assert_eq!(navigation.cursor_in_buffer(), pos(4, 0));
+        assert_eq!(navigation.viewport.select_and_clip(0, 80, &contents).text, "five");
+    }
+
+    #[test]
+    fn a_view_that_still_shows_the_file_is_left_alone() {
+        let contents = EditingModel::from_text(&"line\n".repeat(30));
+        let mut navigation = navigating(&"line\n".repeat(30), &[Direction::PageDown]);
+        let before = navigation.viewport.row_offset;
+        assert!(before > 0);
+
+        navigation.clamp_viewport(&contents);
+        assert_eq!(navigation.viewport.row_offset, before);
+    }
+
+    #[test]
+    fn lines_and_counts_of_a_mixed_buffer() {
+        let contents = EditingModel::from_text("plain\nnaïve café\n日本語\n");
+
+        assert_eq!(contents.iter_lines().collect::<Vec<_>>(), ["plain", "naïve café", "日本語"]);
+        assert_eq!((0..3).map(|row| contents.line_char_len(row)).collect::<Vec<_>>(), [5, 10, 3]);
+        assert_eq!(contents.char_count(), 5 + 10 + 3 + 3);
+        assert_eq!(contents.byte_count(), 5 + 12 + 9 + 3);
+    }
+
+    #[test]
+    fn a_bom_is_kept_out_of_the_text_but_comes_back_on_save() {
+        let with = EditingModel::from_text("\u{feff}first\nsecond");
+        let without = EditingModel::from_text("first\nsecond");
+
+        assert!(with.bom && !without.bom);
+        assert_eq!(with.line(0), "first");
+        assert_eq!(with.serialize(), "\u{feff}first\nsecond");
+        assert_eq!(without.serialize(), "first\nsecond");
+    }
+
+    fn round_trip(test: &str, on_disk: &str) -> (String, String) {
+        let scratch = Scratch::new(test);
+        let file_path = scratch.file("notes.txt", on_disk);
+        let (editor, _) = run_with(vec![file_path.clone()], Config::default(), vec![key(KeyCode::Char('x')), ctrl('s')]);
+        (on_screen(&editor), fs::read_to_string(file_path).unwrap())
+    }
+
+    #[test]
+    fn files_with_and_without_a_bom_save_the_way_they_came() {
+        let (screen, saved) = round_trip("bom_kept", "\u{feff}text");
+        assert!(!screen.contains('\u{feff}'));
+        assert!(screen.contains("xtext"));
+        assert_eq!(saved, "\u{feff}xtext");
+
+        let (_, saved) = round_trip("bom_none", "text");
+        assert_eq!(saved, "xtext");
+    }
+
+    fn kept_after_four(horizon: usize) -> Vec<InputEvent> {
+        let mut history = KeyHistory::with_horizon(horizon, time::Instant::now);
+        for c in ['a', 'b', 'c', 'd'] {
+            history.record(&InputEvent::Char(c));
+        }
+        history.events.iter().map(|KeyEvent(event, _)| event.clone()).collect()
+    }
+
+    #[test]
+    fn the_key_history_drops_the_oldest_past_its_horizon() {
+        assert_eq!(kept_after_four(0), []);
+        assert_eq!(kept_after_four(1), [InputEvent::Char('d')]);
+        assert_eq!(kept_after_four(3), [InputEvent::Char('b'), InputEvent::Char('c'), InputEvent::Char('d')]);
+        assert_eq!(kept_after_four(10).len(), 4);
+    }
+
+    #[test]
+    fn a_hidden_key_history_still_records() {
+        let config = Config { show_key_history: false, ..Config::default() };
+        let (editor, _) = run_with(vec![], config, vec![key(KeyCode::F(5))]);
+
+        assert!(editor.key_history.to_string().contains("F(5)"));
+        assert!(!on_screen(&editor).contains("F(5)"));
+
+        let config = Config { show_key_history: true, ..Config::default() };
+        let (editor, _) = run_with(vec![], config, vec![key(KeyCode::F(5))]);
+        assert!(on_screen(&editor).contains("F(5)"));
+    }
+
+    fn rings(cmd: &elm::Cmd<Message>) -> bool {
+        match cmd {
+            elm::Cmd::Dispatch(Message::Bell) => true,
+            elm::Cmd::AndThen(then, first)    => rings(first) || rings(then),
+            elm::Cmd::Batch(cmds)             => cmds.iter().any(rings),
+            _otherwise                        => false,
+        }
+    }
+
+    #[test]
+    fn moving_past_the_edge_of_the_buffer_rings_the_bell() {
+        let config = Config { bell: Bell::Audible, ..Config::default() };
+        let (mut editor, _) = run_with(vec![], config, typed("ab\ncd").collect());
+
+        assert!(!rings(&editor.update(&key(KeyCode::Up).into())));
+        let cmd = editor.update(&key(KeyCode::Up).into());
+        assert!(rings(&cmd));
+
+        settle(&mut editor, cmd);
+        assert!(editor.ringing);
+        let mut frame = tui::CommandBuffer::default();
+        editor.view(&mut frame).unwrap();
+        assert!(frame.take().unwrap().ends_with(b"\x07"));
+    }
+
+    #[test]
+    fn j_on_the_last_line_rings_the_bell_with_or_without_a_count() {
+        for script in ["Gj", "G5j"] {
+            let (editor, cmd) = last_key(script);
+
+            assert!(rings(&cmd), "{}", script);
+            assert_eq!(editor.cursor().row, 2, "{}", script);
+            assert_eq!(editor.count, None, "{}", script);
+        }
+    }
+
+    #[test]
+    fn the_visual_bell_flashes_the_status_bar_out_of_reverse_video() {
+        let config = Config { bell: Bell::Visual, ..Config::default() };
+        let (mut editor, _) = run_with(vec![], config, vec![]);
+        let reverse = |editor: &Editor| {
+            let mut frame = tui::CommandBuffer::default();
+            frame.resize(80, 10);
+            editor.render_status_bar(&mut frame).unwrap();
+            let mut reversed = vec![];
+            reversed.queue(style::SetAttributes(style::Attributes::from(style::Attribute::Reverse))).unwrap();
+            contains(&frame.take().unwrap(), &reversed)
+        };
+        assert!(reverse(&editor));
+
+        send(&mut editor, key(KeyCode::Left).into());
+        assert!(editor.ringing);
+        assert!(!reverse(&editor));
+    }
+
+    #[test]
+    fn save_as_moves_the_buffer_to_the_new_file() {
+        let scratch = Scratch::new("save_as");
+        let file_path = scratch.file("notes.txt", "one");
+        let copy = scratch.0.join("copy.txt");
+        let (mut editor, _) = run_with(vec![file_path.clone()], Config::default(), typed("x").collect());
+
+        send(&mut editor, Message::SaveAs(copy.clone()));
+        assert_eq!(editor.buffer().file_path.as_deref(), Some(copy.as_path()));
+        assert_eq!(editor.buffer().name, "copy.txt");
+        assert!(!editor.buffer().modified);
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "xone");
+
+        send(&mut editor, key(KeyCode::Char('y')).into());
+        send(&mut editor, ctrl('s').into());
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "xyone");
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "one");
+    }
+
+    #[test]
+    fn save_as_into_a_missing_directory_says_so_and_keeps_the_old_name() {
+        let scratch = Scratch::new("save_as_missing");
+        let file_path = scratch.file("notes.txt", "one");
+        let nowhere = scratch.0.join("missing").join("copy.txt");
+        let (mut editor, _) = run_with(vec![file_path.clone()], Config::default(), typed("x").collect());
+
+        send(&mut editor, Message::SaveAs(nowhere));
+        assert!(editor.message.text().starts_with("Save failed: "), "{}", editor.message.text());
+        assert_eq!(editor.buffer().file_path.as_deref(), Some(file_path.as_path()));
+        assert_eq!(editor.buffer().name, "notes.txt");
+        assert!(editor.buffer().modified);
+    }
 }
@@ -0,0 +1,443 @@
+use std::{iter, ops::Index, sync::Arc};
+
+/* The lines of a buffer, kept in an implicit treap: a binary tree
+   ordered by position, balanced in expectation by random priorities.
+   Indexing, inserting and removing a line are all O(log n), where a
+   Vec<String> has to shift everything after the edit.
+
+   It mimics the parts of Vec's API the editor uses so it can stand
+   in for one, except that lines are read as &str.
+
+   A rope made with indexed keeps the text it was given whole, and its
+   lines point into it until one is written to and gets a String of
+   its own. Opening a file that way costs a pass to find where lines
+   start and a node per line, but no copy of any line; the text stays
+   in memory, though, however much of it gets edited away. */
+pub struct Rope {
+    root:   Link,
+    seed:   u64,
+    source: Option<Arc<Source>>,
+}
+
+type Link = Option<Box<Node>>;
+
+/* Text a rope was made from, and where each of its lines starts. */
+struct Source {
+    text:   String,
+    starts: Vec<usize>,
+    /* Where the last line ends, short of a final line break. */
+    end:    usize,
+}
+
+impl Source {
+    fn line(&self, index: usize) -> &str {
+        let end = self.starts.get(index + 1).map_or(self.end, |start| start - 1);
+        let line = &self.text[self.starts[index]..end];
+        line.strip_suffix('\r').unwrap_or(line)
+    }
+}
+
+enum Line {
+    Owned(String),
+    Indexed(usize),
+}
+
+struct Node {
+    line:     Line,
+    priority: u64,
+    size:     usize,
+    left:     Link,
+    right:    Link,
+}
+
+impl Node {
+    fn new(line: Line, priority: u64) -> Box<Self> {
+        Box::new(Self { line, priority, size: 1, left: None, right: None })
+    }
+
+    fn update(&mut self) {
+        self.size = 1 + size(&self.left) + size(&self.right);
+    }
+}
+
+fn size(link: &Link) -> usize {
+    link.as_ref().map_or(0, |node| node.size)
+}
+
+/* All of left goes before all of right. */
+fn merge(left: Link, right: Link) -> Link {
+    match (left, right) {
+        (None, right) => right,
+        (left, None)  => left,
+        (Some(mut left), Some(mut right)) => {
+            if left.priority > right.priority {
+                left.right = merge(left.right.take(), Some(right));
+                left.update();
+                Some(left)
+            } else {
+                right.left = merge(Some(left), right.left.take());
+                right.update();
+                Some(right)
+            }
+        }
+    }
+}
+
+/* The first count lines, and the rest. */
+fn split(link: Link, count: usize) -> (Link, Link) {
+    match link {
+        None => (None, None),
+        Some(mut node) => {
+            let left_size = size(&node.left);
+            if count <= left_size {
+                let (left, right) = split(node.left.take(), count);
+                node.left = right;
+                node.update();
+                (left, Some(node))
+            } else {
+                let (left, right) = split(node.right.take(), count - left_size - 1);
+                node.right = left;
+                node.update();
+                (Some(node), right)
+            }
+        }
+    }
+}
+
+impl Rope {
+    pub fn new() -> Self {
+        Self { root: None, seed: 0x2545_f491_4f6c_dd1d, source: None }
+    }
+
+    /* The lines of text, split the way EditingModel::from_text splits
+       them, without copying any. */
+    pub fn indexed(text: String) -> Self {
+        let end = text.strip_suffix('\n').map_or(text.len(), str::len);
+        let starts = iter::once(0)
+            .chain(text[..end].match_indices('\n').map(|(at, _)| at + 1))
+            .collect::<Vec<_>>();
+
+        let mut rope = Self::build((0..starts.len()).map(Line::Indexed));
+        rope.source = Some(Arc::new(Source { text, starts, end }));
+        rope
+    }
+
+    /* Builds the treap in one left to right pass, keeping the right
+       spine on a stack, instead of n separate insertions. */
+    fn build<I: Iterator<Item = Line>>(lines: I) -> Self {
+        let mut rope = Rope::new();
+        let mut spine: Vec<Box<Node>> = vec![];
+
+        for line in lines {
+            let mut node = Node::new(line, rope.next_priority());
+            let mut last: Link = None;
+
+            while spine.last().is_some_and(|top| top.priority < node.priority) {
+                if let Some(mut top) = spine.pop() {
+                    top.right = last;
+                    top.update();
+                    last = Some(top);
+                }
+            }
+
+            node.left = last;
+            spine.push(node);
+        }
+
+        let mut root: Link = None;
+        while let Some(mut top) = spine.pop() {
+            top.right = root;
+            top.update();
+            root = Some(top);
+        }
+
+        rope.root = root;
+        rope
+    }
+
+    fn text<'a>(&'a self, line: &'a Line) -> &'a str {
+        match (line, &self.source) {
+            (Line::Owned(line), _)               => line,
+            (Line::Indexed(index), Some(source)) => source.line(*index),
+            (Line::Indexed(_), None)             => "",
+        }
+    }
+
+    fn next_priority(&mut self) -> u64 {
+        /* xorshift64; good enough to keep the tree balanced. */
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        self.seed
+    }
+
+    pub fn len(&self) -> usize { size(&self.root) }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        let mut link = &self.root;
+        let mut index = index;
+
+        while let Some(node) = link {
+            let left_size = size(&node.left);
+            if index < left_size {
+                link = &node.left;
+            } else if index == left_size {
+                return Some(self.text(&node.line));
+            } else {
+                index -= left_size + 1;
+                link = &node.right;
+            }
+        }
+
+        None
+    }
+
+    /* Gives the line a String of its own first, if it has none. */
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut String> {
+        let source = self.source.clone();
+        let mut link = &mut self.root;
+        let mut index = index;
+
+        while let Some(node) = link {
+            let left_size = size(&node.left);
+            if index < left_size {
+                link = &mut node.left;
+            } else if index == left_size {
+                if let (Line::Indexed(index), Some(source)) = (&node.line, &source) {
+                    node.line = Line::Owned(source.line(*index).to_owned());
+                }
+                return match &mut node.line {
+                    Line::Owned(line) => Some(line),
+                    Line::Indexed(_)  => None,
+                };
+            } else {
+                index -= left_size + 1;
+                link = &mut node.right;
+            }
+        }
+
+        None
+    }
+
+    /* get_mut for a line that must be there, the way indexing a Vec
+       would be; a &mut str would not do, lines change length. */
+    pub fn line_mut(&mut self, index: usize) -> &mut String {
+        self.get_mut(index).expect("line index out of bounds")
+    }
+
+    pub fn insert(&mut self, index: usize, line: String) {
+        assert!(index <= self.len(), "insertion index {} is out of bounds", index);
+
+        let node = Node::new(Line::Owned(line), self.next_priority());
+        let (left, right) = split(self.root.take(), index);
+        self.root = merge(merge(left, Some(node)), right);
+    }
+
+    pub fn remove(&mut self, index: usize) -> String {
+        assert!(index < self.len(), "removal index {} is out of bounds", index);
+
+        let (left, rest) = split(self.root.take(), index);
+        let (middle, right) = split(rest, 1);
+        self.root = merge(left, right);
+
+        middle.map_or_else(String::new, |node| self.text(&node.line).to_owned())
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        let mut iter = Iter { rope: self, stack: vec![] };
+        iter.descend(&self.root);
+        iter
+    }
+
+    pub fn join(&self, separator: &str) -> String {
+        let mut joined = String::new();
+        for (index, line) in self.iter().enumerate() {
+            if index > 0 {
+                joined.push_str(separator);
+            }
+            joined.push_str(line);
+        }
+        joined
+    }
+}
+
+impl Default for Rope {
+    fn default() -> Self { Self::new() }
+}
+
+impl FromIterator<String> for Rope {
+    fn from_iter<I: IntoIterator<Item = String>>(lines: I) -> Self {
+        Self::build(lines.into_iter().map(Line::Owned))
+    }
+}
+
+impl Index<usize> for Rope {
+    type Output = str;
+
+    fn index(&self, index: usize) -> &str {
+        self.get(index).expect("line index out of bounds")
+    }
+}
+
+
+pub struct Iter<'a> {
+    rope:  &'a Rope,
+    stack: Vec<&'a Node>,
+}
+
+impl <'a> Iter<'a> {
+    fn descend(&mut self, mut link: &'a Link) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl <'a> Iterator for Iter<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let node = self.stack.pop()?;
+        self.descend(&node.right);
+        Some(self.rope.text(&node.line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* How many lines have a String of their own. */
+    fn owned(rope: &Rope) -> usize {
+        fn count(link: &Link) -> usize {
+            link.as_ref().map_or(0, |node| {
+                usize::from(matches!(node.line, Line::Owned(_))) + count(&node.left) + count(&node.right)
+            })
+        }
+        count(&rope.root)
+    }
+
+    fn points_into(line: &str, text: &str) -> bool {
+        let range = text.as_bytes().as_ptr_range();
+        range.contains(&line.as_ptr()) || line.is_empty()
+    }
+
+    #[test]
+    fn indexed_lines_are_split_like_owned_ones() {
+        for text in ["", "\n", "one", "one\n", "one\r\ntwo\r\n", "one\n\nthree", "日本語\nテスト\n"] {
+            let indexed = Rope::indexed(text.to_owned());
+            let body = text.strip_suffix('\n').unwrap_or(text);
+            let split = body.split('\n')
+                .map(|line| line.strip_suffix('\r').unwrap_or(line).to_owned())
+                .collect::<Vec<_>>();
+
+            assert_eq!(indexed.iter().collect::<Vec<_>>(), split, "{:?}", text);
+            assert_eq!(indexed.len(), split.len(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn scrolling_through_an_indexed_rope_copies_no_lines() {
+        let text = (0..10_000).map(|row| format!("line {}\n", row)).collect::<String>();
+        let rope = Rope::indexed(text);
+        let source = &rope.source.as_ref().unwrap().text;
+
+        for top in (0..rope.len()).step_by(37) {
+            for row in top..(top + 40).min(rope.len()) {
+                let line = &rope[row];
+                assert_eq!(line, format!("line {}", row));
+                assert!(points_into(line, source));
+            }
+        }
+        assert!(rope.iter().all(|line| points_into(line, source)));
+        assert_eq!(owned(&rope), 0);
+    }
+
+    #[test]
+    fn writing_to_an_indexed_line_copies_only_that_one() {
+        let mut rope = Rope::indexed("one\ntwo\nthree\n".to_owned());
+        rope.line_mut(1).push_str(" and a half");
+
+        assert_eq!(owned(&rope), 1);
+        assert_eq!(rope.join("\n"), "one\ntwo and a half\nthree");
+        assert_eq!(rope.source.as_ref().unwrap().text, "one\ntwo\nthree\n");
+    }
+
+    #[test]
+    fn indexed_lines_can_be_inserted_around_and_removed() {
+        let mut rope = Rope::indexed("one\ntwo\nthree".to_owned());
+        rope.insert(1, "one and a half".to_owned());
+        assert_eq!(rope.remove(3), "three");
+        rope.insert(3, "four".to_owned());
+
+        assert_eq!(rope.join("\n"), "one\none and a half\ntwo\nfour");
+        assert_eq!(owned(&rope), 2);
+    }
+
+    /* The same edits on a Vec, for comparison. */
+    #[test]
+    fn edits_land_where_they_would_in_a_vec() {
+        let mut rope = (0..100).map(|n| n.to_string()).collect::<Rope>();
+        let mut lines = (0..100).map(|n| n.to_string()).collect::<Vec<_>>();
+        let mut seed = 7_usize;
+
+        for step in 0..1000 {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            let at = (seed >> 33) % (lines.len() + 1);
+            if step % 3 == 0 && at < lines.len() {
+                assert_eq!(rope.remove(at), lines.remove(at));
+            } else {
+                rope.insert(at, format!("new {}", step));
+                lines.insert(at, format!("new {}", step));
+            }
+        }
+
+        assert_eq!(rope.len(), lines.len());
+        assert_eq!(rope.iter().collect::<Vec<_>>(), lines);
+        assert!((0..lines.len()).all(|index| rope[index] == lines[index]));
+    }
+
+    fn depth(link: &Link) -> usize {
+        link.as_ref().map_or(0, |node| 1 + depth(&node.left).max(depth(&node.right)))
+    }
+
+    /* An edit walks one path from the root, so as long as the tree
+       stays about log n deep, so does the work of an edit. */
+    #[test]
+    fn the_tree_stays_logarithmically_deep_as_the_file_grows() {
+        for lines in [1_000, 10_000, 100_000, 1_000_000] {
+            let mut rope = Rope::indexed("x\n".repeat(lines));
+            for at in (0..1000).map(|step| step * lines / 1000) {
+                rope.insert(at, String::new());
+                rope.remove(at / 2);
+            }
+
+            let bound = 4 * lines.ilog2() as usize;
+            assert!(depth(&rope.root) <= bound, "{} deep at {} lines", depth(&rope.root), lines);
+        }
+    }
+
+    /* Edit time, not just depth: timings, so ignored by default.
+       Run with --ignored to see them. */
+    #[test]
+    #[ignore]
+    fn edits_in_the_middle_take_about_as_long_in_a_big_file_as_a_small_one() {
+        let timed = |lines: usize| {
+            let mut rope = Rope::indexed("the quick brown fox\n".repeat(lines));
+            let started = std::time::Instant::now();
+            for step in 0..10_000 {
+                let at = lines / 2 + step % 100;
+                rope.line_mut(at).insert(3, '!');
+                rope.insert(at, "new".to_owned());
+                rope.remove(at + 1);
+            }
+            started.elapsed()
+        };
+
+        let small = timed(1_000);
+        let big = timed(1_000_000);
+        eprintln!("1k lines {:?}, 1M lines {:?}", small, big);
+        assert!(big < small * 10, "{:?} against {:?}", big, small);
+    }
+}
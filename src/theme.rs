@@ -0,0 +1,64 @@
+use crossterm::style::Color;
+
+/* The colours the editor draws with. The status bar and search matches
+   are drawn in reverse video, so their colour ends up as the
+   background, behind text in the terminal's own background colour. */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub text:          Color,
+    pub line_number:   Color,
+    pub divider:       Color,
+    pub status_bar:    Color,
+    pub cursor_line:   Color,
+    pub search_match:  Color,
+    pub end_of_buffer: Color,
+    /* The markers list mode puts on tabs and trailing spaces. */
+    pub whitespace:    Color,
+    /* The bracket under the cursor and its partner, in bold. */
+    pub bracket:       Color,
+}
+
+impl Theme {
+    pub fn default_dark() -> Self {
+        Self {
+            text:          Color::Reset,
+            line_number:   Color::DarkGrey,
+            divider:       Color::DarkGrey,
+            status_bar:    Color::Grey,
+            cursor_line:   Color::AnsiValue(236),
+            search_match:  Color::Yellow,
+            end_of_buffer: Color::DarkGrey,
+            whitespace:    Color::DarkGrey,
+            bracket:       Color::Cyan,
+        }
+    }
+
+    pub fn default_light() -> Self {
+        Self {
+            text:          Color::Reset,
+            line_number:   Color::Grey,
+            divider:       Color::Grey,
+            status_bar:    Color::DarkGrey,
+            cursor_line:   Color::AnsiValue(254),
+            search_match:  Color::DarkYellow,
+            end_of_buffer: Color::Grey,
+            whitespace:    Color::Grey,
+            bracket:       Color::DarkCyan,
+        }
+    }
+
+    /* The name config.toml picks a theme by. */
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dark"     => Some(Self::default_dark()),
+            "light"    => Some(Self::default_light()),
+            _otherwise => None,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::default_dark()
+    }
+}
@@ -1,15 +1,18 @@
-use std::{io, cell::RefCell, cell::RefMut};
+use std::{io, cell::RefCell, mem, panic};
 use std::io::Write;
+#[cfg(test)]
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time;
 
-use crossterm::{Command, event, terminal, QueueableCommand};
+use crossterm::{cursor, event, style, terminal, QueueableCommand};
 
 use crate::elm;
 
 
 pub fn request_terminal_size<F, Msg: Clone>(to_msg: F) -> elm::Cmd<Msg> 
 where
-    F: FnOnce(u16, u16) -> Msg + 'static
+    F: FnOnce(u16, u16) -> Msg + Send + 'static
 {
     elm::Cmd::suspend(|| {
         let (width, height) = terminal::size()?;
@@ -19,63 +22,643 @@ where
 
 impl elm::Host for Screen {
     type Event = event::Event;
-    type Display = Self;
+    type Frame = CommandBuffer;
 
-    /* I dunno, man. */
-    fn get_display(&self) -> &Self::Display { &self }
-
-    fn poll_events(&self) -> io::Result<Self::Event> {
-        if event::poll(time::Duration::from_millis(5427))? {
-            event::read()
+    fn poll_events(&self) -> io::Result<Option<Self::Event>> {
+        if event::poll(self.poll_timeout)? {
+            event::read().map(Some)
         } else {
-            Err(io::Error::new(io::ErrorKind::TimedOut, "Timed out waiting for the world."))
+            Ok(None)
         }
     }
 
-    fn flush(&self, display: &Self::Display) -> io::Result<()> {
-        display.commit()
+    fn wait_for_event(&self, timeout: time::Duration) -> io::Result<bool> {
+        event::poll(timeout)
+    }
+
+    fn poll_timeout(&self) -> Option<time::Duration> { Some(self.poll_timeout) }
+
+    fn flush(&self, frame: &mut Self::Frame) -> io::Result<()> {
+        self.commit(frame)
+    }
+}
+
+/* One screen position: the char on it and how it is drawn. */
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    c:     char,
+    style: style::ContentStyle,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self { c: ' ', style: style::ContentStyle::new() }
     }
 }
 
-pub struct RenderingBuffer<'a>(RefMut<'a, dyn io::Write>);
+/* A frame drawn into a grid of cells rather than straight at the
+   terminal. take compares it with the frame before and comes up with
+   only what it takes to turn one into the other, so a screen that did
+   not change costs nothing to draw again.
+
+   Text that runs off the right edge is dropped, \r and \n move the
+   pen the way a terminal would. The pen carries colours that
+   set_background and reset_colors change; print_styled draws on top
+   of them without changing them. */
+#[derive(Default)]
+pub struct CommandBuffer {
+    columns:  usize,
+    rows:     usize,
+    cells:    Vec<Cell>,
+    previous: Option<Vec<Cell>>,
+    pen:      style::ContentStyle,
+    at:       (usize, usize),
+    cursor:   Option<(usize, usize)>,
+    shown:    Option<(usize, usize)>,
+    shape:    Option<CursorShape>,
+    shaped:   Option<CursorShape>,
+    bell:     bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CursorShape {
+    Block,
+    Underline,
+    Bar,
+}
+
+impl CommandBuffer {
+    /* Sizes the grid to the screen. A frame of a different size from
+       the one before is drawn from scratch. */
+    pub fn resize(&mut self, columns: usize, rows: usize) -> &mut Self {
+        if (columns, rows) != (self.columns, self.rows) {
+            self.columns = columns;
+            self.rows = rows;
+            self.cells = vec![Cell::default(); columns * rows];
+            self.previous = None;
+        }
+        self
+    }
+
+    /* Row as it was in the frame before, rather than drawn again.
+       false when there is no frame before to take it from. */
+    pub fn keep_row(&mut self, row: usize) -> bool {
+        let (Some(previous), true) = (&self.previous, row < self.rows) else {
+            return false;
+        };
+
+        let columns = row * self.columns..(row + 1) * self.columns;
+        self.cells[columns.clone()].copy_from_slice(&previous[columns]);
+        true
+    }
+
+    pub fn move_to(&mut self, column: usize, row: usize) -> io::Result<&mut Self> {
+        self.at = (column, row);
+        Ok(self)
+    }
 
-impl <'a> RenderingBuffer<'a> {
-    fn new(cell: &'a RefCell<dyn io::Write>) -> Self {
-        Self(cell.borrow_mut())
+    pub fn print(&mut self, text: &str) -> io::Result<&mut Self> {
+        self.print_styled(text, style::ContentStyle::new())
     }
 
-    pub fn queue(&mut self, command: impl Command) -> io::Result<&mut (dyn io::Write + 'a)> {
-        self.0.queue(command)
+    pub fn print_styled(&mut self, text: &str, style: style::ContentStyle) -> io::Result<&mut Self> {
+        let mut look = self.pen;
+        if style.foreground_color.is_some() {
+            look.foreground_color = style.foreground_color;
+        }
+        if style.background_color.is_some() {
+            look.background_color = style.background_color;
+        }
+        look.attributes.extend(style.attributes);
+
+        for c in text.chars() {
+            let (column, row) = self.at;
+            match c {
+                '\r' => self.at.0 = 0,
+                '\n' => self.at.1 += 1,
+                c    => {
+                    if column < self.columns && row < self.rows {
+                        self.cells[row * self.columns + column] = Cell { c, style: look };
+                    }
+                    self.at.0 += 1;
+                }
+            }
+        }
+
+        Ok(self)
     }
+
+    pub fn set_background(&mut self, color: style::Color) -> io::Result<&mut Self> {
+        self.pen.background_color = Some(color);
+        Ok(self)
+    }
+
+    pub fn reset_colors(&mut self) -> io::Result<&mut Self> {
+        self.pen = style::ContentStyle::new();
+        Ok(self)
+    }
+
+    pub fn hide_cursor(&mut self) -> io::Result<&mut Self> {
+        self.cursor = None;
+        Ok(self)
+    }
+
+    /* The cursor goes where the pen is, once the frame is out. */
+    pub fn show_cursor(&mut self) -> io::Result<&mut Self> {
+        self.cursor = Some(self.at);
+        Ok(self)
+    }
+
+    /* Left alone, the cursor keeps whatever shape it had last. */
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) -> io::Result<&mut Self> {
+        self.shape = Some(shape);
+        Ok(self)
+    }
+
+    /* Beeps once the frame is out. */
+    pub fn ring_bell(&mut self) -> io::Result<&mut Self> {
+        self.bell = true;
+        Ok(self)
+    }
+
+    /* The commands that turn the last frame taken into this one. The
+       grid is blank again afterwards, ready for the next. */
+    pub fn take(&mut self) -> io::Result<Vec<u8>> {
+        let mut out = vec![];
+        let mut drawn = style::ContentStyle::new();
+
+        let changed = |index: usize, cell: &Cell| match &self.previous {
+            Some(previous) => previous[index] != *cell,
+            None           => *cell != Cell::default(),
+        };
+
+        if self.previous.is_none() {
+            out.queue(terminal::Clear(terminal::ClearType::All))?;
+        }
+
+        for row in 0..self.rows {
+            let mut column = 0;
+            while column < self.columns {
+                let index = row * self.columns + column;
+                if !changed(index, &self.cells[index]) {
+                    column += 1;
+                    continue;
+                }
+
+                out.queue(cursor::MoveTo(column as u16, row as u16))?;
+                while column < self.columns && changed(row * self.columns + column, &self.cells[row * self.columns + column]) {
+                    let cell = self.cells[row * self.columns + column];
+                    if cell.style != drawn {
+                        queue_style(&mut out, &cell.style)?;
+                        drawn = cell.style;
+                    }
+                    out.queue(style::Print(cell.c))?;
+                    column += 1;
+                }
+            }
+        }
+
+        if drawn != style::ContentStyle::new() {
+            out.queue(style::SetAttribute(style::Attribute::Reset))?;
+        }
+
+        /* Drawing moves the terminal's cursor about, so it is hidden
+           while that happens and put back after, even if it was meant
+           to stay where it was. */
+        let drew = !out.is_empty();
+        if drew && self.shown.is_some() {
+            let mut hidden = vec![];
+            hidden.queue(cursor::Hide)?;
+            out.splice(0..0, hidden);
+        }
+        if drew || self.cursor != self.shown {
+            match self.cursor {
+                Some((column, row)) => {
+                    out.queue(cursor::MoveTo(column as u16, row as u16))?;
+                    if self.shown.is_none() || drew {
+                        out.queue(cursor::Show)?;
+                    }
+                }
+                None =>
+                    if self.shown.is_some() {
+                        out.queue(cursor::Hide)?;
+                    },
+            }
+        }
+
+        if let Some(shape) = self.shape.filter(|shape| Some(*shape) != self.shaped) {
+            out.queue(match shape {
+                CursorShape::Block     => cursor::SetCursorStyle::SteadyBlock,
+                CursorShape::Underline => cursor::SetCursorStyle::SteadyUnderScore,
+                CursorShape::Bar       => cursor::SetCursorStyle::SteadyBar,
+            })?;
+            self.shaped = Some(shape);
+        }
+
+        if mem::take(&mut self.bell) {
+            out.queue(style::Print('\x07'))?;
+        }
+
+        self.shown = self.cursor;
+        self.shape = None;
+        self.previous = Some(mem::replace(&mut self.cells, vec![Cell::default(); self.columns * self.rows]));
+        self.pen = style::ContentStyle::new();
+        self.at = (0, 0);
+        self.cursor = None;
+
+        Ok(out)
+    }
+}
+
+/* Starts over from plain and sets what style asks for. */
+fn queue_style(out: &mut Vec<u8>, style: &style::ContentStyle) -> io::Result<()> {
+    out.queue(style::SetAttribute(style::Attribute::Reset))?;
+    if let Some(foreground) = style.foreground_color {
+        out.queue(style::SetForegroundColor(foreground))?;
+    }
+    if let Some(background) = style.background_color {
+        out.queue(style::SetBackgroundColor(background))?;
+    }
+    if !style.attributes.is_empty() {
+        out.queue(style::SetAttributes(style.attributes))?;
+    }
+    Ok(())
 }
 
+pub const DEFAULT_POLL_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
 pub struct Screen {
-    inner: Box<RefCell<dyn io::Write>>,
+    inner:        Box<RefCell<dyn io::Write>>,
+    poll_timeout: time::Duration,
+    raw:          bool,
+    mouse:        bool,
+    alternate:    bool,
 }
 
 impl Screen {
     pub fn attach<W: Write + 'static>(out: W) -> io::Result<Self> {
         Ok(Self {
-            inner: Box::new(RefCell::new(out)),
+            inner:        Box::new(RefCell::new(out)),
+            poll_timeout: DEFAULT_POLL_TIMEOUT,
+            raw:          false,
+            mouse:        false,
+            alternate:    false,
         })
     }
 
-    pub fn enter_raw_mode(self) -> io::Result<Self> {
+    /* A screen that renders into memory rather than a terminal; the
+       buffer fills up with exactly what would have been written. */
+    #[cfg(test)]
+    pub fn attach_string() -> io::Result<(Self, Rc<RefCell<Vec<u8>>>)> {
+        let output = Rc::new(RefCell::new(vec![]));
+        let screen = Self::attach(SharedBuffer(output.clone()))?;
+        Ok((screen, output))
+    }
+
+    /* How long the loop waits for input before it gives up and goes
+       round, even with no timer due before then. */
+    pub fn with_poll_timeout(mut self, poll_timeout: time::Duration) -> Self {
+        self.poll_timeout = poll_timeout;
+        self
+    }
+
+    /* Raw mode also asks for focus changes and bracketed pastes, which
+       the terminal only reports when told to. */
+    pub fn enter_raw_mode(mut self) -> io::Result<Self> {
         terminal::enable_raw_mode()?;
+        ALTERED.store(true, Ordering::SeqCst);
+        self.inner.borrow_mut()
+            .queue(event::EnableFocusChange)?
+            .queue(event::EnableBracketedPaste)?
+            .flush()?;
+        self.raw = true;
         Ok(self)
     }
 
-    pub fn rendering_buffer(&self) -> RenderingBuffer {
-        RenderingBuffer::new(&self.inner)
+    /* Mouse events are only reported once captured, which also takes
+       over the terminal's own selection. */
+    pub fn enable_mouse(mut self) -> io::Result<Self> {
+        self.inner.borrow_mut()
+            .queue(event::EnableMouseCapture)?
+            .flush()?;
+        self.mouse = true;
+        Ok(self)
+    }
+
+    /* Draws on a screen of its own, so that whatever the shell showed
+       before is still there, scrollback and all, once the editor is
+       gone. */
+    pub fn enter_alternate_screen(mut self) -> io::Result<Self> {
+        self.inner.borrow_mut()
+            .queue(terminal::EnterAlternateScreen)?
+            .flush()?;
+        ALTERNATE.store(true, Ordering::SeqCst);
+        self.alternate = true;
+        Ok(self)
+    }
+
+    /* Back to the shell's screen; once only, however often asked. */
+    pub fn leave_alternate_screen(&mut self) -> io::Result<()> {
+        let entered = mem::take(&mut self.alternate);
+        if entered && ALTERNATE.swap(false, Ordering::SeqCst) {
+            self.inner.borrow_mut()
+                .queue(terminal::LeaveAlternateScreen)?
+                .flush()?;
+        }
+        Ok(())
     }
 
-    pub fn commit(&self) -> io::Result<()> {
-        self.inner.borrow_mut().flush()
+    /* Writes out a frame in one go. */
+    pub fn commit(&self, frame: &mut CommandBuffer) -> io::Result<()> {
+        let mut out = self.inner.borrow_mut();
+        out.write_all(&frame.take()?)?;
+        out.flush()
     }
 }
 
 impl Drop for Screen {
     fn drop(&mut self) {
-        terminal::disable_raw_mode().expect("Unable!")
+        let _ = self.leave_alternate_screen();
+
+        /* A screen that never left cooked mode, say one rendering
+           into memory, has nothing to restore but the mouse. */
+        if self.raw {
+            restore_terminal(&mut *self.inner.borrow_mut()).expect("Unable!")
+        } else if self.mouse {
+            let _ = self.inner.borrow_mut()
+                .queue(event::DisableMouseCapture)
+                .and_then(|out| out.flush());
+        }
+    }
+}
+
+/* Whether the terminal is in raw mode and the rest of it, and whether
+   on the alternate screen. Kept outside of Screen for the panic hook,
+   which has no Screen to ask. */
+static ALTERED: AtomicBool = AtomicBool::new(false);
+static ALTERNATE: AtomicBool = AtomicBool::new(false);
+
+/* Puts the terminal back the way the shell expects it: cooked, with a
+   cursor and nothing captured. Only the first call after
+   enter_raw_mode does anything, so Drop and the panic hook can both
+   have a go. */
+pub fn restore_terminal(out: &mut dyn Write) -> io::Result<()> {
+    if ALTERNATE.swap(false, Ordering::SeqCst) {
+        out.queue(terminal::LeaveAlternateScreen)?.flush()?;
+    }
+
+    if !ALTERED.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    out.queue(event::DisableMouseCapture)?
+        .queue(event::DisableFocusChange)?
+        .queue(event::DisableBracketedPaste)?
+        .queue(style::ResetColor)?
+        .queue(cursor::SetCursorStyle::DefaultUserShape)?
+        .queue(cursor::Show)?
+        .flush()?;
+    terminal::disable_raw_mode()
+}
+
+/* A panic would otherwise print its message into a raw terminal, in
+   which it comes out mangled, if it can be seen at all. The terminal
+   is restored first and the message printed after. */
+pub fn install_panic_hook() {
+    let report = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal(&mut io::stdout());
+        report(info);
+    }));
+}
+
+#[cfg(test)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for SharedBuffer {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(bytes)
     }
-}
\ No newline at end of file
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+/* Rendered output with the escape sequences taken out, leaving only
+   the text a terminal would have printed. Knows CSI sequences, which
+   is all crossterm queues for us, OSC strings, and two byte escapes. */
+#[cfg(test)]
+pub fn strip_ansi(output: &[u8]) -> String {
+    let text = String::from_utf8_lossy(output);
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            plain.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('[') => {
+                /* Parameters and intermediates up to a final byte in @..~ */
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                /* Until BEL, or ESC \ */
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _otherwise => (),
+        }
+    }
+
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::style::Stylize;
+
+    fn frame(buffer: &mut CommandBuffer, lines: &[&str]) {
+        for (row, line) in lines.iter().enumerate() {
+            buffer.move_to(0, row).unwrap().print(line).unwrap();
+        }
+    }
+
+    #[test]
+    fn the_same_frame_twice_draws_nothing_the_second_time() {
+        let mut buffer = CommandBuffer::default();
+        buffer.resize(10, 2);
+        frame(&mut buffer, &["hello", "world"]);
+        assert_eq!(strip_ansi(&buffer.take().unwrap()), "helloworld");
+
+        frame(&mut buffer, &["hello", "world"]);
+        assert!(buffer.take().unwrap().is_empty());
+    }
+
+    #[test]
+    fn only_cells_that_changed_are_drawn() {
+        let mut buffer = CommandBuffer::default();
+        buffer.resize(10, 2);
+        frame(&mut buffer, &["hello", "world"]);
+        buffer.take().unwrap();
+
+        frame(&mut buffer, &["hello", "wOrld"]);
+        assert_eq!(strip_ansi(&buffer.take().unwrap()), "O");
+
+        frame(&mut buffer, &["help", "wOrld"]);
+        assert_eq!(strip_ansi(&buffer.take().unwrap()), "p ");
+    }
+
+    #[test]
+    fn a_resize_draws_everything_again() {
+        let mut buffer = CommandBuffer::default();
+        buffer.resize(10, 2);
+        frame(&mut buffer, &["hello", "world"]);
+        buffer.take().unwrap();
+
+        buffer.resize(12, 2);
+        frame(&mut buffer, &["hello", "world"]);
+        let mut cleared = vec![];
+        cleared.queue(terminal::Clear(terminal::ClearType::All)).unwrap();
+        let output = buffer.take().unwrap();
+
+        assert!(output.starts_with(&cleared));
+        assert_eq!(strip_ansi(&output), "helloworld");
+    }
+
+    #[test]
+    fn kept_rows_are_not_drawn_again() {
+        let mut buffer = CommandBuffer::default();
+        buffer.resize(10, 2);
+        assert!(!buffer.keep_row(0));
+        frame(&mut buffer, &["hello", "world"]);
+        buffer.take().unwrap();
+
+        assert!(buffer.keep_row(0));
+        buffer.move_to(0, 1).unwrap().print("there").unwrap();
+        assert_eq!(strip_ansi(&buffer.take().unwrap()), "there");
+    }
+
+    #[test]
+    fn the_cursor_is_only_moved_when_it_moves() {
+        let mut buffer = CommandBuffer::default();
+        buffer.resize(10, 2);
+        buffer.move_to(3, 1).unwrap().show_cursor().unwrap();
+        assert!(!buffer.take().unwrap().is_empty());
+
+        buffer.move_to(3, 1).unwrap().show_cursor().unwrap();
+        assert!(buffer.take().unwrap().is_empty());
+
+        buffer.move_to(4, 1).unwrap().show_cursor().unwrap();
+        let mut moved = vec![];
+        moved.queue(cursor::MoveTo(4, 1)).unwrap();
+        assert_eq!(buffer.take().unwrap(), moved);
+    }
+
+    #[test]
+    fn stripping_leaves_only_the_printed_text() {
+        let output = "\x1b[2J\x1b[1;1Hplain \x1b[38;5;9mred\x1b[0m \x1b]0;title\x07\x1b]2;too\x1b\\café\x1b7 日本";
+
+        assert_eq!(strip_ansi(output.as_bytes()), "plain red café 日本");
+        assert_eq!(strip_ansi(b""), "");
+    }
+
+    #[test]
+    fn a_string_screen_keeps_everything_committed_to_it() {
+        let (screen, output) = Screen::attach_string().unwrap();
+        let mut buffer = CommandBuffer::default();
+        buffer.resize(10, 2);
+        frame(&mut buffer, &["hello", "world"]);
+        screen.commit(&mut buffer).unwrap();
+        frame(&mut buffer, &["hello", "there"]);
+        screen.commit(&mut buffer).unwrap();
+
+        assert_eq!(strip_ansi(&output.borrow()), "helloworldthere");
+    }
+
+    #[test]
+    fn print_styled_sets_the_colour_prints_and_resets_in_that_order() {
+        let mut buffer = CommandBuffer::default();
+        buffer.resize(10, 1);
+        buffer.move_to(2, 0).unwrap()
+            .print_styled("hi", style::ContentStyle::new().with(style::Color::Red)).unwrap();
+
+        let mut expected = vec![];
+        expected
+            .queue(terminal::Clear(terminal::ClearType::All)).unwrap()
+            .queue(cursor::MoveTo(2, 0)).unwrap()
+            .queue(style::SetAttribute(style::Attribute::Reset)).unwrap()
+            .queue(style::SetForegroundColor(style::Color::Red)).unwrap()
+            .queue(style::Print('h')).unwrap()
+            .queue(style::Print('i')).unwrap()
+            .queue(style::SetAttribute(style::Attribute::Reset)).unwrap();
+        assert_eq!(buffer.take().unwrap(), expected);
+    }
+
+    #[test]
+    fn print_styled_leaves_the_pen_as_it_was() {
+        let mut buffer = CommandBuffer::default();
+        buffer.resize(10, 1);
+        buffer.set_background(style::Color::Blue).unwrap()
+            .print_styled("a", style::ContentStyle::new().with(style::Color::Red)).unwrap()
+            .print("b").unwrap();
+
+        let blue_only = Cell {
+            c:     'b',
+            style: style::ContentStyle { background_color: Some(style::Color::Blue), ..style::ContentStyle::new() },
+        };
+        assert!(buffer.cells[1] == blue_only);
+    }
+
+    #[test]
+    fn nothing_reaches_the_screen_until_the_frame_is_committed() {
+        let (screen, output) = Screen::attach_string().unwrap();
+        let mut buffer = CommandBuffer::default();
+        buffer.resize(10, 2);
+        buffer.move_to(0, 1).unwrap().print("second").unwrap();
+        buffer.move_to(0, 0).unwrap().print("first").unwrap();
+        buffer.ring_bell().unwrap();
+        assert!(output.borrow().is_empty());
+
+        screen.commit(&mut buffer).unwrap();
+        let committed = strip_ansi(&output.borrow());
+        assert_eq!(committed, "firstsecond\x07");
+
+        frame(&mut buffer, &["first", "third!"]);
+        screen.commit(&mut buffer).unwrap();
+        assert_eq!(strip_ansi(&output.borrow()), committed + "third!");
+    }
+
+    #[test]
+    fn restoring_twice_does_nothing_the_second_time() {
+        ALTERED.store(true, Ordering::SeqCst);
+        ALTERNATE.store(true, Ordering::SeqCst);
+
+        let mut first = vec![];
+        restore_terminal(&mut first).unwrap();
+        let mut left = vec![];
+        left.queue(terminal::LeaveAlternateScreen).unwrap();
+        let mut shown = vec![];
+        shown.queue(cursor::Show).unwrap();
+        assert!(first.starts_with(&left));
+        assert!(first.ends_with(&shown));
+
+        let mut second = vec![];
+        restore_terminal(&mut second).unwrap();
+        assert!(second.is_empty());
+    }
+}